@@ -0,0 +1,112 @@
+//! Procedural macros for moss_hecs_schedule, re-exported by the main crate
+//! behind the `macros` feature.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, GenericParam, Result};
+
+/// Implements the borrow traits of a system parameter for a struct with named
+/// fields which are system parameters themselves, such that the struct can be
+/// taken as a single parameter.
+///
+/// The struct must have exactly one lifetime parameter, and no other
+/// generics.
+///
+/// ```rust,ignore
+/// #[derive(SystemData)]
+/// struct PhysicsData<'a> {
+///     world: SubWorld<'a, (&'a Position, &'a mut Velocity)>,
+///     gravity: Read<'a, Gravity>,
+/// }
+/// ```
+#[proc_macro_derive(SystemData)]
+pub fn derive_system_data(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match derive(input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn derive(input: DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let vis = &input.vis;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "SystemData requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "SystemData can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut params = input.generics.params.iter();
+    let lifetime = match (params.next(), params.next()) {
+        (Some(GenericParam::Lifetime(param)), None) => &param.lifetime,
+        _ => {
+            return Err(Error::new_spanned(
+                &input.generics,
+                "SystemData requires exactly one lifetime parameter",
+            ))
+        }
+    };
+
+    let borrower = format_ident!("__{}Borrower", name, span = Span::call_site());
+    let names = fields.iter().map(|field| &field.ident).collect::<Vec<_>>();
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let krate = quote!(::moss_hecs_schedule);
+
+    Ok(quote! {
+        #[doc(hidden)]
+        #vis struct #borrower;
+
+        impl #krate::borrow::IntoBorrow for #name<'_> {
+            type Borrow = #borrower;
+        }
+
+        impl<#lifetime> #krate::borrow::ContextBorrow<#lifetime> for #borrower {
+            type Target = #name<#lifetime>;
+
+            fn borrow(
+                context: &#lifetime #krate::Context,
+            ) -> #krate::error::Result<Self::Target> {
+                Ok(#name {
+                    #(
+                        #names: <<#types as #krate::borrow::IntoBorrow>::Borrow
+                            as #krate::borrow::ContextBorrow<#lifetime>>::borrow(context)?,
+                    )*
+                })
+            }
+        }
+
+        impl<#lifetime> #krate::borrow::ComponentBorrow for #name<#lifetime> {
+            fn borrows() -> #krate::borrow::Borrows {
+                let mut borrows = #krate::borrow::Borrows::new();
+                #(
+                    borrows.extend(<#types as #krate::borrow::ComponentBorrow>::borrows());
+                )*
+                borrows
+            }
+
+            fn has<U: #krate::IntoAccess>() -> bool {
+                false #(|| <#types as #krate::borrow::ComponentBorrow>::has::<U>())*
+            }
+
+            fn has_dynamic(id: ::std::any::TypeId, exclusive: bool) -> bool {
+                false #(|| <#types as #krate::borrow::ComponentBorrow>::has_dynamic(id, exclusive))*
+            }
+        }
+    })
+}
@@ -1,8 +1,14 @@
-use std::any::{type_name, TypeId};
+use std::{
+    any::{type_name, TypeId},
+    marker::PhantomData,
+};
 
-use moss_hecs::{Fetch, Query};
+use moss_hecs::{Component, Entity, Fetch, Frame, Or, Query, Satisfies, With, Without};
 
-use crate::borrow::ComponentBorrow;
+use crate::{
+    borrow::{Borrows, ComponentBorrow},
+    Read, Write,
+};
 
 #[derive(Copy, Clone, PartialOrd, Ord, Eq, PartialEq)]
 /// Describes how a type is accessed.
@@ -10,6 +16,9 @@ pub struct Access {
     pub(crate) name: &'static str,
     pub(crate) id: TypeId,
     pub(crate) exclusive: bool,
+    pub(crate) data: Option<(TypeId, &'static str)>,
+    pub(crate) optional: bool,
+    pub(crate) world: Option<TypeId>,
 }
 
 impl std::fmt::Debug for Access {
@@ -29,6 +38,9 @@ impl Access {
             name,
             id,
             exclusive,
+            data: None,
+            optional: false,
+            world: None,
         }
     }
 
@@ -54,6 +66,117 @@ impl Access {
     pub fn name(&self) -> &'static str {
         self.name
     }
+
+    /// Returns the type and typename of the data which must be provided to
+    /// the execution for the access to succeed, if any
+    #[inline]
+    pub fn data(&self) -> Option<(TypeId, &'static str)> {
+        self.data
+    }
+
+    /// Returns true if the access succeeds even if its data is not provided,
+    /// e.g; for [MaybeRead](crate::borrow::MaybeRead)
+    #[inline]
+    pub fn optional(&self) -> bool {
+        self.optional
+    }
+
+    /// Returns the [World](crate::World) the access refers to, or None for
+    /// the main frame and other data
+    #[inline]
+    pub fn world(&self) -> Option<TypeId> {
+        self.world
+    }
+
+    /// Marks the access as referring to components of `world`
+    pub(crate) fn in_world(mut self, world: Option<TypeId>) -> Self {
+        self.world = world;
+        self
+    }
+
+    /// Marks the access as requiring data of type `T`
+    pub(crate) fn requires<T: 'static>(mut self) -> Self {
+        self.data = Some((TypeId::of::<T>(), type_name::<T>()));
+        self
+    }
+
+    /// Marks the access as accessing data of type `T` if it is provided
+    pub(crate) fn optionally_requires<T: 'static>(mut self) -> Self {
+        self.optional = true;
+        self.requires::<T>()
+    }
+
+    /// Creates a set of accesses which reads the data `T`. See [AccessSet].
+    pub fn reads<T: Component>() -> AccessSet {
+        AccessSet::new().reads::<T>()
+    }
+
+    /// Creates a set of accesses which writes the data `T`. See [AccessSet].
+    pub fn writes<T: Component>() -> AccessSet {
+        AccessSet::new().writes::<T>()
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// Manually declared accesses of a system, for systems whose true accesses
+/// are not visible from their arguments, such as systems using [AllAccess].
+///
+/// Added to a schedule by
+/// [ScheduleBuilder::add_system_with_access](crate::ScheduleBuilder::add_system_with_access).
+pub struct AccessSet {
+    borrows: Borrows,
+}
+
+impl AccessSet {
+    /// Creates an empty set of accesses
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the data `T` passed to the execution, like [Read]
+    pub fn reads<T: Component>(self) -> Self {
+        self.extend(<Read<T> as ComponentBorrow>::borrows())
+    }
+
+    /// Writes the data `T` passed to the execution, like [Write]
+    pub fn writes<T: Component>(self) -> Self {
+        self.extend(<Write<T> as ComponentBorrow>::borrows())
+    }
+
+    /// Reads the component `C` of entities in the world
+    pub fn reads_component<C: Component>(self) -> Self {
+        self.extend([
+            Access::of::<&C>(),
+            Access::of::<&Frame>().requires::<Frame>(),
+        ])
+    }
+
+    /// Writes the component `C` of entities in the world
+    pub fn writes_component<C: Component>(self) -> Self {
+        self.extend([
+            Access::of::<&mut C>(),
+            Access::of::<&Frame>().requires::<Frame>(),
+        ])
+    }
+
+    /// Returns the declared accesses
+    pub fn borrows(&self) -> &[Access] {
+        &self.borrows
+    }
+
+    pub(crate) fn extend(mut self, accesses: impl IntoIterator<Item = Access>) -> Self {
+        for access in accesses {
+            if !self.borrows.contains(&access) {
+                self.borrows.push(access);
+            }
+        }
+
+        self
+    }
+
+    pub(crate) fn into_borrows(self) -> Borrows {
+        self.borrows
+    }
 }
 
 /// Convert a type into the correspodning access.
@@ -77,6 +200,9 @@ impl<T: 'static> IntoAccess for &T {
             id: TypeId::of::<T>(),
             exclusive: false,
             name: type_name::<T>(),
+            data: None,
+            optional: false,
+            world: None,
         }
     }
 }
@@ -87,17 +213,288 @@ impl<T: 'static> IntoAccess for &mut T {
             id: TypeId::of::<T>(),
             exclusive: true,
             name: type_name::<T>(),
+            data: None,
+            optional: false,
+            world: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Describes a component type a subworld can access
+pub struct AccessInfo {
+    name: &'static str,
+    id: TypeId,
+    size: usize,
+    mutable: bool,
+}
+
+impl AccessInfo {
+    /// Describes an access of the component `C`
+    pub fn of<C: Component>(mutable: bool) -> Self {
+        Self {
+            name: type_name::<C>(),
+            id: TypeId::of::<C>(),
+            size: std::mem::size_of::<C>(),
+            mutable,
         }
     }
+
+    /// Returns the type name of the component
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the type of the component
+    pub fn id(&self) -> TypeId {
+        self.id
+    }
+
+    /// Returns the size of the component in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns true if the component can be accessed mutably
+    pub fn mutable(&self) -> bool {
+        self.mutable
+    }
+}
+
+/// Visits a component a subworld can access, see [VisitComponents]
+pub trait ComponentVisitor<C> {
+    /// Visits the component `C`, which may be accessed mutably if `mutable`
+    /// is true
+    fn visit(&mut self, mutable: bool);
+}
+
+/// Visits each component a subworld can access. The traits describing the
+/// components of a subworld, such as [ComponentAccess], are implemented on
+/// top of it.
+///
+/// Implemented for component references, [Option], the query filters,
+/// [ReadOnly] and tuples thereof.
+pub trait VisitComponents<V> {
+    /// Calls `visitor` with each component. The components are only visited
+    /// as mutable if `mutable` is true, which is false below [ReadOnly].
+    fn visit_components(visitor: &mut V, mutable: bool);
+}
+
+impl<C: Component, V: ComponentVisitor<C>> VisitComponents<V> for &C {
+    fn visit_components(visitor: &mut V, _: bool) {
+        visitor.visit(false)
+    }
+}
+
+impl<C: Component, V: ComponentVisitor<C>> VisitComponents<V> for &mut C {
+    fn visit_components(visitor: &mut V, mutable: bool) {
+        visitor.visit(mutable)
+    }
+}
+
+impl<V, Q: VisitComponents<V>> VisitComponents<V> for Option<Q> {
+    fn visit_components(visitor: &mut V, mutable: bool) {
+        Q::visit_components(visitor, mutable)
+    }
 }
 
+// Filters only access the components they fetch
+impl<V, Q: VisitComponents<V>, R> VisitComponents<V> for With<Q, R> {
+    fn visit_components(visitor: &mut V, mutable: bool) {
+        Q::visit_components(visitor, mutable)
+    }
+}
+
+impl<V, Q: VisitComponents<V>, R> VisitComponents<V> for Without<Q, R> {
+    fn visit_components(visitor: &mut V, mutable: bool) {
+        Q::visit_components(visitor, mutable)
+    }
+}
+
+impl<V, L: VisitComponents<V>, R: VisitComponents<V>> VisitComponents<V> for Or<L, R> {
+    fn visit_components(visitor: &mut V, mutable: bool) {
+        L::visit_components(visitor, mutable);
+        R::visit_components(visitor, mutable);
+    }
+}
+
+impl<V, T: VisitComponents<V>> VisitComponents<V> for ReadOnly<T> {
+    fn visit_components(visitor: &mut V, _: bool) {
+        T::visit_components(visitor, false)
+    }
+}
+
+impl<V, Q> VisitComponents<V> for Satisfies<Q> {
+    fn visit_components(_: &mut V, _: bool) {}
+}
+
+impl<V> VisitComponents<V> for Entity {
+    fn visit_components(_: &mut V, _: bool) {}
+}
+
+impl<V> VisitComponents<V> for () {
+    fn visit_components(_: &mut V, _: bool) {}
+}
+
+macro_rules! visit_components_impl {
+    ($($name: ident),*) => {
+        impl<V, $($name: VisitComponents<V>),*> VisitComponents<V> for ($($name,)*) {
+            fn visit_components(visitor: &mut V, mutable: bool) {
+                $($name::visit_components(visitor, mutable);)*
+            }
+        }
+    };
+}
+
+impl_for_tuples!(visit_components_impl);
+
+/// Describes the components of a subworld by name, see
+/// [SubWorldRaw::component_names](crate::SubWorldRaw::component_names).
+///
+/// Implemented for every [VisitComponents].
+pub trait ComponentAccess {
+    /// Calls `f` for each accessible component
+    fn for_each_component(f: &mut dyn FnMut(AccessInfo));
+}
+
+#[doc(hidden)]
+/// Collects the [AccessInfo] of each component
+pub struct AccessInfos(Vec<AccessInfo>);
+
+impl<C: Component> ComponentVisitor<C> for AccessInfos {
+    fn visit(&mut self, mutable: bool) {
+        self.0.push(AccessInfo::of::<C>(mutable))
+    }
+}
+
+impl<T: VisitComponents<AccessInfos>> ComponentAccess for T {
+    fn for_each_component(f: &mut dyn FnMut(AccessInfo)) {
+        let mut infos = AccessInfos(Vec::new());
+        T::visit_components(&mut infos, true);
+        infos.0.into_iter().for_each(f)
+    }
+}
+
+// Keeps a component borrowed while it is used through a trait object
+pub(crate) struct Guard<'a, C>(pub(crate) moss_hecs::Ref<'a, C>);
+
 /// Marker type for a subworld which has access to the whole world
 pub struct AllAccess;
 
+/// Marker type for a subworld which may access any component, like
+/// [AllAccess], but does not declare any component accesses to the schedule.
+/// The system is thus batched with other systems, while the components
+/// queried or accessed through the subworld are recorded at runtime.
+///
+/// After each batch, the recorded accesses are compared against the accesses of
+/// the other systems of the batch. A conflict is reported as a `tracing`
+/// warning, or as
+/// [Error::TrackedAccessConflict](crate::Error::TrackedAccessConflict) if
+/// [ScheduleBuilder::strict_access](crate::ScheduleBuilder::strict_access) is
+/// enabled. Either way, the conflict is only detected after the batch has run.
+/// Conflicting borrows of concurrently running systems still panic, as the
+/// components are borrow checked when accessed.
+///
+/// Accesses are not recorded for asynchronous systems.
+pub struct TrackedAccess;
+
+/// Marker type for a subworld which can access the components accessible by
+/// both `T` and `U`, see [SubWorldRaw::intersect](crate::SubWorldRaw::intersect).
+///
+/// Components accessed mutably by only one of the two are accessed
+/// immutably.
+pub struct Intersect<T, U>(PhantomData<(T, U)>);
+
+/// Marker type for a subworld which can access the components accessible by
+/// `T`, but only immutably, see
+/// [SubWorldRaw::as_read_only](crate::SubWorldRaw::as_read_only).
+pub struct ReadOnly<T>(PhantomData<T>);
+
+/// Collects the query filters of a subworld, which are applied by
+/// [SubWorldRaw::query_filtered](crate::SubWorldRaw::query_filtered).
+///
+/// [With] and [Without] forward their filter, such that a
+/// `SubWorld<(&Position, Exclude<Frozen>)>` never yields frozen entities.
+pub trait SubWorldFilter {
+    /// Filter which all entities queried through the subworld satisfy
+    type Filter: Query;
+}
+
+impl<T> SubWorldFilter for &T {
+    type Filter = ();
+}
+
+impl<T> SubWorldFilter for &mut T {
+    type Filter = ();
+}
+
+// The filters of optional components are optional as well
+impl<Q> SubWorldFilter for Option<Q> {
+    type Filter = ();
+}
+
+impl<Q: SubWorldFilter, R: Query> SubWorldFilter for With<Q, R> {
+    type Filter = (Q::Filter, With<(), R>);
+}
+
+impl<Q: SubWorldFilter, R: Query> SubWorldFilter for Without<Q, R> {
+    type Filter = (Q::Filter, Without<(), R>);
+}
+
+// Either side may match, so neither filter applies to all entities
+impl<L, R> SubWorldFilter for Or<L, R> {
+    type Filter = ();
+}
+
+impl<Q> SubWorldFilter for Satisfies<Q> {
+    type Filter = ();
+}
+
+impl SubWorldFilter for Entity {
+    type Filter = ();
+}
+
+impl SubWorldFilter for () {
+    type Filter = ();
+}
+
+impl SubWorldFilter for AllAccess {
+    type Filter = ();
+}
+
+impl SubWorldFilter for TrackedAccess {
+    type Filter = ();
+}
+
+impl<T: SubWorldFilter, U: SubWorldFilter> SubWorldFilter for Intersect<T, U> {
+    type Filter = (T::Filter, U::Filter);
+}
+
+impl<T: SubWorldFilter> SubWorldFilter for ReadOnly<T> {
+    type Filter = T::Filter;
+}
+
+macro_rules! subworld_filter_impl {
+    ($($name: ident),*) => {
+        impl<$($name: SubWorldFilter),*> SubWorldFilter for ($($name,)*) {
+            type Filter = ($($name::Filter,)*);
+        }
+    };
+}
+
+impl_for_tuples!(subworld_filter_impl);
+
 /// Declare subset relations between tuples
+///
+/// Only the borrows of a query are considered, such that query filters like
+/// [With](crate::With) are not required to be part of the set.
 pub trait Subset {
     /// Returns true if U is a subset of Self
     fn is_subset<U: ComponentBorrow>() -> bool;
+
+    #[doc(hidden)]
+    /// Calls `f` with the id and exclusivity of each borrow of Self
+    fn for_each_borrow(_: &mut dyn FnMut(TypeId, bool)) {}
 }
 
 impl<'a, Q: Query> Subset for Q {
@@ -111,4 +508,8 @@ impl<'a, Q: Query> Subset for Q {
 
         all
     }
+
+    fn for_each_borrow(f: &mut dyn FnMut(TypeId, bool)) {
+        Q::Fetch::for_each_borrow(f)
+    }
 }
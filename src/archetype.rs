@@ -0,0 +1,207 @@
+use std::{
+    any::{type_name, TypeId},
+    fmt::Debug,
+    marker::PhantomData,
+};
+
+use moss_hecs::{Archetype, ArchetypeColumn, ArchetypeColumnMut, Component, Query};
+
+use crate::{borrow::ComponentBorrow, ComponentAccess, Error, IntoAccess, Result};
+
+/// Read-only description of an archetype reachable by a subworld, see
+/// [SubWorldRaw::archetypes](crate::SubWorldRaw::archetypes).
+///
+/// Components are identified by their [TypeId], as the world does not retain
+/// the names of the component types. The names of the components the
+/// subworld can access are known from its access set, see
+/// [type_name](Self::type_name).
+pub struct ArchetypeInfo<'a, T> {
+    archetype: &'a Archetype,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T: ComponentBorrow> ArchetypeInfo<'a, T> {
+    pub(crate) fn new(archetype: &'a Archetype) -> Self {
+        Self {
+            archetype,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the number of entities in the archetype
+    pub fn len(&self) -> u32 {
+        self.archetype.len()
+    }
+
+    /// Returns true if the archetype contains no entities
+    pub fn is_empty(&self) -> bool {
+        self.archetype.is_empty()
+    }
+
+    /// Returns true if the entities of the archetype have the component `C`
+    pub fn has<C: Component>(&self) -> bool {
+        self.archetype.has::<C>()
+    }
+
+    /// Returns the ids of the entities of the archetype, in the same order as
+    /// the components of each column. The ids do not include the generation of
+    /// the entities.
+    pub fn ids(&self) -> &'a [u32] {
+        self.archetype.ids()
+    }
+
+    /// Borrows the column of the component `C`, e.g. to pass a pointer to the
+    /// components to a foreign library. The column stays borrowed until the
+    /// guard is dropped, so any pointer into it must not outlive the guard.
+    ///
+    /// Returns None if the archetype does not have the component. Fails if
+    /// the component is not accessible by the subworld.
+    ///
+    /// # Panics
+    /// Panics if the column is already borrowed mutably.
+    pub fn column<C: Component>(&self) -> Result<Option<ArchetypeColumn<'a, C>>> {
+        self.check::<&C>()?;
+        Ok(self.archetype.get::<&C>())
+    }
+
+    /// Mutably borrows the column of the component `C`, see
+    /// [column](Self::column).
+    ///
+    /// # Panics
+    /// Panics if the column is already borrowed.
+    pub fn column_mut<C: Component>(&self) -> Result<Option<ArchetypeColumnMut<'a, C>>> {
+        self.check::<&mut C>()?;
+        Ok(self.archetype.get::<&mut C>())
+    }
+
+    /// Returns the types of all components of the archetype
+    pub fn component_types(&self) -> impl Iterator<Item = TypeId> + 'a {
+        self.archetype.component_types()
+    }
+
+    /// Returns the types of the components of the archetype which the
+    /// subworld can access
+    pub fn accessible_types(&self) -> impl Iterator<Item = TypeId> + 'a
+    where
+        T: 'a,
+    {
+        self.component_types()
+            .filter(|&id| T::has_dynamic(id, false))
+    }
+
+    // Fails if the subworld can not access `U`
+    fn check<U: IntoAccess>(&self) -> Result<()> {
+        if T::has::<U>() {
+            Ok(())
+        } else {
+            Err(Error::IncompatibleSubworld {
+                subworld: type_name::<T>(),
+                query: type_name::<U>(),
+            })
+        }
+    }
+}
+
+impl<'a, T: ComponentBorrow + ComponentAccess> ArchetypeInfo<'a, T> {
+    /// Returns the type name of the component `id` of the archetype, or None
+    /// if the archetype does not have the component or the subworld can not
+    /// access it
+    pub fn type_name(&self, id: TypeId) -> Option<&'static str> {
+        if !self.archetype.component_types().any(|other| other == id) {
+            return None;
+        }
+
+        let mut name = None;
+        T::for_each_component(&mut |info| {
+            if info.id() == id {
+                name = Some(info.name())
+            }
+        });
+
+        name
+    }
+
+    /// Returns the types of all components of the archetype, along with their
+    /// [type_name](Self::type_name)
+    pub fn named_types(&self) -> impl Iterator<Item = (TypeId, Option<&'static str>)> + '_ {
+        self.component_types().map(|id| (id, self.type_name(id)))
+    }
+
+    /// Returns the [type_name](Self::type_name) of the component `id`, or the
+    /// name it is registered by in `registry` if the subworld can not access
+    /// it
+    #[cfg(feature = "serde")]
+    pub fn type_name_in(
+        &self,
+        id: TypeId,
+        registry: &crate::ComponentRegistry,
+    ) -> Option<&'static str> {
+        self.type_name(id).or_else(|| {
+            self.archetype
+                .component_types()
+                .any(|other| other == id)
+                .then(|| registry.get(id).map(|entry| entry.name))
+                .flatten()
+        })
+    }
+}
+
+impl<'a, T: ComponentBorrow> Debug for ArchetypeInfo<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ArchetypeInfo")
+            .field("len", &self.len())
+            .field(
+                "component_types",
+                &self.component_types().collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Borrows the columns of the components of a query from an archetype, see
+/// [SubWorldRaw::columns](crate::SubWorldRaw::columns).
+///
+/// Implemented for component references and tuples thereof.
+pub trait Columns: Query {
+    /// The borrowed columns, which dereference to slices of the components
+    type Column<'a>;
+
+    /// Borrows the columns from `archetype`, or returns None if the archetype
+    /// lacks any of the components
+    fn columns(archetype: &Archetype) -> Option<Self::Column<'_>>;
+}
+
+impl<C: Component> Columns for &C {
+    type Column<'a> = ArchetypeColumn<'a, C>;
+
+    fn columns(archetype: &Archetype) -> Option<Self::Column<'_>> {
+        archetype.get::<&C>()
+    }
+}
+
+impl<C: Component> Columns for &mut C {
+    type Column<'a> = ArchetypeColumnMut<'a, C>;
+
+    fn columns(archetype: &Archetype) -> Option<Self::Column<'_>> {
+        archetype.get::<&mut C>()
+    }
+}
+
+macro_rules! columns_impl {
+    ($($name: ident),*) => {
+        impl<$($name: Columns),*> Columns for ($($name,)*) {
+            type Column<'a> = ($($name::Column<'a>,)*);
+
+            fn columns(archetype: &Archetype) -> Option<Self::Column<'_>> {
+                // Check first to not borrow any column of unmatched archetypes
+                if !archetype.satisfies::<Self>() {
+                    return None;
+                }
+
+                Some(($($name::columns(archetype)?,)*))
+            }
+        }
+    };
+}
+
+impl_for_tuples!(columns_impl);
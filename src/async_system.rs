@@ -0,0 +1,213 @@
+use std::{
+    any::type_name,
+    future::{poll_fn, Future},
+    pin::Pin,
+    sync::Arc,
+    task::{self, Poll, Wake, Waker},
+    thread::{self, Thread},
+    time::Duration,
+};
+
+use crate::{
+    borrow::{Borrows, ComponentBorrow, ContextBorrow, IntoBorrow},
+    clock::Instant,
+    Context, Error, Result, SystemName,
+};
+
+/// A boxed future of an asynchronous system which borrows from the context
+pub type SystemFuture<'a> = Pin<Box<dyn Future<Output = Result<()>> + 'a>>;
+
+/// Trait which defines any async function that can operate on a world or
+/// other context.
+///
+/// Implemented for `async fn`s returning either nothing or a result. The
+/// borrowed data is held until the returned future completes.
+pub trait AsyncSystem<Args> {
+    /// Borrows from the context and starts the system
+    fn start<'a>(&mut self, context: &'a Context) -> Result<SystemFuture<'a>>;
+
+    /// Returns the system name. Used for debug purposes
+    fn name(&self) -> SystemName;
+
+    /// Returns which data will be accessed
+    fn borrows() -> Borrows;
+}
+
+/// The value returned by a completed asynchronous system
+pub trait SystemOutput {
+    /// Converts the value into the result of the system
+    fn into_result(self, name: SystemName) -> Result<()>;
+}
+
+impl SystemOutput for () {
+    fn into_result(self, _: SystemName) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<Err: Into<anyhow::Error>> SystemOutput for std::result::Result<(), Err> {
+    fn into_result(self, name: SystemName) -> Result<()> {
+        self.map_err(|e| Error::SystemError(name, e.into()))
+    }
+}
+
+#[doc(hidden)]
+/// Names the future returned by a function for arguments of a specific
+/// lifetime, which allows the future to borrow the arguments.
+pub trait AsyncSystemFn<'a, Args> {
+    /// The future returned by the function
+    type Future: Future<Output = Self::Output> + 'a;
+    /// The output of the future
+    type Output: SystemOutput;
+
+    /// Calls the function
+    fn call(&mut self, args: Args) -> Self::Future;
+}
+
+macro_rules! tuple_impl {
+    ($($name: ident), *) => {
+        impl<'a, Func, Fut, $($name,) *> AsyncSystemFn<'a, ($($name,)*)> for Func
+        where
+            Func: FnMut($($name,)*) -> Fut,
+            Fut: Future + 'a,
+            Fut::Output: SystemOutput,
+        {
+            type Future = Fut;
+            type Output = Fut::Output;
+
+            #[allow(non_snake_case)]
+            fn call(&mut self, ($($name,)*): ($($name,)*)) -> Fut {
+                (self)($($name,)*)
+            }
+        }
+
+        impl<Func, $($name,) *> AsyncSystem<($($name,)*)> for Func
+        where
+            // The first bound allows inferring the argument types; the second
+            // allows calling with arguments borrowed from the context
+            Func: AsyncSystemFn<'static, ($($name,)*)>
+                + for<'a> AsyncSystemFn<'a, ($(<$name::Borrow as ContextBorrow<'a>>::Target,)*)>,
+            $($name: IntoBorrow + ComponentBorrow,)*
+        {
+            fn start<'a>(&mut self, context: &'a Context) -> Result<SystemFuture<'a>> {
+                let name = <Self as AsyncSystem<($($name,)*)>>::name(self);
+                let future = AsyncSystemFn::call(self, ($($name::Borrow::borrow(context)?,)*));
+
+                Ok(Box::pin(async move { future.await.into_result(name) }))
+            }
+
+            fn name(&self) -> SystemName {
+                type_name::<Func>().into()
+            }
+
+            fn borrows() -> Borrows {
+                ([].iter()
+                    $(.chain($name::borrows().iter())) *).cloned()
+                .collect()
+            }
+        }
+    };
+}
+
+impl_for_tuples!(tuple_impl);
+
+impl<Func, Fut> AsyncSystem<()> for Func
+where
+    Func: FnMut() -> Fut,
+    Fut: Future + 'static,
+    Fut::Output: SystemOutput,
+{
+    fn start<'a>(&mut self, _: &'a Context) -> Result<SystemFuture<'a>> {
+        let name = <Self as AsyncSystem<()>>::name(self);
+        let future = (self)();
+
+        Ok(Box::pin(async move { future.await.into_result(name) }))
+    }
+
+    fn name(&self) -> SystemName {
+        type_name::<Func>().into()
+    }
+
+    fn borrows() -> Borrows {
+        Borrows::default()
+    }
+}
+
+// Wakes the thread blocking on a future
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark()
+    }
+}
+
+/// Runs the future to completion on the current thread. The future is polled
+/// again at `deadline` even if it is not woken, e.g. to let a timeout expire.
+pub(crate) fn block_on<F: Future>(future: F, deadline: Option<Instant>) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = task::Context::from_waker(&waker);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+
+        match deadline {
+            Some(deadline) => {
+                thread::park_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            None => thread::park(),
+        }
+    }
+}
+
+/// Fails with [Error::SystemTimeout] if the future is still pending after
+/// `timeout` when polled, which cancels it
+pub(crate) fn with_timeout(
+    mut future: SystemFuture<'_>,
+    name: SystemName,
+    timeout: Duration,
+) -> SystemFuture<'_> {
+    let start = Instant::now();
+
+    Box::pin(poll_fn(move |cx| match future.as_mut().poll(cx) {
+        Poll::Pending if start.elapsed() > timeout => Poll::Ready(Err(Error::SystemTimeout {
+            name: name.clone(),
+            elapsed: start.elapsed(),
+        })),
+        poll => poll,
+    }))
+}
+
+/// Polls all futures concurrently and returns their outputs in order
+pub(crate) async fn join_all(futures: Vec<SystemFuture<'_>>) -> Vec<Result<()>> {
+    let mut futures: Vec<_> = futures.into_iter().map(Some).collect();
+    let mut outputs: Vec<_> = futures.iter().map(|_| None).collect();
+
+    poll_fn(|cx| {
+        let mut pending = false;
+
+        for (future, output) in futures.iter_mut().zip(&mut outputs) {
+            if let Some(inner) = future {
+                match inner.as_mut().poll(cx) {
+                    Poll::Ready(result) => {
+                        *output = Some(result);
+                        *future = None;
+                    }
+                    Poll::Pending => pending = true,
+                }
+            }
+        }
+
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(())
+        }
+    })
+    .await;
+
+    outputs.into_iter().flatten().collect()
+}
@@ -3,6 +3,7 @@ use std::{
     marker::PhantomData,
     ops::{Deref, DerefMut},
     ptr::NonNull,
+    sync::atomic::{AtomicU64, Ordering},
 };
 
 /// Type alias for list of borrows
@@ -49,14 +50,17 @@ impl<'a, T: 'static> Read<'a, T> {
     }
 }
 
-#[repr(transparent)]
 /// Wrapper type for an exclusively borrowed value
-pub struct Write<'a, T>(pub(crate) AtomicRefMut<'a, T>);
+pub struct Write<'a, T>(
+    pub(crate) AtomicRefMut<'a, T>,
+    // Advanced on the first mutable access
+    Option<&'a AtomicU64>,
+);
 
 impl<'a, T> Write<'a, T> {
     /// Creates a new Write borrow from an atomic ref
     pub fn new(borrow: AtomicRefMut<'a, T>) -> Self {
-        Self(borrow)
+        Self(borrow, None)
     }
 }
 
@@ -65,11 +69,18 @@ impl<'a, T: 'static> Write<'a, T> {
         cell.try_borrow_mut()
             .map_err(|_| Error::BorrowMut(type_name::<T>()))
             .map(|cell| {
-                Self(AtomicRefMut::map(cell, |val| unsafe {
+                Self::new(AtomicRefMut::map(cell, |val| unsafe {
                     val.cast().as_mut()
                 }))
             })
     }
+
+    /// Borrows `T` from context, counting mutable accesses as changes of `T`
+    fn borrow_tracked(context: &'a Context) -> Result<Self> {
+        let mut borrow = Self::try_from_untyped(context.cell::<&mut T>()?)?;
+        borrow.1 = context.tick::<T>();
+        Ok(borrow)
+    }
 }
 
 impl<'a, T> Deref for Write<'a, T> {
@@ -82,6 +93,10 @@ impl<'a, T> Deref for Write<'a, T> {
 
 impl<'a, T> DerefMut for Write<'a, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
+        if let Some(tick) = self.1.take() {
+            tick.fetch_add(1, Ordering::Release);
+        }
+
         &mut self.0
     }
 }
@@ -111,7 +126,7 @@ impl<'a, T: 'static> ContextBorrow<'a> for &'a mut T {
     type Target = Write<'a, T>;
 
     fn borrow(context: &'a Context) -> Result<Self::Target> {
-        Write::try_from_untyped(context.cell::<&mut T>()?)
+        Write::borrow_tracked(context)
     }
 }
 
@@ -127,13 +142,13 @@ impl<'a, T: 'static> ContextBorrow<'a> for Write<'a, T> {
     type Target = Self;
 
     fn borrow(context: &'a Context) -> Result<Self::Target> {
-        Write::try_from_untyped(context.cell::<&mut T>()?)
+        Write::borrow_tracked(context)
     }
 }
 
 impl<'a, T: 'static> ComponentBorrow for Read<'a, T> {
     fn borrows() -> Borrows {
-        smallvec![Access::of::<&BorrowMarker<T>>()]
+        smallvec![Access::of::<&BorrowMarker<T>>().requires::<T>()]
     }
 
     fn has<U: crate::IntoAccess>() -> bool {
@@ -149,7 +164,7 @@ impl<'a, T: 'static> ComponentBorrow for Read<'a, T> {
 
 impl<'a, T: 'static> ComponentBorrow for Write<'a, T> {
     fn borrows() -> Borrows {
-        smallvec![Access::of::<&mut BorrowMarker<T>>()]
+        smallvec![Access::of::<&mut BorrowMarker<T>>().requires::<T>()]
     }
 
     fn has<U: crate::IntoAccess>() -> bool {
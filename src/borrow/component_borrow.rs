@@ -1,7 +1,7 @@
 use std::any::{type_name, TypeId};
 
 use super::Borrows;
-use crate::{Access, AllAccess, IntoAccess};
+use crate::{Access, AllAccess, Intersect, IntoAccess, ReadOnly, TrackedAccess};
 use moss_hecs::{Fetch, Frame, Query};
 pub use smallvec::smallvec;
 use smallvec::SmallVec;
@@ -14,6 +14,13 @@ pub trait ComponentBorrow {
     fn has_dynamic(id: TypeId, exclusive: bool) -> bool;
     /// Returns true if U exists in Self
     fn has<U: IntoAccess>() -> bool;
+
+    #[doc(hidden)]
+    /// Returns true if the accesses of a subworld of Self are recorded at
+    /// runtime, see [TrackedAccess]
+    fn tracked() -> bool {
+        false
+    }
 }
 
 impl<'a, Q: Query> ComponentBorrow for Q {
@@ -58,3 +65,74 @@ impl ComponentBorrow for AllAccess {
         true
     }
 }
+
+// Declares nothing, as the accesses are recorded when the system runs
+impl ComponentBorrow for TrackedAccess {
+    fn borrows() -> Borrows {
+        Borrows::new()
+    }
+
+    fn has<U: IntoAccess>() -> bool {
+        true
+    }
+
+    fn has_dynamic(_: TypeId, _: bool) -> bool {
+        true
+    }
+
+    fn tracked() -> bool {
+        true
+    }
+}
+
+impl<L: ComponentBorrow, R: ComponentBorrow> ComponentBorrow for Intersect<L, R> {
+    fn borrows() -> Borrows {
+        L::borrows()
+            .into_iter()
+            .filter(|access| R::has_dynamic(access.id(), false))
+            .map(|mut access| {
+                access.exclusive &= R::has_dynamic(access.id(), true);
+                access
+            })
+            .collect()
+    }
+
+    fn has<U: IntoAccess>() -> bool {
+        let access = U::access();
+        Self::has_dynamic(access.id(), access.exclusive())
+    }
+
+    fn has_dynamic(id: TypeId, exclusive: bool) -> bool {
+        L::has_dynamic(id, exclusive) && R::has_dynamic(id, exclusive)
+    }
+
+    // Accesses permitted by a tracked side are not declared by its borrows
+    fn tracked() -> bool {
+        L::tracked() || R::tracked()
+    }
+}
+
+impl<T: ComponentBorrow> ComponentBorrow for ReadOnly<T> {
+    fn borrows() -> Borrows {
+        T::borrows()
+            .into_iter()
+            .map(|mut access| {
+                access.exclusive = false;
+                access
+            })
+            .collect()
+    }
+
+    fn has<U: IntoAccess>() -> bool {
+        let access = U::access();
+        Self::has_dynamic(access.id(), access.exclusive())
+    }
+
+    fn has_dynamic(id: TypeId, exclusive: bool) -> bool {
+        !exclusive && T::has_dynamic(id, false)
+    }
+
+    fn tracked() -> bool {
+        T::tracked()
+    }
+}
@@ -1,5 +1,7 @@
 ///! This module works around the lifetimes for borrow when GAT isn't available
-use crate::{Read, SubWorld, Write};
+use std::marker::PhantomData;
+
+use crate::{Context, Read, Result, SubWorld, SubWorldRaw, World, WorldRef, Write};
 
 use super::{ContextBorrow, MaybeRead, MaybeWrite};
 
@@ -37,3 +39,18 @@ impl_into_borrow!(Component, Write => BorrowMut);
 impl_into_borrow!(Component, MaybeRead => MaybeBorrower);
 impl_into_borrow!(Component, MaybeWrite => MaybeBorrowerMut);
 impl_into_borrow!(Component, SubWorld => SubWorldBorrower);
+
+#[doc(hidden)]
+pub struct WorldBorrower<T, W>(PhantomData<(T, W)>);
+
+impl<T: Component, W: World> IntoBorrow for SubWorldRaw<WorldRef<'_, W>, T> {
+    type Borrow = WorldBorrower<T, W>;
+}
+
+impl<'a, T: Component, W: World> ContextBorrow<'a> for WorldBorrower<T, W> {
+    type Target = SubWorldRaw<WorldRef<'a, W>, T>;
+
+    fn borrow(context: &'a Context) -> Result<Self::Target> {
+        Self::Target::borrow(context)
+    }
+}
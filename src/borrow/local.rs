@@ -0,0 +1,119 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex},
+};
+
+use atomic_refcell::{AtomicRefCell, AtomicRefMut};
+use moss_hecs::Component;
+
+use crate::{tracking::AccessLog, Context, Error, Result};
+
+use super::{Borrows, ComponentBorrow, ContextBorrow, IntoBorrow};
+
+type LocalCell = AtomicRefCell<Box<dyn Any + Send + Sync>>;
+
+#[derive(Default)]
+/// The local values of a single system, by type
+pub(crate) struct Locals {
+    values: Mutex<HashMap<TypeId, Box<LocalCell>>>,
+    accessed: Arc<AccessLog>,
+}
+
+impl Locals {
+    /// Returns the log of the components accessed by the tracked subworlds
+    /// of the system
+    pub(crate) fn accessed(&self) -> &Arc<AccessLog> {
+        &self.accessed
+    }
+
+    /// Borrows the value of type `T`, inserting the default value if it does
+    /// not exist
+    fn get<T: Component + Default>(&self) -> Result<AtomicRefMut<T>> {
+        let cell: *const LocalCell = {
+            let mut values = self.values.lock().expect("Locals were poisoned");
+            &**values
+                .entry(TypeId::of::<T>())
+                .or_insert_with(|| Box::new(AtomicRefCell::new(Box::new(T::default()))))
+        };
+
+        // Safety: the cells are boxed and never removed, so they live as long
+        // as self
+        let cell = unsafe { &*cell };
+
+        cell.try_borrow_mut()
+            .map_err(|_| Error::BorrowMut(type_name::<T>()))
+            .map(|value| {
+                AtomicRefMut::map(value, |value| {
+                    value.downcast_mut().expect("Local stored by wrong type")
+                })
+            })
+    }
+}
+
+/// A value owned by the system which persists between executions, such as a
+/// counter or a cached buffer. The value is initialized using [Default] on
+/// first access.
+///
+/// Each system has its own local value of each type `T`. Locals are not
+/// available to conditions, nor to asynchronous systems polled by
+/// [Schedule::execute_async](crate::Schedule::execute_async).
+pub struct Local<'a, T>(AtomicRefMut<'a, T>);
+
+impl<'a, T> Deref for Local<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, T> DerefMut for Local<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<'a, T: Component + Default> ContextBorrow<'a> for Local<'a, T> {
+    type Target = Self;
+
+    fn borrow(context: &'a Context) -> Result<Self::Target> {
+        context
+            .locals()
+            .ok_or_else(|| Error::MissingData(type_name::<Self>()))?
+            .get()
+            .map(Self)
+    }
+}
+
+// Locals are never shared with other systems
+impl<'a, T: Component + Default> ComponentBorrow for Local<'a, T> {
+    fn borrows() -> Borrows {
+        Borrows::new()
+    }
+
+    fn has<U: crate::IntoAccess>() -> bool {
+        false
+    }
+
+    fn has_dynamic(_: TypeId, _: bool) -> bool {
+        false
+    }
+}
+
+#[doc(hidden)]
+pub struct LocalBorrower<T>(PhantomData<T>);
+
+impl<T: Component + Default> IntoBorrow for Local<'_, T> {
+    type Borrow = LocalBorrower<T>;
+}
+
+impl<'a, T: Component + Default> ContextBorrow<'a> for LocalBorrower<T> {
+    type Target = Local<'a, T>;
+
+    fn borrow(context: &'a Context) -> Result<Self::Target> {
+        Self::Target::borrow(context)
+    }
+}
@@ -148,7 +148,7 @@ impl<'a, T: 'static> ContextBorrow<'a> for MaybeWrite<'a, T> {
 
 impl<'a, T: 'static> ComponentBorrow for MaybeRead<'a, T> {
     fn borrows() -> Borrows {
-        smallvec![BorrowMarker::<&T>::access()]
+        smallvec![BorrowMarker::<&T>::access().optionally_requires::<T>()]
     }
 
     fn has<U: crate::IntoAccess>() -> bool {
@@ -164,7 +164,7 @@ impl<'a, T: 'static> ComponentBorrow for MaybeRead<'a, T> {
 
 impl<'a, T: 'static> ComponentBorrow for MaybeWrite<'a, T> {
     fn borrows() -> Borrows {
-        smallvec![BorrowMarker::<&mut T>::access()]
+        smallvec![BorrowMarker::<&mut T>::access().optionally_requires::<T>()]
     }
 
     fn has<U: crate::IntoAccess>() -> bool {
@@ -8,9 +8,14 @@ mod cell_borrow;
 mod component_borrow;
 #[macro_use]
 mod into_borrow;
+mod local;
 mod maybe_borrow;
+mod yield_now;
 
 pub use cell_borrow::*;
 pub use component_borrow::*;
 pub use into_borrow::*;
+pub(crate) use local::Locals;
+pub use local::{Local, LocalBorrower};
 pub use maybe_borrow::*;
+pub use yield_now::Yield;
@@ -0,0 +1,60 @@
+use std::any::TypeId;
+
+use crate::{Context, Result};
+
+use super::{Borrows, ComponentBorrow, ContextBorrow, IntoBorrow};
+
+#[derive(Debug, Clone, Copy)]
+/// Allows a long running system to let other systems of the same batch run on
+/// the current thread, e.g; between chunks of work.
+///
+/// This only has an effect during parallel execution, where the systems of a
+/// batch which have not yet started may run when [Yield::now] is called. As
+/// the systems of a batch never conflict, they may run while the yielding
+/// system holds its borrows.
+pub struct Yield {
+    _private: (),
+}
+
+impl Yield {
+    /// Runs pending work of the scheduler on the current thread, if any.
+    /// Returns true if any work was executed.
+    pub fn now(&self) -> bool {
+        #[cfg(feature = "parallel")]
+        {
+            matches!(rayon::yield_now(), Some(rayon::Yield::Executed))
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            false
+        }
+    }
+}
+
+impl<'a> ContextBorrow<'a> for Yield {
+    type Target = Self;
+
+    fn borrow(_: &'a Context) -> Result<Self::Target> {
+        Ok(Self { _private: () })
+    }
+}
+
+// Yielding does not access any data
+impl ComponentBorrow for Yield {
+    fn borrows() -> Borrows {
+        Borrows::new()
+    }
+
+    fn has<U: crate::IntoAccess>() -> bool {
+        false
+    }
+
+    fn has_dynamic(_: TypeId, _: bool) -> bool {
+        false
+    }
+}
+
+impl IntoBorrow for Yield {
+    type Borrow = Self;
+}
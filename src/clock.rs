@@ -0,0 +1,9 @@
+//! The clock used for timings, timeouts, budgets and deadlines.
+//!
+//! [std::time::Instant] panics on `wasm32-unknown-unknown`, where the clock of
+//! the browser is used instead. Elsewhere, [Instant] is [std::time::Instant].
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use std::time::Instant;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub use web_time::Instant;
@@ -0,0 +1,641 @@
+use std::{
+    any::{type_name, TypeId},
+    fmt,
+    marker::PhantomData,
+};
+
+use moss_hecs::{Component, Entity, EntityBuilder};
+use serde::{
+    de::{
+        self, DeserializeSeed, Deserializer, EnumAccess, MapAccess, SeqAccess, Unexpected,
+        VariantAccess, Visitor,
+    },
+    ser::{self, SerializeMap, SerializeSeq},
+    Serialize, Serializer,
+};
+
+use crate::{serialize::DeserializeFn, CommandBuffer, CommandEntity, ComponentRegistry};
+
+const COMMAND: &str = "Command";
+const COMMANDS: &[&str] = &["Spawn", "Insert", "Remove", "Despawn"];
+const ENTITY: &str = "Entity";
+const ENTITIES: &[&str] = &["Entity", "Deferred"];
+
+trait ErasedComponent: Send + Sync {
+    fn id(&self) -> TypeId;
+    fn name(&self) -> &'static str;
+    fn as_serialize(&self) -> &dyn erased_serde::Serialize;
+    fn add_to(self: Box<Self>, builder: &mut EntityBuilder);
+}
+
+impl<C: Component + Serialize> ErasedComponent for C {
+    fn id(&self) -> TypeId {
+        TypeId::of::<C>()
+    }
+
+    fn name(&self) -> &'static str {
+        type_name::<C>()
+    }
+
+    fn as_serialize(&self) -> &dyn erased_serde::Serialize {
+        self
+    }
+
+    fn add_to(self: Box<Self>, builder: &mut EntityBuilder) {
+        builder.add(*self);
+    }
+}
+
+#[doc(hidden)]
+/// A type erased component of a [CommandStream]
+pub struct StreamComponent(Box<dyn ErasedComponent>);
+
+impl StreamComponent {
+    pub(crate) fn new<C: Component + Serialize>(component: C) -> Self {
+        Self(Box::new(component))
+    }
+}
+
+/// A bundle of components which can be recorded into a [CommandStream].
+///
+/// Implemented for tuples of [Component]s which implement [Serialize].
+pub trait StreamBundle {
+    #[doc(hidden)]
+    fn into_components(self, components: &mut Vec<StreamComponent>);
+}
+
+macro_rules! stream_bundle_impl {
+    ($($name: ident),*) => {
+        impl<$($name: Component + Serialize),*> StreamBundle for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn into_components(self, components: &mut Vec<StreamComponent>) {
+                let ($($name,)*) = self;
+                $(components.push(StreamComponent::new($name));)*
+            }
+        }
+    };
+}
+
+impl_for_tuples!(stream_bundle_impl);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Placeholder for an entity spawned by [CommandStream::spawn], which is
+/// resolved to the spawned entity when the stream is recorded into a
+/// commandbuffer.
+///
+/// The placeholder may only be used in commands of the same stream. Unlike a
+/// [DeferredEntity](crate::DeferredEntity), it is serialized along with the
+/// stream.
+pub struct StreamEntity(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The target of a command of a [CommandStream], which is either an existing
+/// entity or a [StreamEntity].
+pub enum StreamTarget {
+    /// An existing entity
+    Entity(Entity),
+    /// An entity spawned by the same stream
+    Spawned(StreamEntity),
+}
+
+impl From<Entity> for StreamTarget {
+    fn from(entity: Entity) -> Self {
+        Self::Entity(entity)
+    }
+}
+
+impl From<StreamEntity> for StreamTarget {
+    fn from(entity: StreamEntity) -> Self {
+        Self::Spawned(entity)
+    }
+}
+
+struct Removal {
+    id: TypeId,
+    name: &'static str,
+    remove: fn(&mut CommandBuffer, CommandEntity),
+}
+
+enum StreamCommand {
+    Spawn(Vec<StreamComponent>),
+    Insert(StreamTarget, Vec<StreamComponent>),
+    Remove(StreamTarget, Removal),
+    Despawn(StreamTarget),
+}
+
+#[derive(Default)]
+/// Records commands of registered components which can be serialized, e.g.
+/// to record the commands of a frame on a client, and deserialized to
+/// reapply the same commands in the same order on a server or in a replay.
+///
+/// The components are named by a [ComponentRegistry], and must be registered
+/// with [register_deserialize](ComponentRegistry::register_deserialize) to be
+/// deserialized.
+///
+/// Entities are serialized by their bits. Reapplying a stream therefore refers
+/// to the same entities only if the entities of the worlds match, such as when
+/// replaying from the same initial state. Entities spawned by the stream
+/// itself are referred to by [StreamEntity].
+///
+/// The stream records its own commands, rather than capturing the commands of
+/// a [CommandBuffer]: the commandbuffer holds its components as type erased
+/// bundles and its other commands as closures, neither of which can be
+/// serialized. Commands which should be streamed are thus recorded into the
+/// stream, and the stream into the commandbuffer.
+///
+/// # Example
+/// ```rust
+/// use moss_hecs::Frame;
+/// use moss_hecs_schedule::*;
+///
+/// let mut registry = ComponentRegistry::new();
+/// registry.register_deserialize::<i32>("i32");
+///
+/// let mut stream = CommandStream::new();
+/// let entity = stream.spawn((1_i32,));
+/// stream.insert_one(entity, 2_i32);
+///
+/// let json = stream.serialize(serde_json::value::Serializer, &registry).unwrap();
+/// let replay = CommandStream::deserialize(json, &registry).unwrap();
+///
+/// let mut frame = Frame::default();
+/// let mut cmd = CommandBuffer::new();
+/// replay.record(&mut cmd);
+/// cmd.execute(&mut frame);
+///
+/// assert_eq!(frame.query_mut::<&i32>().into_iter().map(|(_, v)| *v).sum::<i32>(), 2);
+/// ```
+pub struct CommandStream {
+    commands: Vec<StreamCommand>,
+    deferred: usize,
+}
+
+impl CommandStream {
+    /// Creates a new empty command stream
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of recorded commands
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns true if no commands are recorded
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Spawns a new entity with components, see
+    /// [CommandBuffer::spawn_deferred].
+    ///
+    /// The returned placeholder can only be used in later commands of the
+    /// same stream.
+    pub fn spawn(&mut self, components: impl StreamBundle) -> StreamEntity {
+        let mut spawned = Vec::new();
+        components.into_components(&mut spawned);
+        self.commands.push(StreamCommand::Spawn(spawned));
+
+        self.deferred += 1;
+        StreamEntity(self.deferred - 1)
+    }
+
+    /// Inserts components into an entity, see [CommandBuffer::insert]
+    pub fn insert(&mut self, entity: impl Into<StreamTarget>, components: impl StreamBundle) {
+        let mut inserted = Vec::new();
+        components.into_components(&mut inserted);
+        self.commands
+            .push(StreamCommand::Insert(entity.into(), inserted));
+    }
+
+    /// Inserts a single component into an entity, see [insert](Self::insert)
+    pub fn insert_one(
+        &mut self,
+        entity: impl Into<StreamTarget>,
+        component: impl Component + Serialize,
+    ) {
+        self.insert(entity, (component,))
+    }
+
+    /// Removes a single component from an entity, see
+    /// [CommandBuffer::remove_one]
+    pub fn remove_one<C: Component>(&mut self, entity: impl Into<StreamTarget>) {
+        self.commands.push(StreamCommand::Remove(
+            entity.into(),
+            Removal {
+                id: TypeId::of::<C>(),
+                name: type_name::<C>(),
+                remove: |cmd, entity| cmd.remove_one::<C>(entity),
+            },
+        ))
+    }
+
+    /// Despawns an entity, see [CommandBuffer::despawn]
+    pub fn despawn(&mut self, entity: impl Into<StreamTarget>) {
+        self.commands.push(StreamCommand::Despawn(entity.into()))
+    }
+
+    /// Records the commands into a commandbuffer, in the order they were
+    /// recorded into the stream
+    pub fn record(self, cmd: &mut CommandBuffer) {
+        let mut spawned: Vec<CommandEntity> = Vec::with_capacity(self.deferred);
+        let resolve = |entity: StreamTarget, spawned: &[CommandEntity]| -> CommandEntity {
+            match entity {
+                StreamTarget::Entity(entity) => entity.into(),
+                StreamTarget::Spawned(StreamEntity(index)) => spawned[index],
+            }
+        };
+
+        for command in self.commands {
+            match command {
+                StreamCommand::Spawn(components) => {
+                    let mut builder = build(components);
+                    spawned.push(cmd.spawn_deferred(builder.build()).into());
+                }
+                StreamCommand::Insert(entity, components) => {
+                    let mut builder = build(components);
+                    cmd.insert(resolve(entity, &spawned), builder.build());
+                }
+                StreamCommand::Remove(entity, removal) => {
+                    (removal.remove)(cmd, resolve(entity, &spawned))
+                }
+                StreamCommand::Despawn(entity) => cmd.despawn(resolve(entity, &spawned)),
+            }
+        }
+    }
+
+    /// Serializes the commands as a sequence, naming the components by
+    /// `registry`.
+    ///
+    /// Fails if a recorded component is not registered.
+    pub fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+        registry: &ComponentRegistry,
+    ) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.commands.len()))?;
+
+        for command in &self.commands {
+            seq.serialize_element(&SerializeCommand { command, registry })?;
+        }
+
+        seq.end()
+    }
+
+    /// Deserializes the commands serialized by [serialize](Self::serialize),
+    /// looking up the components by name in `registry`.
+    ///
+    /// Fails if a component is not registered for deserialization, or if a
+    /// [StreamEntity] is used before it is spawned.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+        registry: &ComponentRegistry,
+    ) -> Result<Self, D::Error> {
+        deserializer.deserialize_seq(StreamVisitor { registry })
+    }
+}
+
+fn build(components: Vec<StreamComponent>) -> EntityBuilder {
+    let mut builder = EntityBuilder::new();
+    for component in components {
+        component.0.add_to(&mut builder);
+    }
+
+    builder
+}
+
+fn unregistered<E: ser::Error>(name: &str) -> E {
+    E::custom(format_args!("Component {name} is not registered"))
+}
+
+struct SerializeEntity(StreamTarget);
+
+impl Serialize for SerializeEntity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            StreamTarget::Entity(entity) => serializer.serialize_newtype_variant(
+                ENTITY,
+                0,
+                ENTITIES[0],
+                &entity.to_bits().get(),
+            ),
+            StreamTarget::Spawned(StreamEntity(index)) => {
+                serializer.serialize_newtype_variant(ENTITY, 1, ENTITIES[1], &(index as u64))
+            }
+        }
+    }
+}
+
+struct SerializeComponents<'a> {
+    components: &'a [StreamComponent],
+    registry: &'a ComponentRegistry,
+}
+
+impl<'a> Serialize for SerializeComponents<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.components.len()))?;
+
+        for component in self.components {
+            let entry = self
+                .registry
+                .get(component.0.id())
+                .ok_or_else(|| unregistered::<S::Error>(component.0.name()))?;
+
+            map.serialize_entry(entry.name, component.0.as_serialize())?;
+        }
+
+        map.end()
+    }
+}
+
+struct SerializeCommand<'a> {
+    command: &'a StreamCommand,
+    registry: &'a ComponentRegistry,
+}
+
+impl<'a> Serialize for SerializeCommand<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let components = |components| SerializeComponents {
+            components,
+            registry: self.registry,
+        };
+
+        match self.command {
+            StreamCommand::Spawn(spawned) => {
+                serializer.serialize_newtype_variant(COMMAND, 0, COMMANDS[0], &components(spawned))
+            }
+            StreamCommand::Insert(entity, inserted) => serializer.serialize_newtype_variant(
+                COMMAND,
+                1,
+                COMMANDS[1],
+                &(SerializeEntity(*entity), components(inserted)),
+            ),
+            StreamCommand::Remove(entity, removal) => {
+                let entry = self
+                    .registry
+                    .get(removal.id)
+                    .ok_or_else(|| unregistered::<S::Error>(removal.name))?;
+
+                serializer.serialize_newtype_variant(
+                    COMMAND,
+                    2,
+                    COMMANDS[2],
+                    &(SerializeEntity(*entity), entry.name),
+                )
+            }
+            StreamCommand::Despawn(entity) => serializer.serialize_newtype_variant(
+                COMMAND,
+                3,
+                COMMANDS[3],
+                &SerializeEntity(*entity),
+            ),
+        }
+    }
+}
+
+struct StreamVisitor<'a> {
+    registry: &'a ComponentRegistry,
+}
+
+impl<'de, 'a> Visitor<'de> for StreamVisitor<'a> {
+    type Value = CommandStream;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of commands")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut stream = CommandStream::new();
+
+        while let Some(command) = seq.next_element_seed(CommandSeed {
+            registry: self.registry,
+            deferred: stream.deferred,
+        })? {
+            if let StreamCommand::Spawn(_) = command {
+                stream.deferred += 1;
+            }
+
+            stream.commands.push(command);
+        }
+
+        Ok(stream)
+    }
+}
+
+// Deserializes a command, given the number of preceding spawns
+struct CommandSeed<'a> {
+    registry: &'a ComponentRegistry,
+    deferred: usize,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for CommandSeed<'a> {
+    type Value = StreamCommand;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_enum(COMMAND, COMMANDS, self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for CommandSeed<'a> {
+    type Value = StreamCommand;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a command")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        let entity = EntitySeed {
+            deferred: self.deferred,
+        };
+        let components = ComponentsSeed {
+            registry: self.registry,
+        };
+
+        let (variant, access) = data.variant_seed(VariantSeed(COMMANDS))?;
+        match variant {
+            0 => access
+                .newtype_variant_seed(components)
+                .map(StreamCommand::Spawn),
+            1 => {
+                let (entity, inserted) =
+                    access.newtype_variant_seed(PairSeed(entity, components))?;
+                Ok(StreamCommand::Insert(entity, inserted))
+            }
+            2 => {
+                let (entity, name) =
+                    access.newtype_variant_seed(PairSeed(entity, PhantomData::<String>))?;
+
+                let entry = self.registry.get_by_name(&name).ok_or_else(|| {
+                    <A::Error as de::Error>::custom(format_args!(
+                        "Component {name} is not registered"
+                    ))
+                })?;
+
+                Ok(StreamCommand::Remove(
+                    entity,
+                    Removal {
+                        id: entry.id,
+                        name: entry.name,
+                        remove: entry.remove,
+                    },
+                ))
+            }
+            _ => access
+                .newtype_variant_seed(entity)
+                .map(StreamCommand::Despawn),
+        }
+    }
+}
+
+// Deserializes an entity, given the number of preceding spawns
+struct EntitySeed {
+    deferred: usize,
+}
+
+impl<'de> DeserializeSeed<'de> for EntitySeed {
+    type Value = StreamTarget;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_enum(ENTITY, ENTITIES, self)
+    }
+}
+
+impl<'de> Visitor<'de> for EntitySeed {
+    type Value = StreamTarget;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an entity")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        let (variant, access) = data.variant_seed(VariantSeed(ENTITIES))?;
+        let bits = access.newtype_variant::<u64>()?;
+
+        if variant == 0 {
+            Entity::from_bits(bits)
+                .map(StreamTarget::Entity)
+                .ok_or_else(|| de::Error::invalid_value(Unexpected::Unsigned(bits), &self))
+        } else if bits < self.deferred as u64 {
+            Ok(StreamTarget::Spawned(StreamEntity(bits as usize)))
+        } else {
+            Err(de::Error::custom(format_args!(
+                "Deferred entity {bits} is used before it is spawned"
+            )))
+        }
+    }
+}
+
+struct ComponentsSeed<'a> {
+    registry: &'a ComponentRegistry,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for ComponentsSeed<'a> {
+    type Value = Vec<StreamComponent>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for ComponentsSeed<'a> {
+    type Value = Vec<StreamComponent>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a map of components")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut components = Vec::new();
+
+        while let Some(name) = map.next_key::<String>()? {
+            let deserialize = self
+                .registry
+                .get_by_name(&name)
+                .and_then(|entry| entry.deserialize)
+                .ok_or_else(|| {
+                    <A::Error as de::Error>::custom(format_args!(
+                        "Component {name} is not registered for deserialization"
+                    ))
+                })?;
+
+            components.push(map.next_value_seed(ComponentSeed(deserialize))?);
+        }
+
+        Ok(components)
+    }
+}
+
+struct ComponentSeed(DeserializeFn);
+
+impl<'de> DeserializeSeed<'de> for ComponentSeed {
+    type Value = StreamComponent;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let mut erased = <dyn erased_serde::Deserializer>::erase(deserializer);
+        (self.0)(&mut erased).map_err(de::Error::custom)
+    }
+}
+
+// Deserializes the index of a variant by name or index
+struct VariantSeed(&'static [&'static str]);
+
+impl<'de> DeserializeSeed<'de> for VariantSeed {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> Visitor<'de> for VariantSeed {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "one of {:?}", self.0)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        match usize::try_from(v) {
+            Ok(index) if index < self.0.len() => Ok(index),
+            _ => Err(E::invalid_value(Unexpected::Unsigned(v), &self)),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        self.0
+            .iter()
+            .position(|&variant| variant == v)
+            .ok_or_else(|| E::unknown_variant(v, self.0))
+    }
+}
+
+struct PairSeed<A, B>(A, B);
+
+impl<'de, A: DeserializeSeed<'de>, B: DeserializeSeed<'de>> DeserializeSeed<'de>
+    for PairSeed<A, B>
+{
+    type Value = (A::Value, B::Value);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_tuple(2, self)
+    }
+}
+
+impl<'de, A: DeserializeSeed<'de>, B: DeserializeSeed<'de>> Visitor<'de> for PairSeed<A, B> {
+    type Value = (A::Value, B::Value);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a pair")
+    }
+
+    fn visit_seq<S: SeqAccess<'de>>(self, mut seq: S) -> Result<Self::Value, S::Error> {
+        let a = seq
+            .next_element_seed(self.0)?
+            .ok_or_else(|| <S::Error as de::Error>::invalid_length(0, &"a pair"))?;
+        let b = seq
+            .next_element_seed(self.1)?
+            .ok_or_else(|| <S::Error as de::Error>::invalid_length(1, &"a pair"))?;
+
+        Ok((a, b))
+    }
+}
@@ -1,19 +1,142 @@
+use std::{
+    any::type_name,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    error::CommandError, hierarchy, ComponentVisitor, GenericWorld, Resources, VisitComponents,
+};
 use moss_hecs::{
-    Bundle, CommandBuffer as CommandBufferInternal, Component, DynamicBundle, Entity, Frame,
+    Bundle, CommandBuffer as CommandBufferInternal, Component, DynamicBundle, Entity,
+    EntityBuilder, EntityRef, Frame, Query,
 };
 
-#[derive(Default)]
+// Applies a command given the entities spawned by the commandbuffer, and
+// records the failures of the command
+type Write = Box<dyn FnOnce(&mut Frame, &mut Vec<Entity>, &mut Vec<CommandError>) + Send + Sync>;
+type ResourceWrite = Box<dyn FnOnce(&mut Resources) + Send + Sync>;
+
+// A command applied in the order it was recorded
+enum Step {
+    Write(Write),
+    // Applies the next internal commandbuffer, into which a run of contiguous
+    // spawns and inserts was recorded, given the number of inserts recorded
+    // up to the end of the run
+    Components(usize),
+    Despawn(usize, CommandEntity),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Placeholder for an entity spawned by
+/// [CommandBuffer::spawn_deferred], which is resolved to the spawned entity
+/// when the commandbuffer is applied.
+///
+/// The placeholder may only be used in commands of the same commandbuffer,
+/// until the commandbuffer is applied or cleared. Commands targeting the
+/// placeholder otherwise fail with [CommandError::ForeignEntity].
+pub struct DeferredEntity {
+    buffer: u64,
+    index: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The target of a command, which is either an existing or reserved entity or
+/// a [DeferredEntity].
+pub enum CommandEntity {
+    /// An existing or reserved entity
+    Entity(Entity),
+    /// An entity spawned by the same commandbuffer
+    Deferred(DeferredEntity),
+}
+
+impl CommandEntity {
+    fn resolve(self, spawned: &[Entity]) -> Entity {
+        match self {
+            Self::Entity(entity) => entity,
+            Self::Deferred(DeferredEntity { index, .. }) => spawned[index],
+        }
+    }
+}
+
+impl From<Entity> for CommandEntity {
+    fn from(entity: Entity) -> Self {
+        Self::Entity(entity)
+    }
+}
+
+impl From<DeferredEntity> for CommandEntity {
+    fn from(entity: DeferredEntity) -> Self {
+        Self::Deferred(entity)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// Decides in which order the commands of a [CommandBuffer] are applied
+pub enum CommandOrder {
+    /// Applies the commands in the order they were recorded, including
+    /// despawns. Contiguous spawns and inserts into entities are still
+    /// batched.
+    #[default]
+    Recorded,
+    /// Applies the commands grouped by kind for throughput: first all
+    /// [deferred spawns](CommandBuffer::spawn_deferred), then all spawns and
+    /// inserts into entities, which are batched per entity, then the
+    /// remaining commands in the order they were recorded, including
+    /// [batch spawns](CommandBuffer::spawn_batch), and finally all despawns.
+    ///
+    /// Commands which depend on the order, such as a removal followed by an
+    /// insert of the same component, may thus have a different outcome.
+    Relaxed,
+}
+
+static NEXT_BUFFER: AtomicU64 = AtomicU64::new(0);
+
+fn next_buffer() -> u64 {
+    NEXT_BUFFER.fetch_add(1, Ordering::Relaxed)
+}
+
 /// Extends the built in [hecs::CommandBuffer].
 ///
 /// Allows for deferred modifications to the world, spawn, insert, remove,
 /// despawn, or custom closures.
 ///
 /// It is possible to insert a commandbuffer into another commandbuffer.
+///
+/// The commands are applied in the order they were recorded, unless the
+/// commandbuffer is [relaxed](CommandOrder::Relaxed).
+///
+/// Applying or clearing a commandbuffer retains the memory of its command
+/// list and of the components of its spawns and inserts, such that reusing a
+/// commandbuffer, like the one of a [Schedule](crate::Schedule), does not
+/// grow them again each frame. Other commands, such as removes, writes,
+/// [insert_or_spawn](Self::insert_or_spawn), [set_parent](Self::set_parent),
+/// [spawn_deferred](Self::spawn_deferred) and
+/// [despawn_matching](Self::despawn_matching), still allocate when recorded.
 pub struct CommandBuffer {
-    /// Use the already existing hecs::CommmandBuffer
-    components: CommandBufferInternal,
-    despawns: Vec<Entity>,
-    writes: Vec<Box<dyn FnOnce(&mut Frame) + Send + Sync>>,
+    /// Use the already existing hecs::CommmandBuffer, one for each run of
+    /// spawns and inserts. The buffers are kept for reuse once applied.
+    components: Vec<CommandBufferInternal>,
+    // The number of runs which were recorded, and whether the last is open
+    runs: usize,
+    open: bool,
+    // Targets of the inserts recorded into the components, by command index
+    inserts: Vec<(usize, Entity)>,
+    spawns: Vec<EntityBuilder>,
+    despawns: Vec<(usize, CommandEntity)>,
+    steps: Vec<Step>,
+    resources: Vec<ResourceWrite>,
+    len: usize,
+    deferred: usize,
+    // Identifies the placeholders of the commandbuffer, and changes whenever
+    // they are invalidated
+    id: u64,
+    order: CommandOrder,
+}
+
+impl Default for CommandBuffer {
+    fn default() -> Self {
+        Self::with_capacity(0)
+    }
 }
 
 impl CommandBuffer {
@@ -22,66 +145,673 @@ impl CommandBuffer {
         Self::default()
     }
 
-    /// Inserts components into an already existing or reserved entity
-    pub fn insert(&mut self, entity: Entity, components: impl DynamicBundle) {
-        self.components.insert(entity, components)
+    /// Creates a new empty commandbuffer with space for at least `capacity`
+    /// deferred spawns, despawns, and removals or custom commands each,
+    /// without reallocating
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            components: Vec::new(),
+            runs: 0,
+            open: false,
+            inserts: Vec::new(),
+            spawns: Vec::with_capacity(capacity),
+            despawns: Vec::with_capacity(capacity),
+            steps: Vec::with_capacity(capacity),
+            resources: Vec::new(),
+            len: 0,
+            deferred: 0,
+            id: next_buffer(),
+            order: CommandOrder::default(),
+        }
+    }
+
+    /// Returns the order in which the commands are applied
+    pub fn order(&self) -> CommandOrder {
+        self.order
+    }
+
+    /// Sets the order in which the commands are applied.
+    ///
+    /// # Panics
+    /// Panics if the commandbuffer is not empty.
+    pub fn set_order(&mut self, order: CommandOrder) {
+        assert_eq!(
+            self.len, 0,
+            "The order can not be changed with recorded commands"
+        );
+        self.order = order;
+    }
+
+    // Returns true if no commands are recorded, including resource commands
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0 && self.resources.is_empty()
     }
 
-    /// Inserts a single component into an already existing or reserved entity
-    pub fn insert_one(&mut self, entity: Entity, component: impl Component) {
-        self.components.insert(entity, (component,))
+    // Returns the index of the next recorded command
+    fn next_index(&mut self) -> usize {
+        self.len += 1;
+        self.len - 1
+    }
+
+    // Returns the internal commandbuffer of the current run of spawns and
+    // inserts, which is the only one when relaxed
+    fn run(&mut self) -> &mut CommandBufferInternal {
+        if self.runs == self.components.len() {
+            self.components.push(CommandBufferInternal::default());
+        }
+
+        self.open = true;
+        &mut self.components[self.runs]
+    }
+
+    // Ends the current run of spawns and inserts, such that it is applied
+    // before the commands recorded next
+    fn seal(&mut self) {
+        if self.open && self.order == CommandOrder::Recorded {
+            self.steps.push(Step::Components(self.inserts.len()));
+            self.runs += 1;
+            self.open = false;
+        }
+    }
+
+    fn push_write(&mut self, write: Write) {
+        self.seal();
+        self.steps.push(Step::Write(write));
+    }
+
+    // Returns the target of the command `index`, or records the failure of the
+    // command if the target is a placeholder of another commandbuffer
+    fn target(&mut self, index: usize, entity: CommandEntity) -> Option<CommandEntity> {
+        match entity {
+            CommandEntity::Deferred(DeferredEntity { buffer, .. }) if buffer != self.id => {
+                self.push_write(Box::new(move |_, _, errors| {
+                    errors.push(CommandError::ForeignEntity(index))
+                }));
+                None
+            }
+            entity => Some(entity),
+        }
+    }
+
+    // Invalidates the placeholders when the spawned entities are reset
+    fn reset_deferred(&mut self) {
+        if self.deferred > 0 {
+            self.deferred = 0;
+            self.id = next_buffer();
+        }
+    }
+
+    /// Inserts components into an already existing or reserved entity, or an
+    /// entity spawned by [spawn_deferred](Self::spawn_deferred).
+    ///
+    /// See [insert_or_ignore](Self::insert_or_ignore) and
+    /// [insert_or_spawn](Self::insert_or_spawn) for entities which may have
+    /// been despawned before the commandbuffer is applied.
+    pub fn insert(&mut self, entity: impl Into<CommandEntity>, components: impl DynamicBundle) {
+        let index = self.next_index();
+        let entity = match self.target(index, entity.into()) {
+            Some(entity) => entity,
+            None => return,
+        };
+
+        match entity {
+            CommandEntity::Entity(entity) => {
+                self.run().insert(entity, components);
+                self.inserts.push((index, entity));
+            }
+            entity => {
+                let mut builder = EntityBuilder::new();
+                builder.add_bundle(components);
+
+                self.push_write(Box::new(move |w, spawned, errors| {
+                    let entity = entity.resolve(spawned);
+                    if w.insert(entity, builder.build()).is_err() {
+                        errors.push(CommandError::NoSuchEntity(index, entity));
+                    }
+                }))
+            }
+        }
+    }
+
+    /// Inserts components into an entity if it still exists when the
+    /// commandbuffer is applied, and drops the components otherwise
+    pub fn insert_or_ignore(
+        &mut self,
+        entity: impl Into<CommandEntity>,
+        components: impl DynamicBundle,
+    ) {
+        let index = self.next_index();
+        let entity = match self.target(index, entity.into()) {
+            Some(entity) => entity,
+            None => return,
+        };
+
+        let mut builder = EntityBuilder::new();
+        builder.add_bundle(components);
+
+        self.push_write(Box::new(move |w, spawned, _| {
+            let _ = w.insert(entity.resolve(spawned), builder.build());
+        }))
+    }
+
+    /// Inserts components into an entity, or spawns an entity with the same
+    /// handle and components if the entity no longer exists when the
+    /// commandbuffer is applied.
+    ///
+    /// If the id of the entity has since been recycled by another entity, the
+    /// components are dropped and the command fails with
+    /// [CommandError::EntityRecycled], rather than replacing the other entity.
+    pub fn insert_or_spawn(
+        &mut self,
+        entity: impl Into<CommandEntity>,
+        components: impl DynamicBundle,
+    ) {
+        let index = self.next_index();
+        let entity = match self.target(index, entity.into()) {
+            Some(entity) => entity,
+            None => return,
+        };
+
+        let mut builder = EntityBuilder::new();
+        builder.add_bundle(components);
+
+        self.push_write(Box::new(move |w, spawned, errors| {
+            let entity = entity.resolve(spawned);
+            // Reserved entities are also contained
+            if w.contains(entity) {
+                let _ = w.insert(entity, builder.build());
+                return;
+            }
+
+            // Spawning at the handle despawns any entity with the same id,
+            // regardless of its generation.
+            // Safety: the id may be free, in which case the resolved entity is
+            // not live, and is only checked for by contains
+            let current = unsafe { w.find_entity_from_id(entity.id()) };
+            if w.contains(current) {
+                errors.push(CommandError::EntityRecycled(index, entity));
+            } else {
+                w.spawn_at(entity, builder.build());
+            }
+        }))
+    }
+
+    /// Inserts a single component into an already existing or reserved
+    /// entity, see [insert](Self::insert)
+    pub fn insert_one(&mut self, entity: impl Into<CommandEntity>, component: impl Component) {
+        self.insert(entity, (component,))
     }
 
     /// Spawns a new entity with components.
-    /// If the entity ID is desired, consider reserving an entity and then inserting
+    /// If the entity ID is desired, use [spawn_reserved](Self::spawn_reserved)
+    /// or [spawn_deferred](Self::spawn_deferred)
     pub fn spawn(&mut self, components: impl DynamicBundle) {
-        self.components.spawn(components)
+        self.next_index();
+        self.run().spawn(components)
+    }
+
+    /// Spawns a new entity with components, and returns a placeholder which
+    /// can be used to refer to the entity in later commands of the
+    /// commandbuffer, e.g. to insert more components.
+    ///
+    /// The entities are spawned before all other commands when the
+    /// commandbuffer is [relaxed](CommandOrder::Relaxed).
+    ///
+    /// # Example
+    /// ```rust
+    /// use moss_hecs::Frame;
+    /// use moss_hecs_schedule::*;
+    ///
+    /// let mut frame = Frame::default();
+    /// let mut cmd = CommandBuffer::new();
+    ///
+    /// let parent = cmd.spawn_deferred((1_i32,));
+    /// cmd.insert_one(parent, "parent");
+    ///
+    /// cmd.execute(&mut frame);
+    /// assert_eq!(frame.query_mut::<(&i32, &&str)>().into_iter().count(), 1);
+    /// ```
+    pub fn spawn_deferred(&mut self, components: impl DynamicBundle) -> DeferredEntity {
+        self.next_index();
+
+        let mut builder = EntityBuilder::new();
+        builder.add_bundle(components);
+
+        match self.order {
+            CommandOrder::Recorded => self.push_write(Box::new(move |w, spawned, _| {
+                spawned.push(w.spawn(builder.build()));
+            })),
+            CommandOrder::Relaxed => self.spawns.push(builder),
+        }
+
+        self.deferred += 1;
+        DeferredEntity {
+            buffer: self.id,
+            index: self.deferred - 1,
+        }
     }
 
-    /// Despawn an entity from the world
-    pub fn despawn(&mut self, entity: Entity) {
-        self.despawns.push(entity)
+    /// Reserves an entity in `world` and records inserting the components into
+    /// it. The reserved entity is returned right away, and can thus be
+    /// referred to in components created in the same system, even by other
+    /// commandbuffers.
+    ///
+    /// # Example
+    /// ```rust
+    /// use moss_hecs::{Entity, Frame};
+    /// use moss_hecs_schedule::*;
+    ///
+    /// struct Target(Entity);
+    ///
+    /// let mut frame = Frame::default();
+    /// let mut schedule = Schedule::builder()
+    ///     .add_system(|w: SubWorld<()>, mut cmd: Write<CommandBuffer>| {
+    ///         let target = cmd.spawn_reserved(&w, ("target",));
+    ///         cmd.spawn((Target(target),));
+    ///     })
+    ///     .build();
+    ///
+    /// schedule.execute((&mut frame,)).unwrap();
+    ///
+    /// let target = frame
+    ///     .query_mut::<&Target>()
+    ///     .into_iter()
+    ///     .map(|(_, target)| target.0)
+    ///     .next()
+    ///     .unwrap();
+    /// assert_eq!(*frame.get::<&&str>(target).unwrap(), "target");
+    /// ```
+    pub fn spawn_reserved(
+        &mut self,
+        world: &impl GenericWorld,
+        components: impl DynamicBundle,
+    ) -> Entity {
+        let entity = world.reserve_entity();
+        self.insert(entity, components);
+        entity
     }
 
-    /// Remove components from entity
-    pub fn remove<C: Component + Bundle>(&mut self, entity: Entity) {
-        self.writes.push(Box::new(move |w| {
-            let _ = w.remove::<C>(entity);
+    /// Spawns an entity for each bundle of `batch`, which is spawned in one go
+    /// when the commandbuffer is applied. This is considerably faster than
+    /// spawning each entity individually when the bundles are of the same
+    /// type.
+    pub fn spawn_batch<I>(&mut self, batch: I)
+    where
+        I: IntoIterator,
+        I::Item: Bundle + Component,
+    {
+        self.next_index();
+
+        let batch: Vec<_> = batch.into_iter().collect();
+        self.push_write(Box::new(move |w, _, _| {
+            w.spawn_batch(batch);
+        }))
+    }
+
+    /// Despawn an entity from the world. Entities are despawned after all
+    /// other commands have been applied when the commandbuffer is
+    /// [relaxed](CommandOrder::Relaxed).
+    ///
+    /// [execute](Self::execute) panics if the entity no longer exists, see
+    /// [try_execute](Self::try_execute).
+    pub fn despawn(&mut self, entity: impl Into<CommandEntity>) {
+        let index = self.next_index();
+        let entity = match self.target(index, entity.into()) {
+            Some(entity) => entity,
+            None => return,
+        };
+
+        match self.order {
+            CommandOrder::Recorded => {
+                self.seal();
+                self.steps.push(Step::Despawn(index, entity))
+            }
+            CommandOrder::Relaxed => self.despawns.push((index, entity)),
+        }
+    }
+
+    /// Despawn several entities from the world, see [despawn](Self::despawn)
+    pub fn despawn_many(&mut self, entities: impl IntoIterator<Item = Entity>) {
+        entities.into_iter().for_each(|entity| self.despawn(entity))
+    }
+
+    /// Despawn all entities matching the query `Q` when the commandbuffer is
+    /// applied, e.g; `cmd.despawn_matching::<With<(), &Dead>>()`. The
+    /// entities are matched after the preceding removals and custom commands
+    /// have been applied. No components are borrowed.
+    pub fn despawn_matching<Q: Query + 'static>(&mut self) {
+        self.next_index();
+
+        self.push_write(Box::new(|w, _, _| {
+            let entities: Vec<Entity> = w
+                .query::<()>()
+                .with::<Q>()
+                .iter()
+                .map(|(entity, _)| entity)
+                .collect();
+
+            for entity in entities {
+                let _ = w.despawn(entity);
+            }
+        }))
+    }
+
+    /// Spawns a new entity with components as a child of `parent`, see
+    /// [spawn_deferred](Self::spawn_deferred) and
+    /// [set_parent](Self::set_parent)
+    pub fn spawn_child(
+        &mut self,
+        parent: impl Into<CommandEntity>,
+        components: impl DynamicBundle,
+    ) -> DeferredEntity {
+        let child = self.spawn_deferred(components);
+        self.set_parent(child, parent);
+        child
+    }
+
+    /// Sets the [Parent](crate::Parent) of `child` to `parent`, removing the
+    /// child from the [Children](crate::Children) of its previous parent and
+    /// adding it to the children of `parent`.
+    ///
+    /// The command fails if either entity does not exist, or if `parent` is
+    /// `child` or one of its descendants. Despawning an entity does not
+    /// update the hierarchy.
+    pub fn set_parent(
+        &mut self,
+        child: impl Into<CommandEntity>,
+        parent: impl Into<CommandEntity>,
+    ) {
+        let index = self.next_index();
+        let child = match self.target(index, child.into()) {
+            Some(child) => child,
+            None => return,
+        };
+        let parent = match self.target(index, parent.into()) {
+            Some(parent) => parent,
+            None => return,
+        };
+
+        self.push_write(Box::new(move |w, spawned, errors| {
+            let (child, parent) = (child.resolve(spawned), parent.resolve(spawned));
+            if let Err(error) = hierarchy::set_parent(w, index, child, parent) {
+                errors.push(error);
+            }
+        }))
+    }
+
+    /// Remove a bundle of components from an entity, e.g;
+    /// `cmd.remove::<(Position, Velocity)>(entity)`. Nothing is removed if the
+    /// entity lacks any of the components.
+    pub fn remove<C: Bundle + 'static>(&mut self, entity: impl Into<CommandEntity>) {
+        let index = self.next_index();
+        let entity = match self.target(index, entity.into()) {
+            Some(entity) => entity,
+            None => return,
+        };
+
+        self.push_write(Box::new(move |w, spawned, errors| {
+            let entity = entity.resolve(spawned);
+            if let Err(error) = w.remove::<C>(entity) {
+                errors.push(CommandError::from_component(
+                    index,
+                    entity,
+                    error,
+                    type_name::<C>(),
+                ));
+            }
         }))
     }
 
     /// Remove a single component from the world
-    pub fn remove_one<C: Component>(&mut self, entity: Entity) {
-        self.writes.push(Box::new(move |w| {
-            let _ = w.remove_one::<C>(entity);
+    pub fn remove_one<C: Component>(&mut self, entity: impl Into<CommandEntity>) {
+        let index = self.next_index();
+        let entity = match self.target(index, entity.into()) {
+            Some(entity) => entity,
+            None => return,
+        };
+
+        self.push_write(Box::new(move |w, spawned, errors| {
+            let entity = entity.resolve(spawned);
+            if let Err(error) = w.remove_one::<C>(entity) {
+                errors.push(CommandError::from_component(
+                    index,
+                    entity,
+                    error,
+                    type_name::<C>(),
+                ));
+            }
         }))
     }
 
-    /// Applies the recorded commands on the world
+    /// Inserts or replaces the resource of type `T` when the commandbuffer is
+    /// applied to [Resources], see [execute_resources](Self::execute_resources)
+    pub fn insert_resource<T: Component>(&mut self, value: T) {
+        self.next_index();
+        self.resources.push(Box::new(move |resources| {
+            resources.insert(value);
+        }))
+    }
+
+    /// Removes the resource of type `T` when the commandbuffer is applied to
+    /// [Resources], see [execute_resources](Self::execute_resources)
+    pub fn remove_resource<T: Component>(&mut self) {
+        self.next_index();
+        self.resources.push(Box::new(|resources| {
+            resources.remove::<T>();
+        }))
+    }
+
+    /// Applies the recorded resource commands on `resources`, in the order
+    /// they were recorded.
+    ///
+    /// A [Schedule](crate::Schedule) executed with [Resources] as data
+    /// applies the resource commands of its commandbuffer whenever it flushes.
+    /// Flushing fails with [Error::NoResources](crate::Error::NoResources) if
+    /// resource commands were recorded but the schedule was executed with
+    /// other data, and the commands are discarded.
+    pub fn execute_resources(&mut self, resources: &mut Resources) {
+        self.resources.drain(..).for_each(|cmd| cmd(resources));
+    }
+
+    /// Discards the resource commands, returning true if there were any
+    pub(crate) fn discard_resources(&mut self) -> bool {
+        let recorded = !self.resources.is_empty();
+        self.resources.clear();
+        recorded
+    }
+
+    /// Applies the recorded commands on the world, leaving the commandbuffer
+    /// empty except for the resource commands.
+    ///
+    /// Commands which can not be applied are skipped, except for despawns of
+    /// entities which no longer exist, which panic. See
+    /// [try_execute](Self::try_execute).
     pub fn execute(&mut self, frame: &mut Frame) {
-        self.components.run_on(frame);
+        let errors = self.apply(frame);
 
-        self.writes.drain(..).for_each(|cmd| (cmd)(frame));
+        if let Some(error) = errors
+            .iter()
+            .find(|error| matches!(error, CommandError::DespawnFailed(..)))
+        {
+            panic!("Failed to despawn entity: {error}");
+        }
+    }
 
-        self.despawns
+    /// Applies the recorded commands on the world like
+    /// [execute](Self::execute), but returns the commands which could not be
+    /// applied, such as inserts into despawned entities, instead of skipping
+    /// them or panicking.
+    ///
+    /// All other commands are still applied. The errors are ordered by the
+    /// index of the failed command.
+    pub fn try_execute(&mut self, frame: &mut Frame) -> Result<(), Vec<CommandError>> {
+        let errors = self.apply(frame);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Applies the recorded commands and returns the failures
+    fn apply(&mut self, frame: &mut Frame) -> Vec<CommandError> {
+        self.seal();
+
+        let mut errors = Vec::new();
+
+        let mut spawned: Vec<Entity> = self
+            .spawns
             .drain(..)
-            .for_each(|e| frame.despawn(e).expect("Failed to despawn entity"));
+            .map(|mut builder| frame.spawn(builder.build()))
+            .collect();
+
+        // The inserts of the internal commandbuffers skip missing entities
+        let mut checked = 0;
+        let mut check_inserts = |end: usize, frame: &Frame, errors: &mut Vec<CommandError>| {
+            errors.extend(
+                self.inserts[checked..end]
+                    .iter()
+                    .filter(|&&(_, entity)| !frame.contains(entity))
+                    .map(|&(index, entity)| CommandError::NoSuchEntity(index, entity)),
+            );
+            checked = end;
+        };
+
+        let mut runs = self.components.iter_mut();
+
+        if self.order == CommandOrder::Relaxed {
+            check_inserts(self.inserts.len(), frame, &mut errors);
+            if let Some(components) = runs.next() {
+                components.run_on(frame);
+            }
+        }
+
+        for step in self.steps.drain(..) {
+            match step {
+                Step::Write(cmd) => cmd(frame, &mut spawned, &mut errors),
+                Step::Components(end) => {
+                    check_inserts(end, frame, &mut errors);
+                    runs.next().expect("Run was not recorded").run_on(frame);
+                }
+                Step::Despawn(index, entity) => {
+                    despawn(frame, index, entity, &spawned, &mut errors)
+                }
+            }
+        }
+
+        for (index, entity) in self.despawns.drain(..) {
+            despawn(frame, index, entity, &spawned, &mut errors)
+        }
+
+        self.inserts.clear();
+        self.runs = 0;
+        self.open = false;
+        self.len = 0;
+        self.reset_deferred();
+        errors.sort_by_key(CommandError::index);
+        errors
     }
 
-    /// Nest a commandbuffer
-    pub fn append(&mut self, mut other: Self) {
-        self.write(move |w| other.execute(w))
+    /// Moves all commands of `other` into the commandbuffer, leaving `other`
+    /// empty, e.g. to merge the buffers of several threads before applying
+    /// them.
+    ///
+    /// The commands of `other` are applied in the order of `other`, together
+    /// at the position of the append among the removals and custom commands
+    /// of the commandbuffer.
+    ///
+    /// The allocated memory of `other` moves along with its commands, so
+    /// `other` no longer retains it, unlike when it is applied or cleared.
+    /// Only its order is kept.
+    pub fn append(&mut self, other: &mut Self) {
+        self.resources.append(&mut other.resources);
+
+        let offset = self.len;
+        self.len += other.len;
+
+        let order = other.order;
+        let mut other = std::mem::replace(
+            other,
+            Self {
+                order,
+                ..Self::default()
+            },
+        );
+        self.push_write(Box::new(move |w, _, errors| {
+            errors.extend(other.apply(w).into_iter().map(|error| error.offset(offset)))
+        }))
     }
 
     /// Record a custom command modifying the world
     pub fn write(&mut self, cmd: impl FnOnce(&mut Frame) + Component) {
-        self.writes.push(Box::new(cmd))
+        self.next_index();
+        self.push_write(Box::new(move |w, _, _| cmd(w)))
     }
 
-    /// Drop all recorded commands
+    /// Drop all recorded commands, retaining the allocated memory
     pub fn clear(&mut self) {
+        self.inserts.clear();
+        self.spawns.clear();
         self.despawns.clear();
-        self.writes.clear();
-        self.components.clear();
+        self.resources.clear();
+        self.steps.clear();
+        self.components
+            .iter_mut()
+            .for_each(CommandBufferInternal::clear);
+        self.runs = 0;
+        self.open = false;
+        self.len = 0;
+        self.reset_deferred();
+    }
+}
+
+fn despawn(
+    frame: &mut Frame,
+    index: usize,
+    entity: CommandEntity,
+    spawned: &[Entity],
+    errors: &mut Vec<CommandError>,
+) {
+    let entity = entity.resolve(spawned);
+    if frame.despawn(entity).is_err() {
+        errors.push(CommandError::DespawnFailed(index, entity));
+    }
+}
+
+/// Clones the components of a subworld onto another entity, see
+/// [SubWorldRaw::clone_entity_into](crate::SubWorldRaw::clone_entity_into).
+///
+/// Implemented for every [VisitComponents] of [Clone] components.
+pub trait CloneComponents {
+    /// Records an insert of each component of `entity` onto `target`
+    fn clone_components(entity: &EntityRef, target: Entity, cmd: &mut CommandBuffer);
+}
+
+#[doc(hidden)]
+/// Collects a function cloning each component
+pub struct CloneFns(Vec<fn(&EntityRef, Entity, &mut CommandBuffer)>);
+
+impl<C: Component + Clone> ComponentVisitor<C> for CloneFns {
+    fn visit(&mut self, _: bool) {
+        self.0.push(clone_component::<C>)
+    }
+}
+
+impl<T: VisitComponents<CloneFns>> CloneComponents for T {
+    fn clone_components(entity: &EntityRef, target: Entity, cmd: &mut CommandBuffer) {
+        let mut fns = CloneFns(Vec::new());
+        T::visit_components(&mut fns, false);
+        fns.0.iter().for_each(|clone| clone(entity, target, cmd))
+    }
+}
+
+fn clone_component<C: Component + Clone>(
+    entity: &EntityRef,
+    target: Entity,
+    cmd: &mut CommandBuffer,
+) {
+    if let Some(component) = entity.get::<&C>() {
+        cmd.insert_one(target, (*component).clone())
     }
 }
@@ -0,0 +1,114 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    borrow::{Borrows, ComponentBorrow, ContextBorrow, IntoBorrow},
+    Context, Result,
+};
+
+/// Trait which defines any function that decides whether a system should run.
+///
+/// Conditions borrow from the context in the same manner as systems.
+pub trait Condition<Args> {
+    /// Evaluates the condition by borrowing from context
+    fn evaluate(&mut self, context: &Context) -> Result<bool>;
+
+    /// Returns which data will be accessed
+    fn borrows() -> Borrows;
+}
+
+macro_rules! tuple_impl {
+    ($($name: ident), *) => {
+        impl<Func, $($name,) *> Condition<($($name,)*)> for Func
+        where
+            for<'a, 'b> &'b mut Func:
+                FnMut($($name,)*) -> bool +
+                FnMut($(<$name::Borrow as ContextBorrow<'a>>::Target),*) -> bool,
+                $($name: IntoBorrow + ComponentBorrow,)*
+        {
+            fn evaluate(&mut self, context: &Context) -> Result<bool> {
+                let mut func = self;
+                Ok((&mut func)($($name::Borrow::borrow(context)?), *))
+            }
+
+            fn borrows() -> Borrows {
+                ([].iter()
+                    $(.chain($name::borrows().iter())) *).cloned()
+                .collect()
+            }
+        }
+    };
+}
+
+impl<F: FnMut() -> bool> Condition<()> for F {
+    fn evaluate(&mut self, _: &Context) -> Result<bool> {
+        Ok((self)())
+    }
+
+    fn borrows() -> Borrows {
+        Borrows::default()
+    }
+}
+
+impl_for_tuples!(tuple_impl);
+
+// Type erased boxed condition
+pub(crate) struct DynamicCondition {
+    func: Box<dyn FnMut(&Context) -> Result<bool> + Send>,
+    pub(crate) borrows: Borrows,
+}
+
+impl DynamicCondition {
+    pub(crate) fn new<C, Args>(mut condition: C) -> Self
+    where
+        C: 'static + Condition<Args> + Send,
+    {
+        Self {
+            func: Box::new(move |context| condition.evaluate(context)),
+            borrows: C::borrows(),
+        }
+    }
+
+    pub(crate) fn evaluate(&mut self, context: &Context) -> Result<bool> {
+        (self.func)(context)
+    }
+}
+
+// A condition shared between several systems
+#[derive(Clone)]
+pub(crate) struct SharedCondition {
+    inner: Arc<Mutex<(DynamicCondition, Option<(u64, bool)>)>>,
+    borrows: Borrows,
+}
+
+impl SharedCondition {
+    pub(crate) fn new(condition: DynamicCondition) -> Self {
+        Self {
+            borrows: condition.borrows.clone(),
+            inner: Arc::new(Mutex::new((condition, None))),
+        }
+    }
+
+    /// Returns a condition for a single system. The shared condition is
+    /// evaluated at most once per run, and the result is reused by all
+    /// instances.
+    pub(crate) fn instance(&self) -> DynamicCondition {
+        let inner = self.inner.clone();
+
+        DynamicCondition {
+            func: Box::new(move |context| {
+                let mut inner = inner.lock().expect("Shared condition was poisoned");
+                let (condition, last) = &mut *inner;
+
+                match *last {
+                    Some((run, result)) if run == context.run() => Ok(result),
+                    _ => {
+                        let result = condition.evaluate(context)?;
+                        *last = Some((context.run(), result));
+                        Ok(result)
+                    }
+                }
+            }),
+            borrows: self.borrows.clone(),
+        }
+    }
+}
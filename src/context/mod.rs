@@ -1,16 +1,40 @@
 //! This module provides types and traits associated to accessing of borrowed
 //! values.
-use std::{any::TypeId, cmp::Ordering, ptr::NonNull};
+use std::{
+    any::TypeId,
+    cmp::Ordering,
+    ptr::NonNull,
+    sync::{
+        atomic::{self, AtomicU64},
+        Arc,
+    },
+};
 
 use atomic_refcell::AtomicRefCell;
 
-use crate::{borrow::ContextBorrow, Error, IntoAccess, Result};
+use crate::{
+    borrow::{ContextBorrow, Locals},
+    deferred::DeferredQueue,
+    tracking::ChangeTicks,
+    Error, IntoAccess, Result,
+};
 use moss_hecs::Component;
 
 /// Holds all data necessary for the execution of the world.
 /// The data is held by references, and needs to outlive the context itself
 pub struct Context<'a> {
     data: &'a dyn Data,
+    run: u64,
+    locals: Option<&'a Locals>,
+    system: usize,
+    deferred: Option<&'a Arc<DeferredQueue>>,
+    ticks: Option<&'a ChangeTicks>,
+}
+
+static NEXT_RUN: AtomicU64 = AtomicU64::new(0);
+
+fn next_run() -> u64 {
+    NEXT_RUN.fetch_add(1, atomic::Ordering::Relaxed)
 }
 
 // Safe since Send + Sync is required for impl of IntoData
@@ -18,12 +42,108 @@ unsafe impl Send for Context<'_> {}
 unsafe impl Sync for Context<'_> {}
 
 mod erased_cell;
+mod resources;
 use erased_cell::*;
+pub use resources::*;
 
 impl<'a> Context<'a> {
     /// Construct a new context from the tuple of references `data`
     pub fn new(data: &'a dyn Data) -> Context {
-        Self { data }
+        Self {
+            data,
+            run: next_run(),
+            locals: None,
+            system: usize::MAX,
+            deferred: None,
+            ticks: None,
+        }
+    }
+
+    /// Returns a context over the same data for a new run of a schedule
+    pub(crate) fn next_run(&self) -> Context<'a> {
+        Self {
+            data: self.data,
+            run: next_run(),
+            locals: self.locals,
+            system: self.system,
+            deferred: self.deferred,
+            ticks: self.ticks,
+        }
+    }
+
+    /// Returns a context over the same data which continues the run `run`
+    pub(crate) fn with_run(&self, run: u64) -> Context<'a> {
+        Self {
+            data: self.data,
+            run,
+            locals: self.locals,
+            system: self.system,
+            deferred: self.deferred,
+            ticks: self.ticks,
+        }
+    }
+
+    /// Returns a context over the same data which provides `locals` to
+    /// [Local](crate::Local) borrows of the system `system`
+    pub(crate) fn with_locals<'b>(&'b self, locals: &'b Locals, system: usize) -> Context<'b> {
+        Context {
+            data: self.data,
+            run: self.run,
+            locals: Some(locals),
+            system,
+            deferred: self.deferred,
+            ticks: self.ticks,
+        }
+    }
+
+    /// Returns a context over the same data which provides `deferred` to
+    /// subworlds, to be applied by the flush of the schedule, and counts the
+    /// writes to the data watched in `ticks`
+    pub(crate) fn with_schedule<'b>(
+        &'b self,
+        deferred: &'b Arc<DeferredQueue>,
+        ticks: &'b ChangeTicks,
+    ) -> Context<'b> {
+        Context {
+            data: self.data,
+            run: self.run,
+            locals: self.locals,
+            system: self.system,
+            deferred: Some(deferred),
+            ticks: Some(ticks),
+        }
+    }
+
+    /// Returns the deferred commands of the running schedule
+    pub(crate) fn deferred(&self) -> Option<&'a Arc<DeferredQueue>> {
+        self.deferred
+    }
+
+    /// Returns the change ticks of the running schedule
+    pub(crate) fn ticks(&self) -> Option<&'a ChangeTicks> {
+        self.ticks
+    }
+
+    /// Returns the change tick of `T`, if it is watched by the running
+    /// schedule
+    pub(crate) fn tick<T: 'static>(&self) -> Option<&'a AtomicU64> {
+        self.ticks?.get(TypeId::of::<T>())
+    }
+
+    /// Returns the locals of the current system
+    pub(crate) fn locals(&self) -> Option<&'a Locals> {
+        self.locals
+    }
+
+    /// Returns the id of the current system, which is `usize::MAX` outside of
+    /// systems
+    pub(crate) fn system(&self) -> usize {
+        self.system
+    }
+
+    /// Returns an id which is unique to the current run of a schedule
+    pub(crate) fn run(&self) -> u64 {
+        self.run
     }
 
     /// Borrows data of type T from the context. Does not panic.
@@ -50,6 +170,34 @@ pub trait Data {
     fn get(&self, ty: TypeId) -> Option<&AtomicRefCell<NonNull<u8>>>;
 }
 
+/// [Data] which replaces the value of one type of other data
+pub(crate) struct DataOverlay<'a> {
+    base: &'a dyn Data,
+    cell: ErasedCell,
+}
+
+impl<'a> DataOverlay<'a> {
+    /// # Safety
+    /// `value` must outlive the overlay, and not be accessed while the overlay
+    /// is used
+    pub(crate) unsafe fn new<T: Component>(base: &'a dyn Data, value: &mut T) -> Self {
+        Self {
+            base,
+            cell: ErasedCell::from_ref(value),
+        }
+    }
+}
+
+impl<'a> Data for DataOverlay<'a> {
+    fn get(&self, ty: TypeId) -> Option<&AtomicRefCell<NonNull<u8>>> {
+        if self.cell.id == ty {
+            Some(&self.cell.cell)
+        } else {
+            self.base.get(ty)
+        }
+    }
+}
+
 /// Convert a tuple or other type into [Data].
 pub trait IntoData<With>: Send + Sync {
     /// The corresponding [Data] type.
@@ -95,6 +243,68 @@ macro_rules! tuple_impl {
 
 impl_for_tuples_idx!(tuple_impl);
 
+#[macro_export]
+/// Declares a struct of mutable references which can be passed by mutable
+/// reference to the execution of a schedule in place of a tuple.
+///
+/// The fields are resolved by type like the elements of a tuple, and thus
+/// need to be of distinct types.
+///
+/// # Example
+/// ```rust
+/// use moss_hecs::Frame;
+/// use moss_hecs_schedule::*;
+///
+/// struct Physics {
+///     gravity: f32,
+/// }
+///
+/// impl_into_data! {
+///     struct GameContext<'a> {
+///         frame: &'a mut Frame,
+///         physics: &'a mut Physics,
+///     }
+/// }
+///
+/// let mut schedule = Schedule::builder()
+///     .add_system(|physics: Read<Physics>| assert_eq!(physics.gravity, 9.81))
+///     .build();
+///
+/// let mut frame = Frame::default();
+/// let mut physics = Physics { gravity: 9.81 };
+///
+/// schedule
+///     .execute(&mut GameContext {
+///         frame: &mut frame,
+///         physics: &mut physics,
+///     })
+///     .unwrap();
+/// ```
+macro_rules! impl_into_data {
+    (
+        $(#[$meta: meta])*
+        $vis: vis struct $name: ident<$lt: lifetime> {
+            $($field_vis: vis $field: ident: &$field_lt: lifetime mut $ty: ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name<$lt> {
+            $($field_vis $field: &$field_lt mut $ty),*
+        }
+
+        impl<'__borrow, $lt, __With> $crate::IntoData<__With> for &'__borrow mut $name<$lt>
+        where
+            ($(&'__borrow mut $ty,)*): $crate::IntoData<__With>,
+        {
+            type Target = <($(&'__borrow mut $ty,)*) as $crate::IntoData<__With>>::Target;
+
+            unsafe fn into_data(self, with: &mut __With) -> Self::Target {
+                $crate::IntoData::into_data(($(&mut *self.$field,)*), with)
+            }
+        }
+    };
+}
+
 impl<const C: usize> Data for [ErasedCell; C] {
     fn get(&self, ty: TypeId) -> Option<&AtomicRefCell<NonNull<u8>>> {
         let mut low = 0;
@@ -0,0 +1,162 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    ptr::NonNull,
+};
+
+use atomic_refcell::AtomicRefCell;
+use moss_hecs::Component;
+
+use super::{erased_cell::ErasedCell, Data, IntoData};
+
+#[derive(Default)]
+/// A map of values by type, which can be provided to the execution of a
+/// schedule in place of a tuple of references.
+pub struct Resources {
+    values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Resources {
+    /// Creates a new empty resource map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts a value, returning the previous value of the same type
+    ///
+    /// An existing value is replaced in place.
+    pub fn insert<T: Component>(&mut self, value: T) -> Option<T> {
+        match self.get_mut::<T>() {
+            Some(old) => Some(std::mem::replace(old, value)),
+            None => {
+                self.values.insert(TypeId::of::<T>(), Box::new(value));
+                None
+            }
+        }
+    }
+
+    /// Removes and returns the value of type `T`
+    pub fn remove<T: Component>(&mut self) -> Option<T> {
+        self.values
+            .remove(&TypeId::of::<T>())
+            .map(|old| *old.downcast().expect("Value stored by wrong type"))
+    }
+
+    /// Returns a reference to the value of type `T`
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Returns a mutable reference to the value of type `T`
+    pub fn get_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.values
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// Returns true if a value of type `T` exists
+    pub fn contains<T: Component>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Returns the number of values
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if there are no values
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// [Data] which borrows the values of [Resources]
+///
+/// The resources themselves are also available, and are borrowed mutably
+/// when the schedule flushes to apply resource commands, see
+/// [CommandBuffer::insert_resource](crate::CommandBuffer::insert_resource).
+/// Values removed or replaced by resource commands are no longer provided,
+/// and values of types which did not exist when the execution started are
+/// only provided from the next execution.
+pub struct ResourcesData {
+    cells: Vec<ResourceCell>,
+    resources: AtomicRefCell<NonNull<u8>>,
+}
+
+struct ResourceCell {
+    cell: ErasedCell,
+    // The boxed resource value, which is `None` for the value provided along
+    // with the resources
+    value: Option<NonNull<u8>>,
+}
+
+impl Data for ResourcesData {
+    fn get(&self, ty: TypeId) -> Option<&AtomicRefCell<NonNull<u8>>> {
+        if ty == TypeId::of::<Resources>() {
+            return Some(&self.resources);
+        }
+
+        let cell = &self.cells[self
+            .cells
+            .binary_search_by(|cell| cell.cell.cmp_id(ty))
+            .ok()?];
+
+        // The resources are only borrowed mutably while applying resource
+        // commands, which may remove or replace the value
+        if let (Some(value), Ok(resources)) = (cell.value, self.resources.try_borrow()) {
+            // Safety: the resources outlive the data
+            let resources = unsafe { resources.cast::<Resources>().as_ref() };
+            let current = resources
+                .values
+                .get(&ty)
+                .map(|current| NonNull::from(&**current).cast::<u8>());
+
+            if current != Some(value) {
+                return None;
+            }
+        }
+
+        Some(&cell.cell.cell)
+    }
+}
+
+/// The value provided along with the resources, i.e; the commandbuffer, takes
+/// precedence over a value of the same type in the resources.
+impl<With: Component> IntoData<With> for &mut Resources {
+    type Target = ResourcesData;
+
+    unsafe fn into_data(self, with: &mut With) -> Self::Target {
+        let with = ErasedCell::from_ref(with);
+        let mut resources = NonNull::from(self);
+
+        let mut cells: Vec<_> = resources
+            .as_mut()
+            .values
+            .iter_mut()
+            .filter(|(&id, _)| id != with.id && id != TypeId::of::<Resources>())
+            .map(|(&id, value)| {
+                let value: NonNull<u8> = NonNull::from(&mut **value).cast();
+                ResourceCell {
+                    cell: ErasedCell {
+                        cell: AtomicRefCell::new(value),
+                        id,
+                    },
+                    value: Some(value),
+                }
+            })
+            .collect();
+
+        cells.push(ResourceCell {
+            cell: with,
+            value: None,
+        });
+        cells.sort_unstable_by(|a, b| a.cell.cmp(&b.cell));
+
+        ResourcesData {
+            cells,
+            resources: AtomicRefCell::new(resources.cast()),
+        }
+    }
+}
@@ -0,0 +1,319 @@
+use std::{
+    any::TypeId,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Mutex,
+    },
+};
+
+use moss_hecs::Frame;
+
+use crate::{
+    borrow::{Borrows, ComponentBorrow, ContextBorrow, IntoBorrow},
+    CommandBuffer, CommandError, CommandOrder, Context, Error, IntoAccess, Result,
+};
+
+// Applies a command given its index in the queue
+type Command = Box<dyn FnOnce(&mut Frame, usize) -> std::result::Result<(), CommandError> + Send>;
+
+struct Node<T> {
+    value: T,
+    next: *mut Node<T>,
+}
+
+// Lock-free stack, which is taken as a whole
+struct Stack<T> {
+    head: AtomicPtr<Node<T>>,
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+impl<T> Stack<T> {
+    fn push(&self, value: T) {
+        let node = Box::into_raw(Box::new(Node {
+            value,
+            next: ptr::null_mut(),
+        }));
+
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // Safety: the node is not shared until the exchange succeeds
+            unsafe { (*node).next = head };
+
+            match self
+                .head
+                .compare_exchange_weak(head, node, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Removes all values, in the order they were pushed
+    fn take(&self) -> Vec<T> {
+        let mut node = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+        let mut values = Vec::new();
+
+        while !node.is_null() {
+            // Safety: the nodes were detached from the stack by the swap
+            let boxed = unsafe { Box::from_raw(node) };
+            node = boxed.next;
+            values.push(boxed.value);
+        }
+
+        values.reverse();
+        values
+    }
+}
+
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        self.take();
+    }
+}
+
+/// Lock-free queue of world modifications deferred by subworlds, which are
+/// applied when the schedule flushes
+#[derive(Default)]
+pub(crate) struct DeferredQueue {
+    commands: Stack<Command>,
+    // The commandbuffers recorded by systems, by the id of the system
+    buffers: Stack<(usize, CommandBuffer)>,
+    // Applied commandbuffers, which are reused by the next systems
+    spare: Mutex<Vec<CommandBuffer>>,
+}
+
+// The commands are Send and only ever accessed by the thread which pushes or
+// applies them
+unsafe impl Send for DeferredQueue {}
+unsafe impl Sync for DeferredQueue {}
+
+impl DeferredQueue {
+    pub(crate) fn push(
+        &self,
+        command: impl FnOnce(&mut Frame, usize) -> std::result::Result<(), CommandError>
+            + Send
+            + 'static,
+    ) {
+        self.commands.push(Box::new(command))
+    }
+
+    /// Applies all commands to the frame, and returns the errors of the
+    /// commands which failed
+    pub(crate) fn apply(&self, frame: &mut Frame) -> Vec<CommandError> {
+        self.commands
+            .take()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, command)| command(frame, index).err())
+            .collect()
+    }
+
+    /// Returns an empty commandbuffer, reusing the memory of applied ones
+    fn buffer(&self) -> CommandBuffer {
+        self.spare
+            .lock()
+            .expect("Commandbuffers were poisoned")
+            .pop()
+            .unwrap_or_default()
+    }
+
+    fn push_buffer(&self, system: usize, buffer: CommandBuffer) {
+        if buffer.is_empty() {
+            self.recycle([buffer]);
+        } else {
+            self.buffers.push((system, buffer));
+        }
+    }
+
+    /// Removes the commandbuffers recorded by systems, ordered by the systems
+    /// which recorded them
+    pub(crate) fn take_buffers(&self) -> Vec<CommandBuffer> {
+        let mut buffers = self.buffers.take();
+        buffers.sort_by_key(|&(system, _)| system);
+        buffers.into_iter().map(|(_, buffer)| buffer).collect()
+    }
+
+    /// Clears the commandbuffers and keeps them for reuse
+    pub(crate) fn recycle(&self, buffers: impl IntoIterator<Item = CommandBuffer>) {
+        let mut spare = self.spare.lock().expect("Commandbuffers were poisoned");
+
+        spare.extend(buffers.into_iter().map(|mut buffer| {
+            buffer.clear();
+            buffer.set_order(CommandOrder::default());
+            buffer
+        }));
+    }
+}
+
+/// Borrows the deferred commands of the running schedule
+pub(crate) struct Deferred<'a>(Option<&'a DeferredQueue>);
+
+impl<'a> Deferred<'a> {
+    pub(crate) fn apply(&self, frame: &mut Frame) -> Vec<CommandError> {
+        self.0.map(|queue| queue.apply(frame)).unwrap_or_default()
+    }
+
+    pub(crate) fn take_buffers(&self) -> Vec<CommandBuffer> {
+        self.0.map(DeferredQueue::take_buffers).unwrap_or_default()
+    }
+
+    pub(crate) fn recycle(&self, buffers: Vec<CommandBuffer>) {
+        if let Some(queue) = self.0 {
+            queue.recycle(buffers)
+        }
+    }
+}
+
+impl<'a> ContextBorrow<'a> for Deferred<'a> {
+    type Target = Self;
+
+    fn borrow(context: &'a Context) -> Result<Self::Target> {
+        Ok(Self(context.deferred().map(|queue| &**queue)))
+    }
+}
+
+// The queue is synchronized internally, and the frame is borrowed by the
+// flush itself
+impl ComponentBorrow for Deferred<'_> {
+    fn borrows() -> Borrows {
+        Borrows::new()
+    }
+
+    fn has<U: IntoAccess>() -> bool {
+        false
+    }
+
+    fn has_dynamic(_: TypeId, _: bool) -> bool {
+        false
+    }
+}
+
+pub(crate) struct DeferredBorrower;
+
+impl IntoBorrow for Deferred<'_> {
+    type Borrow = DeferredBorrower;
+}
+
+impl<'a> ContextBorrow<'a> for DeferredBorrower {
+    type Target = Deferred<'a>;
+
+    fn borrow(context: &'a Context) -> Result<Self::Target> {
+        Self::Target::borrow(context)
+    }
+}
+
+/// A commandbuffer owned by the running system, which is applied to the world
+/// when the schedule is next flushed, including the flush at the end of the
+/// execution. The commands are applied after those of the shared
+/// [CommandBuffer] resource, in the order the systems were added to the
+/// schedule, regardless of the order they ran in.
+///
+/// Unlike `Write<CommandBuffer>`, each system records into its own buffer, so
+/// systems taking `Commands` do not conflict with each other. The buffers are
+/// reused once applied, which retains their memory as described for
+/// [CommandBuffer].
+/// Commands which can not be applied fail the flush with
+/// [Error::CommandsFailed], see
+/// [CommandBuffer::try_execute](crate::CommandBuffer::try_execute). Resource
+/// commands are applied to the [Resources](crate::Resources) the schedule is
+/// executed with, and fail the flush if there are none.
+///
+/// # Example
+/// ```rust
+/// use moss_hecs::Frame;
+/// use moss_hecs_schedule::*;
+///
+/// let mut frame = Frame::default();
+/// frame.spawn((1_i32,));
+///
+/// let mut schedule = Schedule::builder()
+///     .add_system(|w: SubWorld<&i32>, mut cmd: Commands| {
+///         for (_, val) in w.query::<&i32>().iter() {
+///             cmd.spawn((*val as f32,));
+///         }
+///     })
+///     .build();
+///
+/// schedule.execute((&mut frame,)).unwrap();
+/// assert_eq!(frame.query_mut::<&f32>().into_iter().count(), 1);
+/// ```
+pub struct Commands<'a> {
+    buffer: CommandBuffer,
+    queue: &'a DeferredQueue,
+    system: usize,
+}
+
+impl<'a> Deref for Commands<'a> {
+    type Target = CommandBuffer;
+
+    fn deref(&self) -> &Self::Target {
+        &self.buffer
+    }
+}
+
+impl<'a> DerefMut for Commands<'a> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.buffer
+    }
+}
+
+impl<'a> Drop for Commands<'a> {
+    fn drop(&mut self) {
+        let buffer = std::mem::take(&mut self.buffer);
+        self.queue.push_buffer(self.system, buffer);
+    }
+}
+
+impl<'a> ContextBorrow<'a> for Commands<'a> {
+    type Target = Self;
+
+    fn borrow(context: &'a Context) -> Result<Self::Target> {
+        let queue = context.deferred().ok_or(Error::NoSchedule)?;
+
+        Ok(Self {
+            buffer: queue.buffer(),
+            queue,
+            system: context.system(),
+        })
+    }
+}
+
+// The buffer is owned by the system, and the queue is synchronized internally
+impl ComponentBorrow for Commands<'_> {
+    fn borrows() -> Borrows {
+        Borrows::new()
+    }
+
+    fn has<U: IntoAccess>() -> bool {
+        false
+    }
+
+    fn has_dynamic(_: TypeId, _: bool) -> bool {
+        false
+    }
+}
+
+#[doc(hidden)]
+pub struct CommandsBorrower;
+
+impl IntoBorrow for Commands<'_> {
+    type Borrow = CommandsBorrower;
+}
+
+impl<'a> ContextBorrow<'a> for CommandsBorrower {
+    type Target = Commands<'a>;
+
+    fn borrow(context: &'a Context) -> Result<Self::Target> {
+        Self::Target::borrow(context)
+    }
+}
@@ -0,0 +1,72 @@
+use std::{any::TypeId, fmt::Write};
+
+use crate::Schedule;
+
+fn escape(name: &str) -> String {
+    name.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Schedule {
+    /// Returns the systems of the schedule and the data they access as a
+    /// [DOT](https://graphviz.org/doc/info/lang.html) graph.
+    ///
+    /// Systems are grouped into clusters by batch, and connected to the data
+    /// they read (dashed) or write (solid).
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        self.write_dot(&mut out)
+            .expect("Writing to a string does not fail");
+        out
+    }
+
+    fn write_dot(&self, out: &mut String) -> std::fmt::Result {
+        let mut accesses: Vec<(TypeId, &str)> = Vec::new();
+        let mut edges = String::new();
+
+        writeln!(out, "digraph schedule {{")?;
+
+        for batch in self.batches() {
+            let flush = if batch.has_flush() { " (flush)" } else { "" };
+
+            writeln!(out, "  subgraph cluster_{} {{", batch.index())?;
+            writeln!(out, "    label=\"Batch {}{}\";", batch.index(), flush)?;
+
+            for (i, system) in batch.systems().iter().enumerate() {
+                let node = format!("system_{}_{}", batch.index(), i);
+                writeln!(
+                    out,
+                    "    {} [label=\"{}\", shape=box];",
+                    node,
+                    escape(system.name())
+                )?;
+
+                for access in system.borrows() {
+                    let index = match accesses.iter().position(|&(id, _)| id == access.id()) {
+                        Some(index) => index,
+                        None => {
+                            accesses.push((access.id(), access.name()));
+                            accesses.len() - 1
+                        }
+                    };
+
+                    let style = if access.exclusive() {
+                        "label=\"write\""
+                    } else {
+                        "label=\"read\", style=dashed"
+                    };
+
+                    writeln!(edges, "  {} -> access_{} [{}];", node, index, style)?;
+                }
+            }
+
+            writeln!(out, "  }}")?;
+        }
+
+        for (i, (_, name)) in accesses.iter().enumerate() {
+            writeln!(out, "  access_{} [label=\"{}\"];", i, escape(name))?;
+        }
+
+        out.push_str(&edges);
+        writeln!(out, "}}")
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    any::type_name,
+    fmt::{self, Debug, Display},
+    marker::PhantomData,
+    ops::Deref,
+};
+
+use moss_hecs::{Component, Entity, EntityRef, Frame};
+
+use crate::{
+    access::Guard, borrow::ComponentBorrow, ComponentVisitor, SubWorldRaw, VisitComponents,
+};
+
+type Components<'a> = Vec<(&'static str, Box<dyn Debug + 'a>)>;
+
+/// Formats the components of a subworld, see
+/// [SubWorldRaw::dump](crate::SubWorldRaw::dump).
+///
+/// Implemented for every [VisitComponents] of [Debug] components.
+pub trait DebugComponents {
+    /// Collects the name and value of each component of `entity`
+    fn debug_components<'a>(entity: &EntityRef<'a>, components: &mut Components<'a>);
+}
+
+#[doc(hidden)]
+/// Collects a function formatting each component
+pub struct DebugFns(Vec<for<'a> fn(&EntityRef<'a>, &mut Components<'a>)>);
+
+impl<C: Component + Debug> ComponentVisitor<C> for DebugFns {
+    fn visit(&mut self, _: bool) {
+        self.0.push(debug::<C>)
+    }
+}
+
+impl<T: VisitComponents<DebugFns>> DebugComponents for T {
+    fn debug_components<'a>(entity: &EntityRef<'a>, components: &mut Components<'a>) {
+        let mut fns = DebugFns(Vec::new());
+        T::visit_components(&mut fns, false);
+        fns.0.iter().for_each(|debug| debug(entity, components))
+    }
+}
+
+fn debug<'a, C: Component + Debug>(entity: &EntityRef<'a>, components: &mut Components<'a>) {
+    if let Some(component) = entity.get::<&C>() {
+        components.push((type_name::<C>(), Box::new(Guard(component))))
+    }
+}
+
+/// Formats each entity of a subworld with the components accessible by the
+/// subworld. Entities without any accessible component are skipped.
+///
+/// [Debug] formats the entities as a map, with `{:#?}` formatting each
+/// component on its own line, while [Display] formats an entity per line.
+pub struct Dump<'a, T> {
+    frame: &'a Frame,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T: DebugComponents> Dump<'a, T> {
+    fn for_each(&self, mut f: impl FnMut(Entity, &EntityComponents) -> fmt::Result) -> fmt::Result {
+        for entity in self.frame.iter() {
+            let mut components = Vec::new();
+            T::debug_components(&entity, &mut components);
+
+            if !components.is_empty() {
+                f(entity.entity(), &EntityComponents(components))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, T: DebugComponents> Debug for Dump<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        self.for_each(|entity, components| {
+            map.entry(&entity, components);
+            Ok(())
+        })?;
+
+        map.finish()
+    }
+}
+
+// One entity per line
+impl<'a, T: DebugComponents> Display for Dump<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.for_each(|entity, components| writeln!(f, "{entity:?}: {components:?}"))
+    }
+}
+
+impl<A: Deref<Target = Frame>, T: ComponentBorrow + DebugComponents> SubWorldRaw<A, T> {
+    /// Returns a formatter of the entities and components accessible by the
+    /// subworld, e.g. for test failures.
+    ///
+    /// # Panics
+    /// Panics when formatted if an accessible component is already borrowed
+    /// mutably.
+    ///
+    /// # Example
+    /// ```rust
+    /// use moss_hecs::Frame;
+    /// use moss_hecs_schedule::*;
+    ///
+    /// let mut frame = Frame::default();
+    /// frame.spawn((1_i32, "hidden"));
+    ///
+    /// let w = SubWorldRef::<&i32>::new(&frame);
+    /// let dump = format!("{:?}", w.dump());
+    ///
+    /// assert!(dump.contains("{i32: 1}"));
+    /// assert!(!dump.contains("hidden"));
+    /// ```
+    pub fn dump(&self) -> Dump<'_, T> {
+        Dump {
+            frame: &self.frame,
+            marker: PhantomData,
+        }
+    }
+}
+
+struct EntityComponents<'a>(Components<'a>);
+
+impl<'a> Debug for EntityComponents<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+
+        for (name, value) in &self.0 {
+            map.entry(&format_args!("{name}"), value);
+        }
+
+        map.finish()
+    }
+}
+
+impl<'a, C: Debug> Debug for Guard<'a, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (*self.0).fmt(f)
+    }
+}
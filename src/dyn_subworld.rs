@@ -0,0 +1,197 @@
+use std::any::{type_name, TypeId};
+
+use moss_hecs::{Component, Entity, Fetch, Frame, Query, QueryBorrow};
+
+use crate::{
+    borrow::Borrows, subworld::component_error, Access, AccessSet, Error, IntoAccess, Result,
+};
+
+/// The name used for a [DynSubWorld] in errors
+const NAME: &str = "DynSubWorld";
+
+#[derive(Debug, Default, Clone)]
+/// Describes the components a [DynSubWorld] can access, built at runtime.
+///
+/// # Example
+/// ```rust
+/// use moss_hecs_schedule::*;
+///
+/// let access = DynAccess::new().read::<i32>().write::<f32>();
+///
+/// assert!(access.has::<&i32>());
+/// assert!(access.has::<&mut f32>());
+/// assert!(!access.has::<&mut i32>());
+/// ```
+pub struct DynAccess {
+    borrows: Borrows,
+}
+
+impl DynAccess {
+    /// Creates an access to no components
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the component `C`
+    pub fn read<C: Component>(self) -> Self {
+        self.insert(Access::of::<&C>())
+    }
+
+    /// Reads and writes the component `C`
+    pub fn write<C: Component>(self) -> Self {
+        self.insert(Access::of::<&mut C>())
+    }
+
+    /// Reads the component identified by `id`, e.g. for components
+    /// registered by a script
+    pub fn read_dynamic(self, id: TypeId, name: &'static str) -> Self {
+        self.insert(Access::new(name, id, false))
+    }
+
+    /// Reads and writes the component identified by `id`
+    pub fn write_dynamic(self, id: TypeId, name: &'static str) -> Self {
+        self.insert(Access::new(name, id, true))
+    }
+
+    /// Returns true if the borrow of U is permitted
+    pub fn has<U: IntoAccess>(&self) -> bool {
+        let access = U::access();
+        self.has_dynamic(access.id(), access.exclusive())
+    }
+
+    /// Returns true if the component `id` may be borrowed, exclusively if
+    /// `exclusive`
+    pub fn has_dynamic(&self, id: TypeId, exclusive: bool) -> bool {
+        self.borrows
+            .iter()
+            .any(|access| access.id() == id && (access.exclusive() || !exclusive))
+    }
+
+    /// Returns true if all borrows of Q are permitted
+    pub fn has_all<Q: Query>(&self) -> bool {
+        let mut all = true;
+        Q::Fetch::for_each_borrow(|id, exclusive| {
+            if !self.has_dynamic(id, exclusive) {
+                all = false
+            }
+        });
+
+        all
+    }
+
+    /// Returns the permitted component accesses
+    pub fn borrows(&self) -> &[Access] {
+        &self.borrows
+    }
+
+    /// Returns the accesses of a system using a [DynSubWorld] with this
+    /// access, for
+    /// [ScheduleBuilder::add_system_with_access](crate::ScheduleBuilder::add_system_with_access)
+    pub fn access_set(&self) -> AccessSet {
+        AccessSet::new()
+            .extend(self.borrows.iter().copied())
+            .extend([Access::of::<&Frame>().requires::<Frame>()])
+    }
+
+    fn insert(mut self, access: Access) -> Self {
+        if !self.borrows.contains(&access) {
+            self.borrows.push(access);
+        }
+
+        self
+    }
+}
+
+/// A subworld whose accessible components are determined at runtime by a
+/// [DynAccess] rather than a type, for integrations such as scripting and
+/// editors which can not name the components statically.
+///
+/// As the access is not known to the schedule, systems using a dynamic
+/// subworld declare it with
+/// [ScheduleBuilder::add_system_with_access](crate::ScheduleBuilder::add_system_with_access)
+/// and [DynAccess::access_set].
+pub struct DynSubWorld<'a> {
+    frame: &'a Frame,
+    access: DynAccess,
+}
+
+impl<'a> DynSubWorld<'a> {
+    /// Restricts the frame to `access`. No borrow checking is performed so
+    /// may fail during query unless guarded otherwise.
+    pub fn new(frame: &'a Frame, access: DynAccess) -> Self {
+        Self { frame, access }
+    }
+
+    /// Returns the components the subworld can access
+    pub fn access(&self) -> &DynAccess {
+        &self.access
+    }
+
+    /// Returns true if the subworld can access the borrow of U
+    pub fn has<U: IntoAccess>(&self) -> bool {
+        self.access.has::<U>()
+    }
+
+    /// Returns true if the subworld satisfies the whole query
+    pub fn has_all<Q: Query>(&self) -> bool {
+        self.access.has_all::<Q>()
+    }
+
+    /// Query the subworld.
+    /// # Panics
+    /// Panics if the query items are not a compatible subset of the subworld.
+    pub fn query<Q: Query>(&self) -> QueryBorrow<'a, Q> {
+        self.try_query()
+            .expect("Failed to execute query on subworld")
+    }
+
+    /// Query the subworld. Fails if the query items are not a compatible
+    /// subset of the subworld.
+    pub fn try_query<Q: Query>(&self) -> Result<QueryBorrow<'a, Q>> {
+        if !self.has_all::<Q>() {
+            return Err(Error::IncompatibleSubworld {
+                subworld: NAME,
+                query: type_name::<Q>(),
+            });
+        }
+
+        Ok(self.frame.query())
+    }
+
+    /// Get a single component from the world.
+    ///
+    /// Wraps the hecs::NoSuchEntity error and provides the entity id
+    pub fn get<C: Component>(&self, entity: Entity) -> Result<moss_hecs::Ref<'a, C>> {
+        if !self.has::<&C>() {
+            return Err(Error::IncompatibleSubworld {
+                subworld: NAME,
+                query: type_name::<&C>(),
+            });
+        }
+
+        self.frame
+            .get::<&C>(entity)
+            .map_err(|e| component_error(entity, e))
+    }
+
+    /// Mutably get a single component from the world.
+    ///
+    /// Wraps the hecs::NoSuchEntity error and provides the entity id
+    pub fn get_mut<C: Component>(&self, entity: Entity) -> Result<moss_hecs::RefMut<'a, C>> {
+        if !self.has::<&mut C>() {
+            return Err(Error::IncompatibleSubworld {
+                subworld: NAME,
+                query: type_name::<&mut C>(),
+            });
+        }
+
+        self.frame
+            .get::<&mut C>(entity)
+            .map_err(|e| component_error(entity, e))
+    }
+
+    /// Returns true if the entity exists in the world
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.frame.contains(entity)
+    }
+}
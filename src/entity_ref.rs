@@ -0,0 +1,69 @@
+use std::{any::type_name, marker::PhantomData};
+
+use moss_hecs::{Component, Entity, EntityRef, MissingComponent, Query};
+
+use crate::{borrow::ComponentBorrow, Error, Result, Subset};
+
+/// Handle to a single entity of a subworld, which only permits access to the
+/// components accessible by the subworld. See
+/// [SubWorldRaw::entity](crate::SubWorldRaw::entity).
+pub struct SubEntityRef<'a, T> {
+    entity: EntityRef<'a>,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T: ComponentBorrow> SubEntityRef<'a, T> {
+    pub(crate) fn new(entity: EntityRef<'a>) -> Self {
+        Self {
+            entity,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the entity the handle refers to
+    pub fn entity(&self) -> Entity {
+        self.entity.entity()
+    }
+
+    /// Returns true if the entity has the component `C`. Does not require the
+    /// component to be accessible.
+    pub fn has<C: Component>(&self) -> bool {
+        self.entity.has::<C>()
+    }
+
+    /// Returns true if the entity matches the query, without borrowing any
+    /// components. Fails if the query is not a compatible subset of the
+    /// subworld.
+    pub fn satisfies<Q: Query + Subset>(&self) -> Result<bool> {
+        self.check::<Q>()?;
+        Ok(self.entity.satisfies::<Q>())
+    }
+
+    /// Get a component of the entity
+    pub fn get<C: Component>(&self) -> Result<moss_hecs::Ref<'a, C>> {
+        self.check::<&C>()?;
+        self.entity
+            .get::<&C>()
+            .ok_or_else(|| Error::MissingComponent(self.entity(), MissingComponent::new::<C>()))
+    }
+
+    /// Mutably get a component of the entity
+    pub fn get_mut<C: Component>(&self) -> Result<moss_hecs::RefMut<'a, C>> {
+        self.check::<&mut C>()?;
+        self.entity
+            .get::<&mut C>()
+            .ok_or_else(|| Error::MissingComponent(self.entity(), MissingComponent::new::<C>()))
+    }
+
+    // Fails if the subworld can not access all of `U`
+    fn check<U: Subset>(&self) -> Result<()> {
+        if U::is_subset::<T>() {
+            Ok(())
+        } else {
+            Err(Error::IncompatibleSubworld {
+                subworld: type_name::<T>(),
+                query: type_name::<U>(),
+            })
+        }
+    }
+}
@@ -1,9 +1,11 @@
 //! This module provides the error type and result type aliases for
 //! hecs-schedule.
-use moss_hecs::Entity;
+use std::time::Duration;
+
+use moss_hecs::{ComponentError, Entity};
 use thiserror::*;
 
-use crate::SystemName;
+use crate::{Access, SystemName};
 
 #[doc(hidden)]
 pub type Result<T> = std::result::Result<T, Error>;
@@ -31,6 +33,18 @@ pub enum Error {
     #[doc(hidden)]
     UnsatisfiedQuery(Entity, &'static str),
 
+    #[error("Entity {0:?} was requested more than once")]
+    #[doc(hidden)]
+    DuplicateEntity(Entity),
+
+    #[error("Attempt to join subworlds of different worlds")]
+    #[doc(hidden)]
+    DifferentWorlds,
+
+    #[error("Subworld was not borrowed from an executing schedule")]
+    #[doc(hidden)]
+    NoSchedule,
+
     #[error("Context does not have data of type {0:?}")]
     #[doc(hidden)]
     MissingData(&'static str),
@@ -43,7 +57,152 @@ pub enum Error {
     #[doc(hidden)]
     BorrowMut(&'static str),
 
-    #[error("Failed to execute system {0:#?}")]
+    #[error("Failed to execute system {0:?}: {1}")]
     #[doc(hidden)]
     SystemError(SystemName, #[source] anyhow::Error),
+
+    #[error("System {name:?} panicked: {payload}")]
+    #[doc(hidden)]
+    SystemPanic { name: SystemName, payload: String },
+
+    #[error("System {name:?} timed out after {elapsed:?}")]
+    #[doc(hidden)]
+    SystemTimeout { name: SystemName, elapsed: Duration },
+
+    #[error("System {system:?} accessed {access:?} which conflicts with system {other:?}")]
+    #[doc(hidden)]
+    TrackedAccessConflict {
+        system: SystemName,
+        other: SystemName,
+        access: Access,
+    },
+
+    #[error("Schedules {0} and {1} access conflicting data")]
+    #[doc(hidden)]
+    ConflictingSchedules(usize, usize),
+
+    #[error("No system named {0:?} is registered")]
+    #[doc(hidden)]
+    UnknownSystem(String),
+
+    #[error("Commands could not be applied: {0:?}")]
+    #[doc(hidden)]
+    CommandsFailed(Vec<CommandError>),
+    #[error("Resource commands were recorded, but the schedule was not executed with resources")]
+    #[doc(hidden)]
+    NoResources,
+    #[error("Multiple systems failed: {0:#?}")]
+    #[doc(hidden)]
+    Multiple(Vec<Error>),
+}
+
+impl Error {
+    /// Returns the name of the system which produced the error, if any.
+    ///
+    /// Errors produced by a system during the execution of a schedule are
+    /// always attributed to the system.
+    pub fn system(&self) -> Option<&str> {
+        match self {
+            Self::SystemError(name, _)
+            | Self::SystemPanic { name, .. }
+            | Self::SystemTimeout { name, .. }
+            | Self::TrackedAccessConflict { system: name, .. } => Some(name.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Attributes the error to the system `name`, unless it already is
+    /// attributed to a system
+    pub(crate) fn in_system(self, name: &SystemName) -> Self {
+        match self {
+            Self::SystemError(..)
+            | Self::SystemPanic { .. }
+            | Self::SystemTimeout { .. }
+            | Self::Multiple(_) => self,
+            error => Self::SystemError(name.clone(), error.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+/// A command of a [CommandBuffer](crate::CommandBuffer) which could not be
+/// applied, see [CommandBuffer::try_execute](crate::CommandBuffer::try_execute).
+///
+/// Commands are identified by their index in the order they were recorded.
+pub enum CommandError {
+    /// The entity targeted by an insert or removal does not exist
+    #[error("Command {0} targets entity {1:?} which does not exist")]
+    NoSuchEntity(usize, Entity),
+    /// The entity does not have the removed components
+    #[error("Command {0} removes {2:?} which entity {1:?} does not have")]
+    MissingComponent(usize, Entity, &'static str),
+    /// The despawned entity does not exist
+    #[error("Command {0} despawns entity {1:?} which does not exist")]
+    DespawnFailed(usize, Entity),
+    /// The new parent of the entity is the entity itself or one of its
+    /// descendants
+    #[error("Command {0} sets the parent of entity {1:?} to one of its descendants")]
+    CyclicParent(usize, Entity),
+    /// The entity no longer exists, and its id is used by another entity
+    #[error("Command {0} respawns entity {1:?} whose id is used by another entity")]
+    EntityRecycled(usize, Entity),
+    /// The [DeferredEntity](crate::DeferredEntity) targeted by the command was
+    /// spawned by another commandbuffer, or before the commandbuffer was last
+    /// applied or cleared
+    #[error("Command {0} targets a deferred entity of another commandbuffer")]
+    ForeignEntity(usize),
+}
+
+impl CommandError {
+    /// Returns the index of the failed command
+    pub fn index(&self) -> usize {
+        match *self {
+            Self::NoSuchEntity(index, _)
+            | Self::MissingComponent(index, _, _)
+            | Self::DespawnFailed(index, _)
+            | Self::CyclicParent(index, _)
+            | Self::EntityRecycled(index, _)
+            | Self::ForeignEntity(index) => index,
+        }
+    }
+
+    /// Returns the entity targeted by the failed command, unless the target
+    /// could not be resolved
+    pub fn entity(&self) -> Option<Entity> {
+        match *self {
+            Self::NoSuchEntity(_, entity)
+            | Self::MissingComponent(_, entity, _)
+            | Self::DespawnFailed(_, entity)
+            | Self::CyclicParent(_, entity)
+            | Self::EntityRecycled(_, entity) => Some(entity),
+            Self::ForeignEntity(_) => None,
+        }
+    }
+
+    pub(crate) fn from_component(
+        index: usize,
+        entity: Entity,
+        error: ComponentError,
+        component: &'static str,
+    ) -> Self {
+        match error {
+            ComponentError::NoSuchEntity => Self::NoSuchEntity(index, entity),
+            ComponentError::MissingComponent(_) => Self::MissingComponent(index, entity, component),
+        }
+    }
+
+    /// Shifts the index of the command by `offset`, for commands of an
+    /// appended commandbuffer
+    pub(crate) fn offset(self, offset: usize) -> Self {
+        match self {
+            Self::NoSuchEntity(index, entity) => Self::NoSuchEntity(index + offset, entity),
+            Self::MissingComponent(index, entity, component) => {
+                Self::MissingComponent(index + offset, entity, component)
+            }
+            Self::DespawnFailed(index, entity) => Self::DespawnFailed(index + offset, entity),
+            Self::CyclicParent(index, entity) => Self::CyclicParent(index + offset, entity),
+            Self::EntityRecycled(index, entity) => Self::EntityRecycled(index + offset, entity),
+            Self::ForeignEntity(index) => Self::ForeignEntity(index + offset),
+        }
+    }
 }
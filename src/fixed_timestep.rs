@@ -0,0 +1,148 @@
+use std::time::Duration;
+
+use crate::{clock::Instant, CommandBuffer, Context, IntoData, Result, Schedule};
+
+/// Drives a schedule at a fixed rate, e.g; for physics.
+///
+/// The time elapsed between executions is accumulated, and the inner schedule
+/// is executed once for every whole `step` of accumulated time. This means the
+/// inner schedule runs zero or multiple times per execution.
+pub struct FixedTimestep {
+    schedule: Schedule,
+    step: Duration,
+    accumulator: Duration,
+    max_steps: usize,
+    last: Option<Instant>,
+}
+
+impl FixedTimestep {
+    /// Creates a new fixed timestep which executes `schedule` every `step`.
+    ///
+    /// # Panics
+    /// Panics if `step` is zero.
+    pub fn new(schedule: Schedule, step: Duration) -> Self {
+        assert!(!step.is_zero(), "Fixed timestep must be non-zero");
+
+        Self {
+            schedule,
+            step,
+            accumulator: Duration::ZERO,
+            max_steps: usize::MAX,
+            last: None,
+        }
+    }
+
+    /// Creates a new fixed timestep which executes `schedule` `rate` times per
+    /// second.
+    pub fn from_rate(schedule: Schedule, rate: f64) -> Self {
+        Self::new(schedule, Duration::from_secs_f64(1.0 / rate))
+    }
+
+    /// Limits the number of steps per execution. Any whole steps beyond the
+    /// limit are discarded, which prevents a slow inner schedule from falling
+    /// further and further behind.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Adds time to the accumulator in addition to the wall clock time measured
+    /// between executions.
+    pub fn advance(&mut self, delta: Duration) {
+        self.accumulator += delta;
+    }
+
+    /// Returns how far into the next step the accumulated time is, in the range
+    /// `0..1`. Useful for interpolating between fixed steps.
+    pub fn alpha(&self) -> f64 {
+        self.accumulator.as_secs_f64() / self.step.as_secs_f64()
+    }
+
+    /// Returns the duration of a single step
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Get a reference to the inner schedule.
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    /// Get a mutable reference to the inner schedule.
+    pub fn schedule_mut(&mut self) -> &mut Schedule {
+        &mut self.schedule
+    }
+
+    /// Accumulates the elapsed time and returns the number of steps to run
+    fn tick(&mut self) -> usize {
+        let now = Instant::now();
+        if let Some(last) = self.last.replace(now) {
+            self.accumulator += now - last;
+        }
+
+        let mut steps = 0;
+        while self.accumulator >= self.step && steps < self.max_steps {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+
+        if steps == self.max_steps {
+            let remainder = self.accumulator.as_nanos() % self.step.as_nanos();
+            self.accumulator = Duration::from_nanos(remainder as u64);
+        }
+
+        steps
+    }
+
+    /// Sequentially executes the inner schedule once for each elapsed step.
+    /// Returns the number of steps which were executed.
+    ///
+    /// The wall clock starts at the first execution.
+    pub fn execute_seq<D: IntoData<CommandBuffer>>(&mut self, data: D) -> Result<usize> {
+        let data = unsafe { data.into_data(self.schedule.cmd_mut()) };
+
+        let context = Context::new(&data);
+
+        self.run_seq(&context)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    /// Sequentially executes the inner schedule once for each elapsed step, as
+    /// the `parallel` feature is disabled. Returns the number of steps which
+    /// were executed.
+    pub fn execute<D: IntoData<CommandBuffer>>(&mut self, data: D) -> Result<usize> {
+        self.execute_seq(data)
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Executes the inner schedule in parallel once for each elapsed step.
+    /// Returns the number of steps which were executed.
+    ///
+    /// The wall clock starts at the first execution.
+    pub fn execute<D: IntoData<CommandBuffer> + Send + Sync>(&mut self, data: D) -> Result<usize> {
+        let data = unsafe { data.into_data(self.schedule.cmd_mut()) };
+
+        let context = Context::new(&data);
+
+        self.run_par(&context)
+    }
+
+    pub(crate) fn run_seq(&mut self, context: &Context) -> Result<usize> {
+        let steps = self.tick();
+        for _ in 0..steps {
+            self.schedule.run_seq(context)?;
+        }
+
+        Ok(steps)
+    }
+
+    #[cfg(feature = "parallel")]
+    pub(crate) fn run_par(&mut self, context: &Context) -> Result<usize> {
+        let steps = self.tick();
+        for _ in 0..steps {
+            self.schedule.run_par(context)?;
+        }
+
+        Ok(steps)
+    }
+}
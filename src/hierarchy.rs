@@ -0,0 +1,85 @@
+use std::ops::Deref;
+
+use moss_hecs::{Entity, Frame};
+
+use crate::error::CommandError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// The parent of an entity in a hierarchy.
+///
+/// Kept consistent with the [Children] of the parent by
+/// [CommandBuffer::set_parent](crate::CommandBuffer::set_parent) and
+/// [CommandBuffer::spawn_child](crate::CommandBuffer::spawn_child).
+pub struct Parent(Entity);
+
+impl Parent {
+    /// Returns the parent entity
+    pub fn entity(&self) -> Entity {
+        self.0
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+/// The children of an entity in a hierarchy, in the order they were added.
+///
+/// Kept consistent with the [Parent] of each child by
+/// [CommandBuffer::set_parent](crate::CommandBuffer::set_parent) and
+/// [CommandBuffer::spawn_child](crate::CommandBuffer::spawn_child).
+pub struct Children(Vec<Entity>);
+
+impl Deref for Children {
+    type Target = [Entity];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+// Moves `child` from its current parent, if any, to the children of `parent`
+pub(crate) fn set_parent(
+    frame: &mut Frame,
+    index: usize,
+    child: Entity,
+    parent: Entity,
+) -> Result<(), CommandError> {
+    if let Some(entity) = [child, parent]
+        .into_iter()
+        .find(|&entity| !frame.contains(entity))
+    {
+        return Err(CommandError::NoSuchEntity(index, entity));
+    }
+
+    // The parent may not be the child or one of its descendants
+    let mut ancestor = Some(parent);
+    while let Some(entity) = ancestor {
+        if entity == child {
+            return Err(CommandError::CyclicParent(index, child));
+        }
+
+        ancestor = frame.get::<&Parent>(entity).ok().map(|parent| parent.0);
+    }
+
+    let previous = frame.get::<&Parent>(child).ok().map(|parent| parent.0);
+    match previous {
+        Some(previous) if previous == parent => return Ok(()),
+        Some(previous) => {
+            if let Ok(mut children) = frame.get::<&mut Children>(previous) {
+                children.0.retain(|&entity| entity != child);
+            }
+        }
+        None => {}
+    }
+
+    frame.insert_one(child, Parent(parent)).unwrap();
+
+    let added = frame
+        .get::<&mut Children>(parent)
+        .map(|mut children| children.0.push(child))
+        .is_ok();
+
+    if !added {
+        frame.insert_one(parent, Children(vec![child])).unwrap();
+    }
+
+    Ok(())
+}
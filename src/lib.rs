@@ -117,28 +117,79 @@
 #[macro_use]
 mod macros;
 mod access;
+mod archetype;
+mod async_system;
 #[macro_use]
 pub mod borrow;
+pub mod clock;
+#[cfg(feature = "serde")]
+mod command_stream;
 mod commandbuffer;
+mod condition;
 pub mod context;
+mod deferred;
+mod dot;
+mod dump;
+mod dyn_subworld;
+mod entity_ref;
 pub mod error;
+mod fixed_timestep;
+mod hierarchy;
+mod planner;
 mod query;
+mod registry;
+mod retry;
 mod schedule;
+#[cfg(feature = "serde")]
+mod serialize;
+mod state;
+mod stats;
+pub mod strategy;
 mod subworld;
 mod subworld_impls;
 pub mod system;
+mod system_set;
+mod timing;
+mod trace;
+mod tracking;
 pub mod traits;
+mod validation;
 
 pub use access::*;
-pub use borrow::{Read, Write};
+pub use archetype::{ArchetypeInfo, Columns};
+pub use async_system::*;
+pub use borrow::{Local, Read, Write, Yield};
+#[cfg(feature = "serde")]
+pub use command_stream::{
+    CommandStream, StreamBundle, StreamComponent, StreamEntity, StreamTarget,
+};
 pub use commandbuffer::*;
+pub use condition::Condition;
 pub use context::*;
-pub use error::Error;
+pub use deferred::Commands;
+pub use dump::{DebugComponents, Dump};
+pub use dyn_subworld::{DynAccess, DynSubWorld};
+pub use entity_ref::SubEntityRef;
+pub use error::{CommandError, Error};
+pub use fixed_timestep::*;
+pub use hierarchy::{Children, Parent};
 pub use query::*;
+pub use registry::*;
+pub use retry::*;
 pub use subworld_impls::*;
 // Don't export result so that hecs-schedule can be glob imported without
 // conflict
 pub(crate) use error::Result;
+#[cfg(feature = "macros")]
+pub use moss_hecs_schedule_derive::SystemData;
 pub use schedule::*;
+#[cfg(feature = "serde")]
+pub use serialize::ComponentRegistry;
+pub use state::State;
+pub use stats::{ArchetypeStats, Churn, WorldStats};
+pub use strategy::BatchingStrategy;
 pub use subworld::*;
 pub use system::*;
+pub use system_set::*;
+pub use timing::*;
+pub use validation::*;
@@ -0,0 +1,210 @@
+//! Orders the systems of a schedule and splits them into batches.
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::{borrow::Borrows, Access, Batch, BatchingStrategy, DynamicSystem};
+
+/// A node of a schedule which has not yet been split into batches
+pub(crate) enum Node {
+    System(DynamicSystem),
+    Barrier,
+}
+
+/// Returns true if the two sets of borrows can not be held at the same time
+pub(crate) fn conflicts(a: &Borrows, b: &Borrows) -> bool {
+    conflicting(a, b.iter()).is_some()
+}
+
+/// Returns the first access of `a` which can not be held at the same time as
+/// any access of `b`
+pub(crate) fn conflicting<'a, 'b>(
+    a: &'a Borrows,
+    b: impl Iterator<Item = &'b Access> + Clone,
+) -> Option<&'a Access> {
+    a.iter().find(|a| {
+        b.clone()
+            .any(|b| a.id() == b.id() && a.world() == b.world() && (a.exclusive() || b.exclusive()))
+    })
+}
+
+/// Orders the nodes such that all ordering constraints are satisfied while
+/// otherwise preserving insertion order.
+///
+/// Returns the indices of the nodes that are part of a cycle if no such order
+/// exists.
+fn sort(nodes: &[Node]) -> std::result::Result<Vec<usize>, Vec<usize>> {
+    let mut edges = vec![Vec::new(); nodes.len()];
+    let mut incoming = vec![0_usize; nodes.len()];
+
+    let mut add_edge = |from: usize, to: usize| {
+        edges[from].push(to);
+        incoming[to] += 1;
+    };
+
+    // Barriers keep everything before them before, and everything after them
+    // after.
+    let mut prev_barrier = None;
+    let mut segment = Vec::new();
+    for (i, node) in nodes.iter().enumerate() {
+        if let Some(barrier) = prev_barrier {
+            add_edge(barrier, i);
+        }
+
+        match node {
+            Node::Barrier => {
+                segment.drain(..).for_each(|j| add_edge(j, i));
+                prev_barrier = Some(i);
+            }
+            Node::System(_) => segment.push(i),
+        }
+    }
+
+    for (i, node) in nodes.iter().enumerate() {
+        let system = match node {
+            Node::System(system) => system,
+            Node::Barrier => continue,
+        };
+
+        for (j, other) in nodes.iter().enumerate() {
+            let other = match other {
+                Node::System(other) if i != j => other,
+                _ => continue,
+            };
+
+            if system.before.iter().any(|label| other.has_label(label)) {
+                add_edge(i, j);
+            }
+
+            if system.after.iter().any(|label| other.has_label(label)) {
+                add_edge(j, i);
+            }
+        }
+    }
+
+    let mut queue: BinaryHeap<_> = (0..nodes.len())
+        .filter(|&i| incoming[i] == 0)
+        .map(Reverse)
+        .collect();
+
+    let mut order = Vec::with_capacity(nodes.len());
+    while let Some(Reverse(i)) = queue.pop() {
+        order.push(i);
+        for &j in &edges[i] {
+            incoming[j] -= 1;
+            if incoming[j] == 0 {
+                queue.push(Reverse(j));
+            }
+        }
+    }
+
+    if order.len() == nodes.len() {
+        Ok(order)
+    } else {
+        Err((0..nodes.len()).filter(|&i| incoming[i] > 0).collect())
+    }
+}
+
+/// Returns the names of the systems which form a cycle, if any
+pub(crate) fn find_cycle(nodes: &[Node]) -> Option<Vec<String>> {
+    let cycle = sort(nodes).err()?;
+
+    Some(
+        cycle
+            .into_iter()
+            .filter_map(|i| match &nodes[i] {
+                Node::System(system) => Some(system.name().to_string()),
+                Node::Barrier => None,
+            })
+            .collect(),
+    )
+}
+
+/// Returns the indices of the earlier systems each system has to wait for.
+///
+/// Disabled systems neither wait for nor are waited on by other systems.
+fn dependencies(systems: &[DynamicSystem], segments: &[usize]) -> Vec<Vec<usize>> {
+    systems
+        .iter()
+        .enumerate()
+        .map(|(i, system)| {
+            if !system.enabled {
+                return Vec::new();
+            }
+
+            (0..i)
+                .filter(|&j| {
+                    let other = &systems[j];
+                    if !other.enabled {
+                        return false;
+                    }
+
+                    segments[j] < segments[i]
+                        || system.exclusive
+                        || other.exclusive
+                        || conflicts(&system.borrows, &other.borrows)
+                        || system.after.iter().any(|label| other.has_label(label))
+                        || other.before.iter().any(|label| system.has_label(label))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Splits the nodes into batches of systems which can run in parallel, using
+/// `strategy` to assign the systems to batches.
+///
+/// # Panics
+/// Panics if the ordering constraints of the systems form a cycle, or if the
+/// strategy places a system before one of its dependencies.
+pub(crate) fn plan(nodes: Vec<Node>, strategy: &dyn BatchingStrategy) -> Vec<Batch> {
+    let order = match sort(&nodes) {
+        Ok(order) => order,
+        Err(_) => {
+            let names = find_cycle(&nodes).unwrap_or_default();
+            panic!("Ordering constraints form a cycle between: {:?}", names)
+        }
+    };
+
+    let mut nodes: Vec<_> = nodes.into_iter().map(Some).collect();
+    let mut systems = Vec::with_capacity(nodes.len());
+    let mut segments = Vec::with_capacity(nodes.len());
+    let mut segment = 0;
+
+    for i in order {
+        match nodes[i].take().expect("Node visited twice") {
+            Node::System(system) => {
+                systems.push(system);
+                segments.push(segment);
+            }
+            Node::Barrier => segment += 1,
+        }
+    }
+
+    let deps = dependencies(&systems, &segments);
+    let assignment = strategy.assign(&systems, &deps);
+
+    assert_eq!(
+        assignment.len(),
+        systems.len(),
+        "Batching strategy did not place every system"
+    );
+
+    for (i, deps) in deps.iter().enumerate() {
+        assert!(
+            deps.iter().all(|&j| assignment[j] < assignment[i]),
+            "Batching strategy placed {:?} before one of its dependencies",
+            systems[i].name()
+        );
+    }
+
+    let count = assignment.iter().max().map_or(0, |&batch| batch + 1);
+    let mut batches: Vec<Batch> = (0..count).map(|_| Batch::default()).collect();
+
+    for (system, batch) in systems.into_iter().zip(assignment) {
+        batches[batch].push(system);
+    }
+
+    batches.retain(|batch| !batch.is_empty());
+    batches.iter_mut().for_each(Batch::sort_by_priority);
+
+    batches
+}
@@ -1,7 +1,9 @@
-use std::any::type_name;
+use std::{any::type_name, fmt::Debug, marker::PhantomData};
 
-use crate::{Error, Result};
-use moss_hecs::{Entity, Query};
+use crate::{subworld::collect_array, Error, GenericWorld, Result, Subset};
+use moss_hecs::{Entity, Frame, Query, QueryShared};
+#[cfg(feature = "parallel")]
+use rayon::iter::{ParallelBridge, ParallelIterator};
 
 /// Wraps the bulting QueryOne with a Result containing the entity and component instead of option
 pub struct QueryOne<'a, Q: Query> {
@@ -28,3 +30,339 @@ impl<'a, Q: Query> QueryOne<'a, Q> {
         }
     }
 }
+
+/// Wraps the builtin PreparedQuery, which caches the archetypes matching
+/// the query between uses, such that the compatibility with the subworld is
+/// also only checked once.
+///
+/// See [SubWorldRaw::prepared_query](crate::SubWorldRaw::prepared_query).
+pub struct PreparedQuery<Q: Query> {
+    pub(crate) inner: moss_hecs::PreparedQuery<Q>,
+    // The subworld the query was last checked against
+    pub(crate) subworld: Option<&'static str>,
+}
+
+impl<Q: Query> PreparedQuery<Q> {
+    /// Creates a prepared query which is prepared on first use
+    pub fn new() -> Self {
+        Self {
+            inner: moss_hecs::PreparedQuery::new(),
+            subworld: None,
+        }
+    }
+}
+
+impl<Q: Query> Default for PreparedQuery<Q> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Wraps the builtin ViewBorrow, which provides random access to the entities
+/// matching a query, with Results containing the entity instead of options.
+///
+/// See [SubWorldRaw::view](crate::SubWorldRaw::view) and
+/// [SubWorldRaw::view_mut](crate::SubWorldRaw::view_mut).
+pub struct QueryView<'w, Q: Query> {
+    frame: &'w Frame,
+    view: moss_hecs::ViewBorrow<'w, Q>,
+}
+
+impl<'w, Q: Query> QueryView<'w, Q> {
+    pub(crate) fn new(frame: &'w Frame) -> Self {
+        Self {
+            frame,
+            view: frame.view(),
+        }
+    }
+
+    /// Returns true if the entity matches the query
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.view.contains(entity)
+    }
+
+    /// Get the query result of an entity
+    pub fn get(&self, entity: Entity) -> Result<Q::Item<'_>>
+    where
+        Q: QueryShared,
+    {
+        let frame = self.frame;
+        self.view
+            .get(entity)
+            .ok_or_else(|| unsatisfied::<Q>(frame, entity))
+    }
+
+    /// Get the query result of an entity, which may access components
+    /// mutably
+    pub fn get_mut(&mut self, entity: Entity) -> Result<Q::Item<'_>> {
+        let frame = self.frame;
+        self.view
+            .get_mut(entity)
+            .ok_or_else(|| unsatisfied::<Q>(frame, entity))
+    }
+
+    /// Get the query results of several distinct entities at once, failing on
+    /// the first entity which does not match the query.
+    pub fn get_many_mut<const N: usize>(
+        &mut self,
+        entities: [Entity; N],
+    ) -> Result<[Q::Item<'_>; N]> {
+        for (i, entity) in entities.iter().enumerate() {
+            if entities[..i].contains(entity) {
+                return Err(Error::DuplicateEntity(*entity));
+            }
+        }
+
+        let frame = self.frame;
+        let mut entity = entities.iter();
+        let items = self.view.get_many_mut(entities).map(|item| {
+            let entity = *entity.next().expect("One entity per item");
+            item.ok_or_else(|| unsatisfied::<Q>(frame, entity))
+        });
+
+        collect_array(items)
+    }
+}
+
+// Distinguishes despawned entities from those not matching the query
+fn unsatisfied<Q: Query>(frame: &Frame, entity: Entity) -> Error {
+    if frame.contains(entity) {
+        Error::UnsatisfiedQuery(entity, type_name::<Q>())
+    } else {
+        Error::NoSuchEntity(entity)
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// Remembers the position of a query between executions, such that expensive
+/// per-entity work can be spread over several frames by processing at most a
+/// fixed number of entities each execution.
+///
+/// The position is an index into the matching entities, so entities spawned or
+/// despawned between executions may shift which entities are processed next.
+///
+/// Usually stored in a [Local](crate::Local).
+///
+/// # Example
+/// ```rust
+/// use moss_hecs::Frame;
+/// use moss_hecs_schedule::*;
+///
+/// let mut frame = Frame::default();
+/// frame.spawn_batch((0..5).map(|i| (i,)));
+///
+/// let mut schedule = Schedule::builder()
+///     .add_system(|w: SubWorld<&mut i32>, mut progress: Local<ProgressiveQuery>| {
+///         progress
+///             .for_each::<&mut i32>(&w, 2, |_, val| *val += 10)
+///             .unwrap();
+///     })
+///     .build();
+///
+/// for _ in 0..3 {
+///     schedule.execute((&mut frame,)).unwrap();
+/// }
+///
+/// assert!(frame.query_mut::<&i32>().into_iter().all(|(_, val)| *val >= 10));
+/// ```
+pub struct ProgressiveQuery {
+    cursor: usize,
+}
+
+impl ProgressiveQuery {
+    /// Creates a query which starts at the first entity
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of entities processed in the current pass
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Restarts the pass from the first entity
+    pub fn reset(&mut self) {
+        self.cursor = 0
+    }
+
+    /// Calls `f` for at most `limit` of the entities matching `Q` which
+    /// follow those processed by previous calls. Once all entities have been
+    /// processed, the next call starts a new pass from the first entity.
+    ///
+    /// Returns true if the pass was completed by this call. Fails if the query
+    /// is not a compatible subset of the world.
+    pub fn for_each<Q: Query + Subset>(
+        &mut self,
+        world: &impl GenericWorld,
+        limit: usize,
+        mut f: impl FnMut(Entity, Q::Item<'_>),
+    ) -> Result<bool> {
+        let mut query = world.try_query::<Q>()?;
+        let mut iter = query.iter();
+
+        if self.cursor >= iter.len() {
+            self.cursor = 0;
+        }
+
+        let mut processed = 0;
+        for (entity, item) in iter.by_ref().skip(self.cursor).take(limit) {
+            f(entity, item);
+            processed += 1;
+        }
+
+        self.cursor += processed;
+
+        let finished = iter.len() == 0;
+        if finished {
+            self.cursor = 0;
+        }
+
+        Ok(finished)
+    }
+}
+
+/// Owned chunk of the entities matching `Q`, see
+/// [SubWorldRaw::iter_chunks](crate::SubWorldRaw::iter_chunks). The entities
+/// of a chunk belong to the same archetype.
+///
+/// Chunks do not borrow the world, and can thus be sent to other threads
+/// which process them using [for_each](Self::for_each). As the components
+/// are borrowed per archetype, chunks of the same archetype can only be
+/// processed concurrently if `Q` does not access any component mutably.
+pub struct QueryChunk<Q> {
+    pub(crate) entities: Vec<Entity>,
+    pub(crate) marker: PhantomData<fn() -> Q>,
+}
+
+impl<Q> QueryChunk<Q> {
+    /// Returns the entities of the chunk
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    /// Returns the number of entities in the chunk
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns true if the chunk contains no entities
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+impl<Q: Query + Subset> QueryChunk<Q> {
+    /// Calls `f` for each entity of the chunk which still matches `Q`,
+    /// skipping entities which were despawned since the chunk was created.
+    /// Fails if the query is not a compatible subset of the world.
+    ///
+    /// # Panics
+    /// Panics if the components of an entity are already borrowed
+    /// incompatibly, e.g. by a thread processing another chunk of the
+    /// archetype.
+    pub fn for_each(
+        &self,
+        world: &impl GenericWorld,
+        mut f: impl FnMut(Entity, Q::Item<'_>),
+    ) -> Result<()> {
+        for &entity in &self.entities {
+            let mut query = match world.try_query_one::<Q>(entity) {
+                Ok(query) => query,
+                Err(Error::NoSuchEntity(_)) => continue,
+                Err(e) => return Err(e),
+            };
+
+            if let Ok(item) = query.get() {
+                f(entity, item);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<Q> Clone for QueryChunk<Q> {
+    fn clone(&self) -> Self {
+        Self {
+            entities: self.entities.clone(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<Q> Debug for QueryChunk<Q> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("QueryChunk").field(&self.entities).finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Scratch buffer of [SubWorldRaw::query_sorted](crate::SubWorldRaw::query_sorted),
+/// which is reused between calls to avoid reallocating.
+///
+/// Usually stored in a [Local](crate::Local).
+pub struct SortedQuery<K> {
+    pub(crate) entities: Vec<(K, Entity)>,
+}
+
+impl<K> SortedQuery<K> {
+    /// Creates an empty buffer
+    pub fn new() -> Self {
+        Self {
+            entities: Vec::new(),
+        }
+    }
+}
+
+impl<K> Default for SortedQuery<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "parallel")]
+/// Wraps a [QueryBorrow](moss_hecs::QueryBorrow) to iterate the matching
+/// entities with rayon.
+///
+/// The entities are split into batches which never span multiple archetypes,
+/// sized to spread the entities evenly across the current thread pool.
+pub struct ParQueryBorrow<'w, Q: Query> {
+    query: moss_hecs::QueryBorrow<'w, Q>,
+}
+
+#[cfg(feature = "parallel")]
+impl<'w, Q: Query> ParQueryBorrow<'w, Q> {
+    pub(crate) fn new(query: moss_hecs::QueryBorrow<'w, Q>) -> Self {
+        Self { query }
+    }
+
+    /// Returns a parallel iterator over the matching entities, with a batch
+    /// size chosen from the number of entities and threads
+    pub fn par_iter<'q>(&'q mut self) -> impl ParallelIterator<Item = (Entity, Q::Item<'q>)> + 'q
+    where
+        Q::Item<'q>: Send,
+    {
+        let len = self.query.iter().len();
+        // Several batches per thread allow work stealing to even out
+        // archetypes of different sizes
+        let batches = rayon::current_num_threads() * 4;
+        let batch_size = (len / batches).clamp(1, u32::MAX as usize) as u32;
+
+        self.par_iter_batched(batch_size)
+    }
+
+    /// Returns a parallel iterator over the matching entities, which are
+    /// processed in batches of at most `batch_size` entities
+    pub fn par_iter_batched<'q>(
+        &'q mut self,
+        batch_size: u32,
+    ) -> impl ParallelIterator<Item = (Entity, Q::Item<'q>)> + 'q
+    where
+        Q::Item<'q>: Send,
+    {
+        self.query
+            .iter_batched(batch_size)
+            .par_bridge()
+            .flat_map_iter(|batch| batch)
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use crate::{
+    borrow::Borrows, Access, DynamicSystem, Error, Result, Schedule, ScheduleBuilder, System,
+    SystemLabel,
+};
+
+struct Entry {
+    factory: Box<dyn Fn() -> DynamicSystem + Send + Sync>,
+    borrows: Borrows,
+}
+
+#[derive(Default)]
+/// A collection of systems registered by name, from which schedules can be
+/// built at runtime.
+///
+/// Registering a system under an existing name replaces it, which allows
+/// rebuilding schedules with new versions of systems, e.g; after reloading a
+/// library.
+pub struct SystemRegistry {
+    systems: HashMap<SystemLabel, Entry>,
+}
+
+impl SystemRegistry {
+    /// Creates a new empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `system` by `name`, replacing any system previously
+    /// registered by the same name.
+    ///
+    /// Each schedule built from the registry receives its own copy of the
+    /// system. The system is named and labeled `name`.
+    pub fn register<L, Args, Ret, S>(&mut self, name: L, system: S) -> &mut Self
+    where
+        L: Into<SystemLabel>,
+        S: 'static + System<Args, Ret> + Send + Sync + Clone,
+    {
+        let name = name.into();
+        let label = name.clone();
+
+        self.systems.insert(
+            name,
+            Entry {
+                factory: Box::new(move || DynamicSystem::new_named(label.clone(), system.clone())),
+                borrows: S::borrows(),
+            },
+        );
+
+        self
+    }
+
+    /// Removes the system registered by `name`. Returns true if the system was
+    /// registered.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.systems.remove(name).is_some()
+    }
+
+    /// Returns true if a system is registered by `name`
+    pub fn contains(&self, name: &str) -> bool {
+        self.systems.contains_key(name)
+    }
+
+    /// Returns the data accessed by the system registered by `name`
+    pub fn borrows(&self, name: &str) -> Option<&[Access]> {
+        self.systems.get(name).map(|entry| &entry.borrows[..])
+    }
+
+    /// Returns the names of all registered systems, in no particular order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.systems.keys().map(|name| name.as_ref())
+    }
+
+    /// Returns the number of registered systems
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    /// Returns true if no systems are registered
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+
+    /// Returns a builder containing the systems registered by `names`, in
+    /// order. Further systems and options can be added to the builder before
+    /// building.
+    ///
+    /// Fails with [Error::UnknownSystem] if any name is not registered.
+    pub fn builder<'a, I>(&self, names: I) -> Result<ScheduleBuilder>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut builder = ScheduleBuilder::default();
+
+        for name in names {
+            let entry = self
+                .systems
+                .get(name)
+                .ok_or_else(|| Error::UnknownSystem(name.to_string()))?;
+
+            builder.add_internal((entry.factory)());
+        }
+
+        Ok(builder)
+    }
+
+    /// Builds a schedule of the systems registered by `names`, in order.
+    ///
+    /// Fails with [Error::UnknownSystem] if any name is not registered.
+    pub fn build<'a, I>(&self, names: I) -> Result<Schedule>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        Ok(self.builder(names)?.build())
+    }
+}
@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// Describes how often a failing system is retried before the failure is
+/// reported.
+///
+/// Only errors returned by the system itself are retried; failing to borrow
+/// data from the context is not.
+pub struct RetryPolicy {
+    times: usize,
+    backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Retries a failing system up to `times` times
+    pub fn times(times: usize) -> Self {
+        Self {
+            times,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    /// Waits for `backoff` before each retry. This blocks the thread running
+    /// the system.
+    ///
+    /// The backoff is skipped on `wasm32-unknown-unknown`, where the thread
+    /// can not be blocked.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Returns the maximum number of retries
+    pub fn max_retries(&self) -> usize {
+        self.times
+    }
+
+    /// Returns the time waited before each retry
+    pub fn backoff_duration(&self) -> Duration {
+        self.backoff
+    }
+
+    /// Blocks the current thread for the backoff
+    pub(crate) fn wait(&self) {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        if !self.backoff.is_zero() {
+            std::thread::sleep(self.backoff);
+        }
+    }
+}
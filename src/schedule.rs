@@ -1,19 +1,43 @@
 use std::{
-    any::TypeId,
+    any::{type_name, Any, TypeId},
+    cmp::Reverse,
     collections::HashMap,
     fmt::{Debug, Display},
     ops::{Deref, DerefMut},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::Arc,
+    time::Duration,
 };
 
-use moss_hecs::Frame;
+use moss_hecs::{Component, Frame, Query};
 use smallvec::SmallVec;
 
 #[cfg(feature = "parallel")]
-use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use rayon::{
+    iter::{
+        IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator,
+        IntoParallelRefMutIterator, ParallelIterator,
+    },
+    ThreadPool, ThreadPoolBuilder,
+};
 
 use crate::{
-    borrow::{Borrows, MaybeWrite},
-    Access, CommandBuffer, Context, IntoData, Result, System, SystemName, Write,
+    async_system::{block_on, join_all, with_timeout},
+    borrow::{Borrows, ComponentBorrow, Locals, MaybeWrite},
+    clock::Instant,
+    condition::DynamicCondition,
+    context::DataOverlay,
+    deferred::{Deferred, DeferredQueue},
+    planner::{conflicting, conflicts, plan, Node},
+    state::transition_system,
+    strategy::{Greedy, LatencyOptimized},
+    timing::DEFAULT_TIMING_WINDOW,
+    tracking::ChangeTicks,
+    validation::{validate, validate_queries},
+    Access, AccessSet, AsyncSystem, BatchingStrategy, CommandBuffer, Condition, Context, Data,
+    Error, ExclusiveSystem, IntoData, Read, Resources, Result, RetryPolicy, State, System,
+    SystemFuture, SystemLabel, SystemName, SystemSet, SystemTiming, ValidationError, Write,
 };
 
 #[derive(Default, Debug, Clone)]
@@ -29,6 +53,9 @@ impl<'a> Display for BatchInfo<'a> {
             for system in &batch.systems {
                 writeln!(f, " - {}", system.name())?;
             }
+            if batch.has_flush {
+                writeln!(f, " (flush)")?;
+            }
             writeln!(f)?;
         }
 
@@ -36,6 +63,77 @@ impl<'a> Display for BatchInfo<'a> {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+/// Describes a system of a schedule and the data it accesses
+pub struct SystemInfo<'a> {
+    name: &'a str,
+    borrows: &'a [Access],
+}
+
+impl<'a> SystemInfo<'a> {
+    /// Returns the name of the system
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns all data accessed by the system
+    pub fn borrows(&self) -> &'a [Access] {
+        self.borrows
+    }
+
+    /// Returns the data which is accessed immutably
+    pub fn reads(&self) -> impl Iterator<Item = &'a Access> {
+        self.borrows.iter().filter(|access| !access.exclusive())
+    }
+
+    /// Returns the data which is accessed mutably
+    pub fn writes(&self) -> impl Iterator<Item = &'a Access> {
+        self.borrows.iter().filter(|access| access.exclusive())
+    }
+
+    /// Returns the components which are accessed through the world
+    pub fn components(&self) -> impl Iterator<Item = &'a Access> {
+        self.borrows.iter().filter(|access| access.data().is_none())
+    }
+
+    /// Returns the data passed to the execution which is accessed immutably,
+    /// including the world itself
+    pub fn resource_reads(&self) -> impl Iterator<Item = &'a Access> {
+        self.reads().filter(|access| access.data().is_some())
+    }
+
+    /// Returns the data passed to the execution which is accessed mutably,
+    /// including the world itself
+    pub fn resource_writes(&self) -> impl Iterator<Item = &'a Access> {
+        self.writes().filter(|access| access.data().is_some())
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Describes a batch of systems which run in parallel
+pub struct BatchDescription<'a> {
+    index: usize,
+    systems: Vec<SystemInfo<'a>>,
+    has_flush: bool,
+}
+
+impl<'a> BatchDescription<'a> {
+    /// Returns the position of the batch in the schedule
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the systems in the batch
+    pub fn systems(&self) -> &[SystemInfo<'a>] {
+        &self.systems
+    }
+
+    /// Returns true if the batch applies the commandbuffer to the world
+    pub fn has_flush(&self) -> bool {
+        self.has_flush
+    }
+}
+
 #[derive(Default)]
 /// Represents a unit of work with compatible borrows.
 pub struct Batch {
@@ -56,14 +154,283 @@ impl Debug for Batch {
 }
 
 impl Batch {
-    fn push(&mut self, system: DynamicSystem) {
+    pub(crate) fn push(&mut self, system: DynamicSystem) {
+        self.has_flush |= system.flush;
         self.systems.push(system)
     }
 
+    /// Returns true if any enabled systems of the two batches can not run at
+    /// the same time
+    fn conflicts_with(&self, other: &Batch) -> bool {
+        self.iter().filter(|system| system.enabled).any(|system| {
+            other.iter().filter(|other| other.enabled).any(|other| {
+                system.exclusive || other.exclusive || conflicts(&system.borrows, &other.borrows)
+            })
+        })
+    }
+
+    /// Orders the systems by descending priority, keeping the order of
+    /// systems with equal priority
+    pub(crate) fn sort_by_priority(&mut self) {
+        self.systems.sort_by_key(|system| Reverse(system.priority));
+    }
+
     /// Get a reference to the batch's systems.
     pub fn systems(&self) -> &SmallVec<[DynamicSystem; 8]> {
         &self.systems
     }
+
+    /// Returns true if the batch applies the commandbuffer to the world
+    pub fn has_flush(&self) -> bool {
+        self.has_flush
+    }
+
+    fn run_seq(&mut self, context: &Context, options: &RunOptions) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("batch", systems = self.len()).entered();
+
+        let execute = |system: &mut DynamicSystem| system.run(context, options);
+
+        let result = match options.error_policy {
+            ErrorPolicy::FailFast => self.iter_mut().try_for_each(execute),
+            ErrorPolicy::CollectAll => collect_errors(
+                self.iter_mut()
+                    .filter_map(|system| execute(system).err())
+                    .collect(),
+            ),
+        };
+
+        result.and(self.check_tracked(options))
+    }
+
+    /// Compares the components accessed by the tracked subworlds of each
+    /// system against the accesses of the other systems of the batch, see
+    /// [TrackedAccess](crate::TrackedAccess)
+    fn check_tracked(&self, options: &RunOptions) -> Result<()> {
+        let accessed: SmallVec<[Borrows; 8]> = self
+            .iter()
+            .map(|system| system.locals.accessed().take())
+            .collect();
+
+        for (system, touched) in self.iter().zip(&accessed) {
+            if touched.is_empty() {
+                continue;
+            }
+
+            for (other, other_touched) in self.iter().zip(&accessed) {
+                if ptr::eq(system, other) || !other.enabled {
+                    continue;
+                }
+
+                let access = match conflicting(touched, other.borrows.iter().chain(other_touched)) {
+                    Some(access) => access,
+                    None => continue,
+                };
+
+                let error = Error::TrackedAccessConflict {
+                    system: system.name.clone(),
+                    other: other.name.clone(),
+                    access: *access,
+                };
+
+                if options.strict_access {
+                    return Err(error);
+                }
+
+                #[cfg(feature = "tracing")]
+                tracing::warn!("{error}");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "parallel")]
+    fn run_par(&mut self, context: &Context, options: &RunOptions) -> Result<()> {
+        let result = if self.iter().any(|system| system.main_thread) {
+            self.run_pinned(context, options)
+        } else {
+            match &options.pool {
+                Some(pool) => pool.install(|| self.run_par_unpinned(context, options)),
+                None => self.run_par_unpinned(context, options),
+            }
+        };
+
+        result.and(self.check_tracked(options))
+    }
+
+    #[cfg(feature = "parallel")]
+    // Runs the systems pinned to the main thread on the current thread, while
+    // the other systems run in parallel on the pool
+    fn run_pinned(&mut self, context: &Context, options: &RunOptions) -> Result<()> {
+        // Partitioned into vectors, as rayon only yields shared references
+        // from a smallvec
+        let (pinned, others): (Vec<_>, Vec<_>) =
+            self.iter_mut().partition(|system| system.main_thread);
+
+        let mut errors = Vec::new();
+        let mut other_errors = Vec::new();
+
+        in_place_scope(options.pool.as_deref(), |scope| {
+            scope.spawn(|_| {
+                other_errors = others
+                    .into_par_iter()
+                    .filter_map(|system| system.run(context, options).err())
+                    .collect();
+            });
+
+            errors.extend(
+                pinned
+                    .into_iter()
+                    .filter_map(|system| system.run(context, options).err()),
+            );
+        });
+
+        errors.append(&mut other_errors);
+
+        match options.error_policy {
+            ErrorPolicy::FailFast => errors.into_iter().next().map_or(Ok(()), Err),
+            ErrorPolicy::CollectAll => collect_errors(errors),
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    fn run_par_unpinned(&mut self, context: &Context, options: &RunOptions) -> Result<()> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!("batch", systems = self.len());
+
+        let execute = |system: &mut DynamicSystem| {
+            // Systems run on the worker threads of the pool, so the batch span
+            // is entered on each of them
+            #[cfg(feature = "tracing")]
+            let _enter = span.enter();
+
+            system.run(context, options)
+        };
+
+        match options.error_policy {
+            ErrorPolicy::FailFast => self.par_iter_mut().try_for_each(execute),
+            ErrorPolicy::CollectAll => collect_errors(
+                self.par_iter_mut()
+                    .filter_map(|system| execute(system).err())
+                    .collect(),
+            ),
+        }
+    }
+
+    async fn run_async(&mut self, context: &Context<'_>, options: &RunOptions) -> Result<()> {
+        let context = &context.with_schedule(&options.deferred, &options.ticks);
+        let mut futures = Vec::new();
+        let mut names = Vec::new();
+        let mut errors = Vec::new();
+
+        for system in self.iter_mut() {
+            match system.start(context, &options.hooks) {
+                Ok(Some(future)) => {
+                    futures.push(future);
+                    names.push(system.name.clone());
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    let e = e.in_system(&system.name);
+                    if options.error_policy == ErrorPolicy::FailFast {
+                        return Err(e);
+                    }
+
+                    errors.push(e)
+                }
+            }
+        }
+
+        errors.extend(
+            join_all(futures)
+                .await
+                .into_iter()
+                .zip(&names)
+                .filter_map(|(result, name)| result.err().map(|e| e.in_system(name))),
+        );
+
+        match options.error_policy {
+            ErrorPolicy::FailFast => errors.into_iter().next().map_or(Ok(()), Err),
+            ErrorPolicy::CollectAll => collect_errors(errors),
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+// Creates a scope on the current thread which spawns onto `pool`, or the
+// current thread pool
+fn in_place_scope<'scope, R>(
+    pool: Option<&ThreadPool>,
+    op: impl FnOnce(&rayon::Scope<'scope>) -> R,
+) -> R {
+    match pool {
+        Some(pool) => pool.in_place_scope(op),
+        None => rayon::in_place_scope(op),
+    }
+}
+
+// Extracts the message of a panic payload
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "Box<dyn Any>".to_string(),
+        },
+    }
+}
+
+// Returns the only error as is, or all errors together
+fn collect_errors(mut errors: Vec<Error>) -> Result<()> {
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        _ => Err(Error::Multiple(errors)),
+    }
+}
+
+// Options which affect how the systems of a schedule are run
+#[derive(Default, Clone)]
+struct RunOptions {
+    error_policy: ErrorPolicy,
+    catch_panics: bool,
+    strict_access: bool,
+    // Shared so that cloning the options for a run does not allocate
+    hooks: Arc<Vec<Hook>>,
+    deferred: Arc<DeferredQueue>,
+    ticks: Arc<ChangeTicks>,
+    #[cfg(feature = "parallel")]
+    pool: Option<Arc<ThreadPool>>,
+}
+
+// Function invoked around each system
+type Hook = Arc<dyn Fn(&SystemInfo, HookPhase) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Describes when a hook is invoked
+pub enum HookPhase {
+    /// Before the system runs
+    Before,
+    /// After the system has run, regardless of whether it failed
+    After,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+/// Decides how a schedule proceeds when a system fails
+pub enum ErrorPolicy {
+    /// Stops at the first failing system. Systems in the same batch which
+    /// already started still run to completion, but their errors are
+    /// discarded.
+    #[default]
+    FailFast,
+    /// Runs the remaining systems of the batch containing the failing system,
+    /// and returns the errors of all failing systems. Later batches are not
+    /// run.
+    ///
+    /// A single failure is returned as is, several failures are returned as
+    /// [Error::Multiple].
+    CollectAll,
 }
 
 impl Deref for Batch {
@@ -80,222 +447,1845 @@ impl DerefMut for Batch {
     }
 }
 
+// Type erased system function
+enum SystemFn {
+    Blocking(Box<dyn FnMut(&Context) -> Result<()> + Send>),
+    Async(Box<dyn for<'a> FnMut(&'a Context) -> Result<SystemFuture<'a>> + Send>),
+}
+
 // Type erased boxed system
 #[doc(hidden)]
 pub struct DynamicSystem {
-    func: Box<dyn FnMut(&Context) -> Result<()> + Send>,
+    func: SystemFn,
     name: SystemName,
-    borrows: Borrows,
+    pub(crate) borrows: Borrows,
+    pub(crate) labels: Vec<SystemLabel>,
+    pub(crate) before: Vec<SystemLabel>,
+    pub(crate) after: Vec<SystemLabel>,
+    conditions: Vec<DynamicCondition>,
+    // Change ticks of the data watched by run_if_changed, as of the last run
+    watched: Vec<(TypeId, Option<u64>)>,
+    pub(crate) exclusive: bool,
+    flush: bool,
+    id: usize,
+    pub(crate) enabled: bool,
+    timing: SystemTiming,
+    retry: RetryPolicy,
+    priority: i32,
+    timeout: Option<Duration>,
+    pub(crate) main_thread: bool,
+    pub(crate) queries: Vec<(&'static str, Borrows)>,
+    locals: Locals,
 }
 
 #[doc(hidden)]
 impl DynamicSystem {
-    fn new<S, Args, Ret>(mut system: S) -> Self
+    pub(crate) fn new<S, Args, Ret>(mut system: S) -> Self
     where
         S: 'static + System<Args, Ret> + Send,
     {
-        let borrows = S::borrows();
         let name = system.name();
-        Self {
-            func: Box::new(move |context| system.execute(context)),
-            name,
-            borrows,
-        }
+        Self::from_parts(name, S::borrows(), move |context| system.execute(context))
     }
 
-    fn execute(&mut self, context: &Context) -> Result<()> {
-        (self.func)(context)
+    pub(crate) fn new_named<S, Args, Ret>(label: SystemLabel, system: S) -> Self
+    where
+        S: 'static + System<Args, Ret> + Send,
+    {
+        let mut system = Self::new(system.named(label.clone()));
+        system.labels.push(label);
+        system
     }
 
-    /// Get a reference to the dynamic system's name.
-    pub fn name(&self) -> &str {
-        self.name.as_ref()
+    fn new_exclusive<S, Args, Ret>(mut system: S) -> Self
+    where
+        S: 'static + ExclusiveSystem<Args, Ret> + Send,
+    {
+        let name = system.name();
+        let mut system =
+            Self::from_parts(name, S::borrows(), move |context| system.execute(context));
+        system.exclusive = true;
+        system
     }
-}
 
-/// A shedule represents a collections of system which will run with effects in
-/// a determined order.
-pub struct Schedule {
-    batches: Vec<Batch>,
-    cmd: CommandBuffer,
-}
+    fn new_async<S, Args>(mut system: S) -> Self
+    where
+        S: 'static + AsyncSystem<Args> + Send,
+    {
+        let name = system.name();
+        Self::from_fn(
+            name,
+            S::borrows(),
+            SystemFn::Async(Box::new(move |context| system.start(context))),
+        )
+    }
 
-impl Schedule {
-    /// Creates a new schedule from provided batches.
-    pub fn new(batches: Vec<Batch>) -> Self {
+    fn from_parts(
+        name: SystemName,
+        borrows: Borrows,
+        func: impl FnMut(&Context) -> Result<()> + Send + 'static,
+    ) -> Self {
+        Self::from_fn(name, borrows, SystemFn::Blocking(Box::new(func)))
+    }
+
+    fn from_fn(name: SystemName, borrows: Borrows, func: SystemFn) -> Self {
         Self {
-            batches,
-            cmd: Default::default(),
+            func,
+            timing: SystemTiming::new(name.clone()),
+            name,
+            borrows,
+            labels: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            conditions: Vec::new(),
+            watched: Vec::new(),
+            exclusive: false,
+            flush: false,
+            id: usize::MAX,
+            enabled: true,
+            retry: RetryPolicy::default(),
+            priority: 0,
+            timeout: None,
+            main_thread: false,
+            queries: Vec::new(),
+            locals: Locals::default(),
         }
     }
 
-    /// Returns information of how the schedule was split into batches
-    pub fn batch_info(&self) -> BatchInfo {
-        BatchInfo {
-            batches: &self.batches,
+    /// Creates a system which runs all systems of `schedule` in order
+    fn from_schedule(mut schedule: Schedule) -> Self {
+        let mut borrows = Borrows::new();
+        let mut exclusive = false;
+
+        for system in schedule.dynamic_systems() {
+            exclusive |= system.exclusive;
+            for access in &system.borrows {
+                if !borrows.contains(access) {
+                    borrows.push(*access);
+                }
+            }
         }
+
+        let mut system = Self::from_parts("Schedule".into(), borrows, move |context| {
+            schedule.run_seq(context)
+        });
+
+        system.exclusive = exclusive;
+        system
     }
 
-    /// Creates a new [ScheduleBuilder]
-    pub fn builder() -> ScheduleBuilder {
-        ScheduleBuilder::default()
+    /// Creates the system which applies the commandbuffer
+    fn flush() -> Self {
+        let mut system = Self::new(flush_system);
+        system.flush = true;
+        system
     }
 
-    /// Executes the systems inside the schedule sequentially using the provided data, which
-    /// is a tuple of mutable references. Returns Err if any system fails.
-    ///
-    /// A commandbuffer is always available and will be flushed at the end.
-    pub fn execute_seq<D: IntoData<CommandBuffer>>(&mut self, data: D) -> Result<()> {
-        let data = unsafe { data.into_data(&mut self.cmd) };
+    // Returns true if the system is enabled and all conditions are met
+    fn should_run(&mut self, context: &Context) -> Result<bool> {
+        if !self.enabled {
+            return Ok(false);
+        }
 
-        let context = Context::new(&data);
+        for condition in &mut self.conditions {
+            if !condition.evaluate(context)? {
+                return Ok(false);
+            }
+        }
 
-        self.batches.iter_mut().try_for_each(|batch| {
-            batch
-                .iter_mut()
-                .try_for_each(|system| system.execute(&context))
-        })
-    }
+        if self.watched.is_empty() {
+            return Ok(true);
+        }
 
-    #[cfg(feature = "parallel")]
-    /// Executes the systems inside the schedule ina parallel using the provided data, which
-    /// is a tuple of mutable references. Returns Err if any system fails
-    ///
-    /// A commandbuffer is always available and will be flushed at the end.
-    pub fn execute<D: IntoData<CommandBuffer> + Send + Sync>(&mut self, data: D) -> Result<()> {
-        let data = unsafe { data.into_data(&mut self.cmd) };
+        let ticks = match context.ticks() {
+            Some(ticks) => ticks,
+            None => return Ok(true),
+        };
 
-        let context = Context::new(&data);
+        let current: SmallVec<[u64; 4]> = self
+            .watched
+            .iter()
+            .map(|&(id, _)| ticks.watch(id))
+            .collect();
 
-        self.batches.iter_mut().try_for_each(|batch| {
-            batch
-                .par_iter_mut()
-                .try_for_each(|system| system.execute(&context))
-        })
+        let changed = self
+            .watched
+            .iter()
+            .zip(&current)
+            .all(|(&(_, seen), &tick)| seen != Some(tick));
+
+        // Only consumed when the system runs
+        if changed {
+            for ((_, seen), tick) in self.watched.iter_mut().zip(current) {
+                *seen = Some(tick);
+            }
+        }
+
+        Ok(changed)
     }
 
-    /// Get a reference to the schedule's cmd.
-    pub fn cmd(&self) -> &CommandBuffer {
-        &self.cmd
+    fn execute(&mut self, context: &Context, hooks: &[Hook]) -> Result<()> {
+        if !self.should_run(context)? {
+            return Ok(());
+        }
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("system", name = %self.name).entered();
+
+        self.invoke_hooks(hooks, HookPhase::Before);
+
+        let start = Instant::now();
+        let result = self.call(context);
+        let end = Instant::now();
+        let elapsed = end.duration_since(start);
+
+        self.timing.record(context.run(), start, end);
+        if self.timeout.map_or(false, |timeout| elapsed > timeout) {
+            self.timing.record_overrun();
+        }
+
+        self.invoke_hooks(hooks, HookPhase::After);
+
+        result
     }
 
-    /// Get a mutable reference to the schedule's cmd.
-    pub fn cmd_mut(&mut self) -> &mut CommandBuffer {
-        &mut self.cmd
+    fn invoke_hooks(&self, hooks: &[Hook], phase: HookPhase) {
+        let info = self.info();
+        hooks.iter().for_each(|hook| hook(&info, phase));
     }
-}
 
-#[derive(Default)]
-/// Builder for incrementally constructing a schedule.
-pub struct ScheduleBuilder {
-    batches: Vec<Batch>,
-    current_batch: Batch,
-    current_borrows: HashMap<TypeId, Access>,
-}
+    // Calls the system, retrying according to the retry policy
+    fn call(&mut self, context: &Context) -> Result<()> {
+        let mut retries = 0;
 
-impl ScheduleBuilder {
-    /// Creates a new [ScheduleBuilder]
-    pub fn new() -> Self {
-        Default::default()
+        loop {
+            match self.invoke(context) {
+                Err(Error::SystemError(..)) if retries < self.retry.max_retries() => {
+                    retries += 1;
+                    self.retry.wait();
+                }
+                result => return result,
+            }
+        }
     }
 
-    /// Add a system to the builder
-    pub fn add_system<Args, Ret, S>(&mut self, system: S) -> &mut Self
-    where
-        S: 'static + System<Args, Ret> + Send,
-    {
-        self.add_internal(DynamicSystem::new(system));
-        self
+    // Calls the system once, blocking until asynchronous systems complete
+    fn invoke(&mut self, context: &Context) -> Result<()> {
+        let context = &context.with_locals(&self.locals, self.id);
+
+        match &mut self.func {
+            SystemFn::Blocking(func) => func(context),
+            SystemFn::Async(func) => {
+                let future = func(context)?;
+                match self.timeout {
+                    Some(timeout) => {
+                        let future = with_timeout(future, self.name.clone(), timeout);
+                        block_on(future, Some(Instant::now() + timeout))
+                    }
+                    None => block_on(future, None),
+                }
+            }
+        }
     }
 
-    fn add_internal(&mut self, system: DynamicSystem) {
-        // Check borrow
-        let borrows = &system.borrows;
+    /// Starts asynchronous systems and returns their future. Blocking systems
+    /// are run to completion.
+    fn start<'a>(
+        &mut self,
+        context: &'a Context,
+        hooks: &[Hook],
+    ) -> Result<Option<SystemFuture<'a>>> {
+        if let SystemFn::Blocking(_) = self.func {
+            return self.execute(context, hooks).map(|_| None);
+        }
 
-        if !self.check_compatible(borrows) {
-            // Push and create a new batch
-            self.barrier();
+        if !self.should_run(context)? {
+            return Ok(None);
         }
 
-        self.add_borrows(borrows);
-        self.current_batch.push(system);
-    }
+        self.invoke_hooks(hooks, HookPhase::Before);
 
-    /// Append all system from `other` into self, leaving `other` empty.
-    /// This allows constructing smaller schedules in different modules and then
-    /// joining them together. Work will be paralellized between the two
-    /// schedules.
-    pub fn append(&mut self, other: &mut ScheduleBuilder) -> &mut Self {
-        other.barrier();
+        let mut future = match &mut self.func {
+            SystemFn::Async(func) => func(context)?,
+            SystemFn::Blocking(_) => unreachable!(),
+        };
 
-        other.batches.drain(..).for_each(|mut batch| {
-            batch
-                .systems
-                .drain(..)
-                .for_each(|system| self.add_internal(system))
-        });
+        if let Some(timeout) = self.timeout {
+            future = with_timeout(future, self.name.clone(), timeout);
+        }
 
-        self
-    }
+        if hooks.is_empty() {
+            return Ok(Some(future));
+        }
 
-    /// Inserts a barrier that will divide the schedule pararell execution in
-    /// two dependant halves.
-    ///
-    /// Usually this is not required, as the borrows of the system automatically
-    /// creates dependencies, but sometimes a manual dependency is needed for things
-    /// such as interior mutability or channels.
-    pub fn barrier(&mut self) -> &mut Self {
-        let batch = std::mem::take(&mut self.current_batch);
+        let hooks = hooks.to_vec();
+        let name = self.name.clone();
+        let borrows = self.borrows.clone();
 
-        self.batches.push(batch);
+        Ok(Some(Box::pin(async move {
+            let result = future.await;
 
-        self.current_borrows.clear();
+            let info = SystemInfo {
+                name: &name,
+                borrows: &borrows,
+            };
 
-        self
+            hooks.iter().for_each(|hook| hook(&info, HookPhase::After));
+            result
+        })))
     }
 
-    /// Flush the commandbuffer and apply the commands to the world
-    pub fn flush(&mut self) -> &mut Self {
-        self.current_batch.has_flush = true;
-        self.add_system(flush_system)
+    fn run(&mut self, context: &Context, options: &RunOptions) -> Result<()> {
+        self.run_unattributed(context, options)
+            .map_err(|e| e.in_system(&self.name))
     }
 
-    fn add_borrows(&mut self, borrows: &Borrows) {
-        self.current_borrows
-            .extend(borrows.into_iter().map(|val| (val.id(), *val)))
+    // Runs the system without attributing errors which occur outside of the
+    // system itself, such as borrow errors, to the system
+    fn run_unattributed(&mut self, context: &Context, options: &RunOptions) -> Result<()> {
+        let context = &context.with_schedule(&options.deferred, &options.ticks);
+        if options.catch_panics {
+            panic::catch_unwind(AssertUnwindSafe(|| self.execute(context, &options.hooks)))
+                .unwrap_or_else(|payload| {
+                    Err(Error::SystemPanic {
+                        name: self.name.clone(),
+                        payload: panic_message(payload),
+                    })
+                })
+        } else {
+            self.execute(context, &options.hooks)
+        }
     }
 
-    /// Returns true if no borrows conflict with the current ones
-    fn check_compatible(&self, borrows: &Borrows) -> bool {
-        for borrow in borrows {
-            // Type is already borrowed
-            if let Some(curr) = self.current_borrows.get(&borrow.id()) {
-                // Already exclusively borrowed or new borrow is exlcusive
-                if curr.exclusive() || borrow.exclusive() {
-                    return false;
-                }
-            }
-        }
+    pub(crate) fn add_condition(&mut self, condition: DynamicCondition) {
+        self.borrows.extend(condition.borrows.iter().copied());
+        self.conditions.push(condition);
+    }
 
-        true
+    /// Get a reference to the dynamic system's name.
+    pub fn name(&self) -> &str {
+        self.name.as_ref()
     }
 
-    /// FLushes the commandbuffer and builds the schedule.
-    pub fn build(&mut self) -> Schedule {
-        self.flush();
-        // Push the current batch
-        self.barrier();
+    /// Describes the system
+    pub fn info(&self) -> SystemInfo {
+        SystemInfo {
+            name: &self.name,
+            borrows: &self.borrows,
+        }
+    }
 
-        let builder = std::mem::take(self);
+    /// Returns the durations of the most recent executions of the system
+    pub fn timing(&self) -> &SystemTiming {
+        &self.timing
+    }
 
-        Schedule::new(builder.batches)
+    pub(crate) fn has_label(&self, label: &str) -> bool {
+        self.labels.iter().any(|l| l == label)
+    }
+}
+
+/// A shedule represents a collections of system which will run with effects in
+/// a determined order.
+pub struct Schedule {
+    batches: Vec<Batch>,
+    startup: Vec<Batch>,
+    layout: Vec<Slot>,
+    next_id: usize,
+    final_flush: bool,
+    timing_window: usize,
+    options: RunOptions,
+    strategy: Arc<dyn BatchingStrategy>,
+    // The next batch and run id of a budgeted execution
+    cursor: Option<(usize, u64)>,
+    cmd: CommandBuffer,
+}
+
+// Position of a system or barrier in the order the schedule was built
+enum Slot {
+    System(usize),
+    Barrier,
+}
+
+impl Schedule {
+    /// Creates a new schedule from provided batches.
+    pub fn new(mut batches: Vec<Batch>) -> Self {
+        let mut layout = Vec::new();
+        let mut next_id = 0;
+
+        for batch in &mut batches {
+            for system in batch.iter_mut() {
+                system.id = next_id;
+                layout.push(Slot::System(next_id));
+                next_id += 1;
+            }
+
+            layout.push(Slot::Barrier);
+        }
+
+        Self {
+            batches,
+            startup: Vec::new(),
+            layout,
+            next_id,
+            final_flush: false,
+            timing_window: DEFAULT_TIMING_WINDOW,
+            options: RunOptions::default(),
+            strategy: Arc::new(Greedy),
+            cursor: None,
+            cmd: Default::default(),
+        }
+    }
+
+    /// Creates a schedule from nodes in insertion order, which is flushed at
+    /// the end
+    fn from_nodes(nodes: Vec<Node>, strategy: Arc<dyn BatchingStrategy>) -> Self {
+        let mut schedule = Self::new(Vec::new());
+        schedule.final_flush = true;
+        schedule.strategy = strategy;
+
+        let systems: Vec<_> = nodes
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::System(system) => Some(schedule.register(system)),
+                Node::Barrier => {
+                    schedule.layout.push(Slot::Barrier);
+                    None
+                }
+            })
+            .collect();
+
+        schedule.replan(systems);
+        schedule
+    }
+
+    // Assigns an id to the system and places it last in the layout
+    fn register(&mut self, mut system: DynamicSystem) -> DynamicSystem {
+        system.id = self.next_id;
+        system.timing.set_window(self.timing_window);
+        self.next_id += 1;
+        self.layout.push(Slot::System(system.id));
+        system
+    }
+
+    /// Recomputes the batches from the layout, including the `added` systems
+    fn replan(&mut self, added: Vec<DynamicSystem>) {
+        let strategy = self.strategy.clone();
+        self.replan_with(added, &*strategy)
+    }
+
+    fn replan_with(&mut self, added: Vec<DynamicSystem>, strategy: &dyn BatchingStrategy) {
+        let mut systems: HashMap<_, _> = self
+            .batches
+            .drain(..)
+            .flat_map(|batch| batch.systems.into_iter())
+            .chain(added)
+            .map(|system| (system.id, system))
+            .collect();
+
+        let mut nodes: Vec<_> = self
+            .layout
+            .iter()
+            .filter_map(|slot| match *slot {
+                Slot::System(id) => systems.remove(&id).map(Node::System),
+                Slot::Barrier => Some(Node::Barrier),
+            })
+            .collect();
+
+        // The batches of a partial run no longer apply
+        self.cursor = None;
+
+        if self.final_flush {
+            nodes.push(Node::Barrier);
+            nodes.push(Node::System(DynamicSystem::flush()));
+        }
+
+        self.batches = plan(nodes, strategy);
+    }
+
+    /// Recomputes the batches using the measured durations of the systems, see
+    /// [timings](Self::timings), such that the batches are more evenly long
+    /// and parallel execution keeps more threads occupied.
+    ///
+    /// Systems may move to other batches, but never across their ordering
+    /// constraints, conflicting borrows or barriers, and the number of batches
+    /// never increases. Systems without measurements are assumed to take no
+    /// time. Later changes to the schedule, such as adding systems, recompute
+    /// the batches using the strategy of the schedule.
+    ///
+    /// See [LatencyOptimized](crate::strategy::LatencyOptimized).
+    pub fn rebalance(&mut self) {
+        self.replan_with(Vec::new(), &LatencyOptimized)
+    }
+
+    /// Adds a system to the already built schedule. The system is placed after
+    /// all existing systems, but before the final flush.
+    ///
+    /// The batches are recomputed.
+    ///
+    /// # Panics
+    /// Panics if the ordering constraints of the systems form a cycle.
+    pub fn add_system_dynamic<Args, Ret, S>(&mut self, system: S) -> &mut Self
+    where
+        S: 'static + System<Args, Ret> + Send,
+    {
+        let system = self.register(DynamicSystem::new(system));
+        self.replan(vec![system]);
+        self
+    }
+
+    /// Adds a system named and labeled by `label` to the already built
+    /// schedule. See [Self::add_system_dynamic].
+    pub fn add_system_dynamic_named<L, Args, Ret, S>(&mut self, label: L, system: S) -> &mut Self
+    where
+        L: Into<SystemLabel>,
+        S: 'static + System<Args, Ret> + Send,
+    {
+        let system = self.register(DynamicSystem::new_named(label.into(), system));
+        self.replan(vec![system]);
+        self
+    }
+
+    /// Adds all systems of `set` to the already built schedule, leaving `set`
+    /// empty. See [Self::add_system_dynamic].
+    pub fn add_set_dynamic(&mut self, set: &mut SystemSet) -> &mut Self {
+        let systems = std::mem::take(set)
+            .into_systems()
+            .map(|system| self.register(system))
+            .collect();
+
+        self.replan(systems);
+        self
+    }
+
+    /// Enables or disables all systems labeled `label`. Returns the number of
+    /// affected systems.
+    ///
+    /// Disabled systems are skipped during execution, and their borrows are
+    /// ignored when batching, which allows other systems to run in parallel.
+    pub fn set_enabled(&mut self, label: &str, enabled: bool) -> usize {
+        let mut count = 0;
+        let mut changed = false;
+
+        for system in self.batches.iter_mut().flat_map(|batch| batch.iter_mut()) {
+            if system.has_label(label) {
+                count += 1;
+                changed |= system.enabled != enabled;
+                system.enabled = enabled;
+            }
+        }
+
+        if changed {
+            self.replan(Vec::new());
+        }
+
+        count
+    }
+
+    /// Removes all systems labeled `label` from the schedule and recomputes the
+    /// batches. Returns the number of removed systems.
+    pub fn remove_system(&mut self, label: &str) -> usize {
+        let mut removed = Vec::new();
+
+        for batch in &mut self.batches {
+            batch.systems.retain(|system| {
+                let keep = !system.has_label(label);
+                if !keep {
+                    removed.push(system.id);
+                }
+                keep
+            });
+        }
+
+        self.layout.retain(|slot| match slot {
+            Slot::System(id) => !removed.contains(id),
+            Slot::Barrier => true,
+        });
+
+        self.replan(Vec::new());
+        removed.len()
+    }
+
+    /// Returns information of how the schedule was split into batches
+    pub fn batch_info(&self) -> BatchInfo {
+        BatchInfo {
+            batches: &self.batches,
+        }
+    }
+
+    /// Returns a description of each batch of the schedule, in execution
+    /// order
+    pub fn batches(&self) -> Vec<BatchDescription> {
+        self.batches
+            .iter()
+            .enumerate()
+            .map(|(index, batch)| BatchDescription {
+                index,
+                systems: batch.iter().map(DynamicSystem::info).collect(),
+                has_flush: batch.has_flush,
+            })
+            .collect()
+    }
+
+    // Returns all systems, including startup systems which have not yet run
+    fn dynamic_systems(&self) -> impl Iterator<Item = &DynamicSystem> {
+        self.startup
+            .iter()
+            .chain(&self.batches)
+            .flat_map(|batch| batch.iter())
+    }
+
+    /// Describes all systems of the schedule and the data they access, in
+    /// execution order. Startup systems which have not yet run come first.
+    pub fn systems(&self) -> impl Iterator<Item = SystemInfo> {
+        self.dynamic_systems().map(DynamicSystem::info)
+    }
+
+    /// Returns the execution timings of each system, in execution order
+    pub fn timings(&self) -> Vec<&SystemTiming> {
+        self.batches
+            .iter()
+            .flat_map(|batch| batch.iter())
+            .map(DynamicSystem::timing)
+            .collect()
+    }
+
+    /// Sets the number of most recent executions timings are kept for. The
+    /// default is 64. A window of zero disables timing.
+    pub fn set_timing_window(&mut self, window: usize) {
+        self.timing_window = window;
+        self.batches
+            .iter_mut()
+            .flat_map(|batch| batch.iter_mut())
+            .for_each(|system| system.timing.set_window(window));
+    }
+
+    /// Allocates the state the executor would otherwise allocate during the
+    /// first executions, such as the timing buffers of each system and the
+    /// worker threads of the thread pool, to avoid a spike on the first frame.
+    ///
+    /// This only removes the allocations specific to the first executions.
+    /// Every execution still allocates, e.g. for the tasks handed to the
+    /// thread pool, the systems pinned to the main thread and the commands
+    /// recorded by systems. Changing the timing window with
+    /// [Self::set_timing_window] requires warming up again.
+    pub fn warm_up(&mut self) {
+        self.startup
+            .iter_mut()
+            .chain(self.batches.iter_mut())
+            .flat_map(|batch| batch.iter_mut())
+            .for_each(|system| system.timing.reserve());
+
+        // Starts the worker threads of the global thread pool
+        #[cfg(feature = "parallel")]
+        if self.options.pool.is_none() {
+            rayon::current_num_threads();
+        }
+    }
+
+    /// Creates a new [ScheduleBuilder]
+    pub fn builder() -> ScheduleBuilder {
+        ScheduleBuilder::default()
+    }
+
+    /// Executes the systems inside the schedule sequentially using the provided data, which
+    /// is a tuple of mutable references. Returns Err if any system fails.
+    ///
+    /// A commandbuffer is always available and will be flushed at the end.
+    pub fn execute_seq<D: IntoData<CommandBuffer>>(&mut self, data: D) -> Result<()> {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+
+        self.run_seq(&context)
+    }
+
+    /// Executes the systems inside the schedule sequentially like
+    /// [execute_seq](Self::execute_seq), but keeps executing after a system
+    /// fails and returns the result of every system, in execution order.
+    ///
+    /// Systems which were skipped, due to a run condition or being disabled,
+    /// report Ok. The error policy of the schedule does not apply. As each
+    /// result is paired with the name of its system, errors are not wrapped
+    /// in [Error::SystemError] unless the system itself failed.
+    pub fn execute_seq_all<D: IntoData<CommandBuffer>>(
+        &mut self,
+        data: D,
+    ) -> Vec<(SystemName, Result<()>)> {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+        let context = &context.next_run();
+        let options = self.options.clone();
+
+        let run = |system: &mut DynamicSystem| {
+            let result = system.run_unattributed(context, &options);
+            (system.name.clone(), result)
+        };
+
+        let mut results: Vec<_> = self
+            .startup
+            .iter_mut()
+            .flat_map(|batch| batch.iter_mut())
+            .map(run)
+            .collect();
+
+        // Startup systems run again by the next execution unless all succeed
+        if results.iter().all(|(_, result)| result.is_ok()) {
+            self.startup.clear();
+        }
+
+        results.extend(
+            self.batches
+                .iter_mut()
+                .flat_map(|batch| batch.iter_mut())
+                .map(run),
+        );
+
+        results
+    }
+
+    /// Executes the systems one at a time in a fixed order, batch by batch and
+    /// by priority and insertion order within each batch.
+    ///
+    /// The order only depends on how the schedule was built, so executing
+    /// identically built schedules with identical data produces side effects
+    /// in an identical order. This is useful for lockstep networking and
+    /// replays.
+    pub fn execute_deterministic<D: IntoData<CommandBuffer>>(&mut self, data: D) -> Result<()> {
+        self.execute_seq(data)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    /// Executes the systems inside the schedule sequentially using the provided
+    /// data, as the `parallel` feature is disabled. Returns Err if any system
+    /// fails.
+    ///
+    /// A commandbuffer is always available and will be flushed at the end.
+    pub fn execute<D: IntoData<CommandBuffer>>(&mut self, data: D) -> Result<()> {
+        self.execute_seq(data)
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Executes the systems inside the schedule ina parallel using the provided data, which
+    /// is a tuple of mutable references. Returns Err if any system fails
+    ///
+    /// A commandbuffer is always available and will be flushed at the end.
+    pub fn execute<D: IntoData<CommandBuffer> + Send + Sync>(&mut self, data: D) -> Result<()> {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+
+        self.run_par(&context)
+    }
+
+    /// Repeatedly executes the systems inside the schedule sequentially until
+    /// `until` returns true after an execution. Returns the number of
+    /// executions, or Err if any system fails.
+    ///
+    /// The condition may borrow from the context, e.g;
+    /// `|context| context.borrow::<Read<Quit>>().is_ok()`.
+    pub fn execute_seq_until<D, F>(&mut self, data: D, until: F) -> Result<usize>
+    where
+        D: IntoData<CommandBuffer>,
+        F: FnMut(&Context) -> bool,
+    {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+
+        self.run_until(&context, until, Self::run_seq)
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Repeatedly executes the systems inside the schedule in parallel until
+    /// `until` returns true after an execution. See
+    /// [Self::execute_seq_until].
+    pub fn execute_until<D, F>(&mut self, data: D, until: F) -> Result<usize>
+    where
+        D: IntoData<CommandBuffer> + Send + Sync,
+        F: FnMut(&Context) -> bool,
+    {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+
+        self.run_until(&context, until, Self::run_par)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    /// Repeatedly executes the systems inside the schedule sequentially until
+    /// `until` returns true after an execution, as the `parallel` feature is
+    /// disabled. See [Self::execute_seq_until].
+    pub fn execute_until<D, F>(&mut self, data: D, until: F) -> Result<usize>
+    where
+        D: IntoData<CommandBuffer>,
+        F: FnMut(&Context) -> bool,
+    {
+        self.execute_seq_until(data, until)
+    }
+
+    fn run_until(
+        &mut self,
+        context: &Context,
+        mut until: impl FnMut(&Context) -> bool,
+        run: fn(&mut Self, &Context) -> Result<()>,
+    ) -> Result<usize> {
+        let mut runs = 0;
+
+        loop {
+            run(self, context)?;
+            runs += 1;
+
+            if until(context) {
+                return Ok(runs);
+            }
+        }
+    }
+
+    /// Executes the batches of the schedule sequentially until `budget` has
+    /// elapsed, after which no new batches are started. The next call resumes
+    /// with the first batch which did not run. At least one batch runs per
+    /// call.
+    ///
+    /// Returns true if the end of the schedule was reached, or Err if any
+    /// system fails, in which case the next call starts over.
+    pub fn execute_seq_budgeted<D: IntoData<CommandBuffer>>(
+        &mut self,
+        data: D,
+        budget: Duration,
+    ) -> Result<bool> {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+
+        self.run_budgeted(&context, budget, Batch::run_seq)
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Executes the batches of the schedule in parallel until `budget` has
+    /// elapsed. See [Self::execute_seq_budgeted].
+    pub fn execute_budgeted<D: IntoData<CommandBuffer> + Send + Sync>(
+        &mut self,
+        data: D,
+        budget: Duration,
+    ) -> Result<bool> {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+
+        self.run_budgeted(&context, budget, Batch::run_par)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    /// Executes the batches of the schedule sequentially until `budget` has
+    /// elapsed, as the `parallel` feature is disabled. See
+    /// [Self::execute_seq_budgeted].
+    pub fn execute_budgeted<D: IntoData<CommandBuffer>>(
+        &mut self,
+        data: D,
+        budget: Duration,
+    ) -> Result<bool> {
+        self.execute_seq_budgeted(data, budget)
+    }
+
+    fn run_budgeted(
+        &mut self,
+        context: &Context,
+        budget: Duration,
+        run: fn(&mut Batch, &Context, &RunOptions) -> Result<()>,
+    ) -> Result<bool> {
+        let start = Instant::now();
+        let options = self.options.clone();
+
+        let (first, id) = self
+            .cursor
+            .take()
+            .unwrap_or_else(|| (0, context.next_run().run()));
+
+        let context = &context.with_run(id);
+
+        self.run_startup(context, &options, run)?;
+
+        for index in first..self.batches.len() {
+            if index > first && start.elapsed() >= budget {
+                self.cursor = Some((index, id));
+                return Ok(false);
+            }
+
+            run(&mut self.batches[index], context, &options)?;
+        }
+
+        Ok(true)
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Executes the batches of the schedule in parallel, skipping all batches
+    /// which would start after `deadline`. Returns which systems were executed
+    /// and which were skipped, or Err if any system fails.
+    ///
+    /// Unlike [Self::execute_budgeted], skipped batches are not resumed by the
+    /// next call. Startup systems always run.
+    pub fn execute_with_deadline<D: IntoData<CommandBuffer> + Send + Sync>(
+        &mut self,
+        data: D,
+        deadline: Instant,
+    ) -> Result<DeadlineReport> {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+
+        self.run_with_deadline(&context, deadline, Batch::run_par)
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    /// Executes the batches of the schedule sequentially, skipping all batches
+    /// which would start after `deadline`, as the `parallel` feature is
+    /// disabled. Returns which systems were executed and which were skipped,
+    /// or Err if any system fails.
+    ///
+    /// Unlike [Self::execute_budgeted], skipped batches are not resumed by the
+    /// next call. Startup systems always run.
+    pub fn execute_with_deadline<D: IntoData<CommandBuffer>>(
+        &mut self,
+        data: D,
+        deadline: Instant,
+    ) -> Result<DeadlineReport> {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+
+        self.run_with_deadline(&context, deadline, Batch::run_seq)
+    }
+
+    fn run_with_deadline(
+        &mut self,
+        context: &Context,
+        deadline: Instant,
+        run: fn(&mut Batch, &Context, &RunOptions) -> Result<()>,
+    ) -> Result<DeadlineReport> {
+        let context = &context.next_run();
+        let options = self.options.clone();
+        let mut report = DeadlineReport::default();
+
+        self.run_startup(context, &options, run)?;
+
+        for batch in &mut self.batches {
+            let names: Vec<_> = batch.iter().map(|system| system.name.clone()).collect();
+
+            if Instant::now() < deadline {
+                run(batch, context, &options)?;
+                report.executed.extend(names);
+            } else {
+                report.skipped.extend(names);
+            }
+        }
+
+        Ok(report)
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Executes the schedule `runs` times in parallel, overlapping the last
+    /// batch of each run with the first batch of the next run when none of
+    /// their systems conflict. Returns Err if any system fails.
+    ///
+    /// This hides the latency of schedules which end with long running
+    /// systems. As the last batch is usually the final flush, overlapping only
+    /// occurs if the first batch does not access the world or the
+    /// commandbuffer.
+    pub fn execute_pipelined<D: IntoData<CommandBuffer> + Send + Sync>(
+        &mut self,
+        data: D,
+        runs: usize,
+    ) -> Result<()> {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+
+        match self.options.pool.clone() {
+            Some(pool) => pool.install(|| self.run_pipelined(&context, runs)),
+            None => self.run_pipelined(&context, runs),
+        }
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    /// Executes the schedule `runs` times sequentially, as the `parallel`
+    /// feature is disabled. Returns Err if any system fails.
+    pub fn execute_pipelined<D: IntoData<CommandBuffer>>(
+        &mut self,
+        data: D,
+        runs: usize,
+    ) -> Result<()> {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+
+        (0..runs).try_for_each(|_| self.run_seq(&context))
+    }
+
+    #[cfg(feature = "parallel")]
+    fn run_pipelined(&mut self, context: &Context, runs: usize) -> Result<()> {
+        // Startup systems only run as part of the first run
+        if runs == 0 {
+            return Ok(());
+        }
+
+        let options = self.options.clone();
+
+        self.run_startup(context, &options, Batch::run_par)?;
+
+        let len = self.batches.len();
+        let overlap = len >= 2 && !self.batches[len - 1].conflicts_with(&self.batches[0]);
+
+        let mut current = context.next_run();
+        for run in 0..runs {
+            let first = if overlap && run > 0 { 1 } else { 0 };
+            let last = if overlap && run + 1 < runs {
+                len - 1
+            } else {
+                len
+            };
+
+            self.batches[first..last]
+                .iter_mut()
+                .try_for_each(|batch| batch.run_par(&current, &options))?;
+
+            if last < len {
+                let next = context.next_run();
+                let (head, tail) = self.batches.split_at_mut(len - 1);
+
+                let (a, b) = rayon::join(
+                    || tail[0].run_par(&current, &options),
+                    || head[0].run_par(&next, &options),
+                );
+
+                a?;
+                b?;
+                current = next;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Executes several schedules concurrently using the provided data. Each
+    /// schedule uses its own commandbuffer. Returns Err if any system fails.
+    ///
+    /// The schedules must access disjoint data, which is verified before any
+    /// of them run. As flushing writes to the frame, schedules which flush
+    /// conflict with all other schedules accessing the frame, unless no frame
+    /// is provided.
+    pub fn join<D: IntoData<CommandBuffer> + Send + Sync>(
+        schedules: &mut [&mut Schedule],
+        data: D,
+    ) -> Result<()> {
+        let mut cmd = CommandBuffer::new();
+        let data = unsafe { data.into_data(&mut cmd) };
+
+        Self::check_disjoint(schedules, &data)?;
+
+        let overlays: Vec<_> = schedules
+            .iter_mut()
+            .map(|schedule| unsafe { DataOverlay::new(&data, &mut schedule.cmd) })
+            .collect();
+
+        let contexts: Vec<_> = overlays
+            .iter()
+            .map(|overlay| Context::new(overlay))
+            .collect();
+
+        schedules
+            .par_iter_mut()
+            .zip(contexts.par_iter())
+            .try_for_each(|(schedule, context)| schedule.run_par(context))
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    /// Executes several schedules one after another using the provided data,
+    /// as the `parallel` feature is disabled. Each schedule uses its own
+    /// commandbuffer. Returns Err if any system fails.
+    ///
+    /// The schedules must access disjoint data, which is verified before any
+    /// of them run.
+    pub fn join<D: IntoData<CommandBuffer>>(
+        schedules: &mut [&mut Schedule],
+        data: D,
+    ) -> Result<()> {
+        let mut cmd = CommandBuffer::new();
+        let data = unsafe { data.into_data(&mut cmd) };
+
+        Self::check_disjoint(schedules, &data)?;
+
+        schedules.iter_mut().try_for_each(|schedule| {
+            let overlay = unsafe { DataOverlay::new(&data, &mut schedule.cmd) };
+            schedule.run_seq(&Context::new(&overlay))
+        })
+    }
+
+    // Fails if any two schedules access conflicting data
+    fn check_disjoint(schedules: &[&mut Schedule], data: &dyn Data) -> Result<()> {
+        let has_frame = data.get(TypeId::of::<Frame>()).is_some();
+
+        // Each schedule has its own commandbuffer, and flushing without a
+        // frame does nothing
+        let mut ignored = <Write<CommandBuffer> as ComponentBorrow>::borrows();
+        if !has_frame {
+            ignored.extend(<MaybeWrite<Frame> as ComponentBorrow>::borrows());
+        }
+
+        let borrows: Vec<Borrows> = schedules
+            .iter()
+            .map(|schedule| {
+                schedule
+                    .dynamic_systems()
+                    .flat_map(|system| system.borrows.iter())
+                    .filter(|access| !ignored.contains(access))
+                    .copied()
+                    .collect()
+            })
+            .collect();
+
+        for (i, a) in borrows.iter().enumerate() {
+            for (j, b) in borrows.iter().enumerate().skip(i + 1) {
+                if conflicts(a, b) {
+                    return Err(Error::ConflictingSchedules(i, j));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes the systems inside the schedule using the provided data,
+    /// polling the futures of the asynchronous systems in each batch
+    /// concurrently. Blocking systems are run sequentially on the calling
+    /// thread. Returns Err if any system fails.
+    ///
+    /// The returned future is driven by the caller, and can thus be awaited
+    /// in any runtime. Timings and retries are not applied to asynchronous
+    /// systems, and panics are not caught.
+    ///
+    /// A commandbuffer is always available and will be flushed at the end.
+    pub async fn execute_async<D: IntoData<CommandBuffer>>(&mut self, data: D) -> Result<()> {
+        let data = unsafe { data.into_data(&mut self.cmd) };
+
+        let context = Context::new(&data);
+        let options = self.options.clone();
+
+        while let Some(batch) = self.startup.first_mut() {
+            batch.run_async(&context, &options).await?;
+            self.startup.remove(0);
+        }
+
+        for batch in &mut self.batches {
+            batch.run_async(&context, &options).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Executes the systems sequentially against an existing context
+    pub(crate) fn run_seq(&mut self, context: &Context) -> Result<()> {
+        let context = &context.next_run();
+        let options = self.options.clone();
+
+        self.run_startup(context, &options, Batch::run_seq)?;
+        self.batches
+            .iter_mut()
+            .try_for_each(|batch| batch.run_seq(context, &options))
+    }
+
+    // Runs the startup batches which have not yet succeeded. Each batch is
+    // removed once it succeeds, such that a failed batch is run again by the
+    // next execution.
+    fn run_startup(
+        &mut self,
+        context: &Context,
+        options: &RunOptions,
+        run: fn(&mut Batch, &Context, &RunOptions) -> Result<()>,
+    ) -> Result<()> {
+        while let Some(batch) = self.startup.first_mut() {
+            run(batch, context, options)?;
+            self.startup.remove(0);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Executes the systems in parallel against an existing context
+    pub(crate) fn run_par(&mut self, context: &Context) -> Result<()> {
+        // Systems pinned to the main thread require the execution to stay on
+        // the calling thread, in which case each batch enters the pool itself
+        let pinned = self.dynamic_systems().any(|system| system.main_thread);
+
+        match self.options.pool.clone() {
+            Some(pool) if !pinned => pool.install(|| self.run_par_in_pool(context)),
+            _ => self.run_par_in_pool(context),
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    // Executes the systems in parallel on the current thread pool
+    fn run_par_in_pool(&mut self, context: &Context) -> Result<()> {
+        let context = &context.next_run();
+        let options = self.options.clone();
+
+        self.run_startup(context, &options, Batch::run_par)?;
+        self.batches
+            .iter_mut()
+            .try_for_each(|batch| batch.run_par(context, &options))
+    }
+
+    /// Get a reference to the schedule's cmd.
+    pub fn cmd(&self) -> &CommandBuffer {
+        &self.cmd
+    }
+
+    /// Get a mutable reference to the schedule's cmd.
+    pub fn cmd_mut(&mut self) -> &mut CommandBuffer {
+        &mut self.cmd
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+/// Describes which systems ran during [Schedule::execute_with_deadline]
+pub struct DeadlineReport {
+    executed: Vec<SystemName>,
+    skipped: Vec<SystemName>,
+}
+
+impl DeadlineReport {
+    /// Returns the systems of the batches which started before the deadline,
+    /// including systems skipped by a run condition
+    pub fn executed(&self) -> &[SystemName] {
+        &self.executed
+    }
+
+    /// Returns the systems of the batches which were skipped as the deadline
+    /// had passed
+    pub fn skipped(&self) -> &[SystemName] {
+        &self.skipped
+    }
+
+    /// Returns true if no systems were skipped
+    pub fn met_deadline(&self) -> bool {
+        self.skipped.is_empty()
+    }
+}
+
+#[derive(Default)]
+/// Builder for incrementally constructing a schedule.
+pub struct ScheduleBuilder {
+    nodes: Vec<Node>,
+    startup: Vec<Node>,
+    last_startup: bool,
+    options: RunOptions,
+    provided: Vec<TypeId>,
+    states: Vec<(TypeId, DynamicSystem)>,
+    strategy: Option<Arc<dyn BatchingStrategy>>,
+}
+
+impl ScheduleBuilder {
+    /// Creates a new [ScheduleBuilder]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a system to the builder
+    pub fn add_system<Args, Ret, S>(&mut self, system: S) -> &mut Self
+    where
+        S: 'static + System<Args, Ret> + Send,
+    {
+        self.add_internal(DynamicSystem::new(system));
+        self
+    }
+
+    /// Add a system which only runs on the first execution of the schedule,
+    /// before all other systems.
+    ///
+    /// Startup systems are batched separately, and the commandbuffer is
+    /// flushed after they have run. If a batch of startup systems fails, the
+    /// execution fails, and the batch is run again by the next execution.
+    pub fn add_startup_system<Args, Ret, S>(&mut self, system: S) -> &mut Self
+    where
+        S: 'static + System<Args, Ret> + Send,
+    {
+        self.startup.push(Node::System(DynamicSystem::new(system)));
+        self.last_startup = true;
+        self
+    }
+
+    /// Add a system whose accesses are declared by `access` rather than
+    /// derived from its arguments. This allows systems which hide their true
+    /// accesses, e.g; by using [AllAccess](crate::AllAccess), to be batched
+    /// with other systems.
+    ///
+    /// The declared accesses must include all data and components the system
+    /// accesses, as they are not verified.
+    pub fn add_system_with_access<Args, Ret, S>(
+        &mut self,
+        system: S,
+        access: AccessSet,
+    ) -> &mut Self
+    where
+        S: 'static + System<Args, Ret> + Send,
+    {
+        let mut system = DynamicSystem::new(system);
+        system.borrows = access.into_borrows();
+        self.add_internal(system);
+        self
+    }
+
+    /// Add a system which requires exclusive access to the [Frame].
+    ///
+    /// The system forms a serialization point: it runs alone, after all
+    /// systems added before it and before all systems added after it.
+    pub fn add_exclusive_system<Args, Ret, S>(&mut self, system: S) -> &mut Self
+    where
+        S: 'static + ExclusiveSystem<Args, Ret> + Send,
+    {
+        self.add_internal(DynamicSystem::new_exclusive(system));
+        self
+    }
+
+    /// Add an asynchronous system to the builder.
+    ///
+    /// The system holds its borrows until its future completes, and is batched
+    /// like any other system. Asynchronous systems in the same batch are
+    /// polled concurrently by [Schedule::execute_async], while the other
+    /// execution methods block on each future.
+    pub fn add_async_system<Args, S>(&mut self, system: S) -> &mut Self
+    where
+        S: 'static + AsyncSystem<Args> + Send,
+    {
+        self.add_internal(DynamicSystem::new_async(system));
+        self
+    }
+
+    /// Add a system which runs when `state` is entered. See [State].
+    pub fn on_enter<T, Args, Ret, S>(&mut self, state: T, system: S) -> &mut Self
+    where
+        T: Component + Clone + Eq,
+        S: 'static + System<Args, Ret> + Send,
+    {
+        self.add_state_transition::<T>();
+        self.add_system(system)
+            .run_if(move |current: Read<State<T>>| current.entered() == Some(&state))
+    }
+
+    /// Add a system which runs on every execution while in `state`, including
+    /// the execution which entered it. See [State].
+    pub fn on_update<T, Args, Ret, S>(&mut self, state: T, system: S) -> &mut Self
+    where
+        T: Component + Clone + Eq,
+        S: 'static + System<Args, Ret> + Send,
+    {
+        self.add_state_transition::<T>();
+        self.add_system(system)
+            .run_if(move |current: Read<State<T>>| current.current() == &state)
+    }
+
+    /// Add a system which runs when `state` is exited. See [State].
+    pub fn on_exit<T, Args, Ret, S>(&mut self, state: T, system: S) -> &mut Self
+    where
+        T: Component + Clone + Eq,
+        S: 'static + System<Args, Ret> + Send,
+    {
+        self.add_state_transition::<T>();
+        self.add_system(system)
+            .run_if(move |current: Read<State<T>>| current.exited() == Some(&state))
+    }
+
+    // Registers the transition of the state `T`, unless already registered
+    fn add_state_transition<T: Component + Clone + Eq>(&mut self) {
+        let id = TypeId::of::<T>();
+        if self.states.iter().all(|(state, _)| *state != id) {
+            self.states
+                .push((id, DynamicSystem::new(transition_system::<T>)));
+        }
+    }
+
+    /// Add a schedule which runs as a single system. The systems of `schedule`
+    /// run sequentially in order, using the union of their borrows for
+    /// batching within this schedule.
+    ///
+    /// Commands are recorded into the commandbuffer of the outer schedule, and
+    /// flushes of the inner schedule apply them.
+    pub fn add_schedule(&mut self, schedule: Schedule) -> &mut Self {
+        self.add_internal(DynamicSystem::from_schedule(schedule));
+        self
+    }
+
+    /// Add a system to the builder which is named and labeled by `label`.
+    ///
+    /// The label can be referred to by [before](Self::before) and
+    /// [after](Self::after) constraints of other systems.
+    pub fn add_system_named<L, Args, Ret, S>(&mut self, label: L, system: S) -> &mut Self
+    where
+        L: Into<SystemLabel>,
+        S: 'static + System<Args, Ret> + Send,
+    {
+        self.add_internal(DynamicSystem::new_named(label.into(), system));
+        self
+    }
+
+    /// Requires the most recently added system to run before all systems
+    /// labeled `label`, regardless of whether their borrows conflict.
+    ///
+    /// # Panics
+    /// Panics if no system has been added.
+    pub fn before<L: Into<SystemLabel>>(&mut self, label: L) -> &mut Self {
+        self.last_system().before.push(label.into());
+        self
+    }
+
+    /// Requires the most recently added system to run after all systems
+    /// labeled `label`, regardless of whether their borrows conflict.
+    ///
+    /// # Panics
+    /// Panics if no system has been added.
+    pub fn after<L: Into<SystemLabel>>(&mut self, label: L) -> &mut Self {
+        self.last_system().after.push(label.into());
+        self
+    }
+
+    /// Only runs the most recently added system when `condition` evaluates
+    /// to true. The condition is evaluated on every execution and its borrows
+    /// are accounted for when batching.
+    ///
+    /// # Panics
+    /// Panics if no system has been added.
+    pub fn run_if<Args, C>(&mut self, condition: C) -> &mut Self
+    where
+        C: 'static + Condition<Args> + Send,
+    {
+        self.last_system()
+            .add_condition(DynamicCondition::new(condition));
+        self
+    }
+
+    /// Retries the most recently added system according to `policy` when it
+    /// fails. All retries happen within the same execution of the schedule.
+    ///
+    /// # Panics
+    /// Panics if no system has been added.
+    pub fn with_retry(&mut self, policy: RetryPolicy) -> &mut Self {
+        self.last_system().retry = policy;
+        self
+    }
+
+    /// Sets the priority of the most recently added system. Systems with a
+    /// higher priority are launched first within their batch. The default
+    /// priority is 0.
+    ///
+    /// This is a hint and does not affect the order of systems with
+    /// conflicting borrows, which always run in the order they were added.
+    ///
+    /// Priority only orders the systems within a batch, and does not move
+    /// systems between batches. As the parallel executor hands the systems of
+    /// a batch to the thread pool without any ordering guarantee, the launch
+    /// order is only guaranteed when batches run sequentially, e.g. by
+    /// [Schedule::execute_seq] or [Schedule::execute_deterministic]. To keep a
+    /// long running system from delaying a batch in parallel executions, use
+    /// the [LatencyOptimized](crate::strategy::LatencyOptimized) strategy
+    /// instead.
+    ///
+    /// # Panics
+    /// Panics if no system has been added.
+    pub fn priority(&mut self, priority: i32) -> &mut Self {
+        self.last_system().priority = priority;
+        self
+    }
+
+    /// Sets a timeout for the most recently added system.
+    ///
+    /// Asynchronous systems which are still pending when polled after the
+    /// timeout are cancelled, and fail with [Error::SystemTimeout]. Blocking
+    /// systems can not be interrupted, but each execution exceeding the
+    /// timeout is counted by [SystemTiming::overruns].
+    ///
+    /// # Panics
+    /// Panics if no system has been added.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.last_system().timeout = Some(timeout);
+        self
+    }
+
+    /// Pins the most recently added system to the thread which executes the
+    /// schedule, e.g. for systems using windowing or graphics contexts. Other
+    /// systems of the same batch still run in parallel on the thread pool.
+    ///
+    /// Pinning is honored by [Schedule::execute] and the sequential
+    /// executors, but not by [Schedule::execute_pipelined].
+    ///
+    /// # Panics
+    /// Panics if no system has been added.
+    pub fn on_main_thread(&mut self) -> &mut Self {
+        self.last_system().main_thread = true;
+        self
+    }
+
+    /// Declares the queries the most recently added system performs on its
+    /// subworlds, e.g; `declare_queries::<(&Position, &mut Velocity)>()`.
+    ///
+    /// The components of the queries must be accessible by the system, which
+    /// is checked when the schedule is built rather than when the query is
+    /// executed. Several queries can be declared together as a tuple.
+    ///
+    /// # Panics
+    /// Panics if no system has been added.
+    pub fn declare_queries<Q: Query>(&mut self) -> &mut Self {
+        self.last_system()
+            .queries
+            .push((type_name::<Q>(), Q::borrows()));
+        self
+    }
+
+    /// Only runs the most recently added system when at least `interval` has
+    /// passed since it last ran. The system runs on the first execution.
+    ///
+    /// Like other run conditions, this is only evaluated when the conditions
+    /// added before it are met.
+    ///
+    /// # Panics
+    /// Panics if no system has been added.
+    pub fn every(&mut self, interval: Duration) -> &mut Self {
+        let mut last: Option<Instant> = None;
+
+        self.run_if(move || {
+            let now = Instant::now();
+            match last {
+                Some(last) if now.duration_since(last) < interval => false,
+                _ => {
+                    last = Some(now);
+                    true
+                }
+            }
+        })
+    }
+
+    /// Only runs the most recently added system when the data of type `T` has
+    /// changed since the system last ran. The system runs on the first
+    /// execution.
+    ///
+    /// A change is any mutable access to `T` through a [Write] or `&mut T`
+    /// borrow of a system in this schedule, whether or not the value was
+    /// modified. Changes made outside of the schedule are not detected.
+    ///
+    /// This is evaluated after the other conditions of the system, and a
+    /// change is only consumed when the system runs. Watching several types
+    /// runs the system when all of them have changed.
+    ///
+    /// # Panics
+    /// Panics if no system has been added.
+    pub fn run_if_changed<T: Component>(&mut self) -> &mut Self {
+        let system = self.last_system();
+        // Ordered after the systems writing `T`
+        system
+            .borrows
+            .extend(<Read<T> as ComponentBorrow>::borrows());
+        system.watched.push((TypeId::of::<T>(), None));
+        self
+    }
+
+    /// Only runs the most recently added system on every `n`th execution,
+    /// starting with the first.
+    ///
+    /// Like other run conditions, this is only evaluated when the conditions
+    /// added before it are met.
+    ///
+    /// # Panics
+    /// Panics if no system has been added, or if `n` is zero.
+    pub fn every_nth(&mut self, n: usize) -> &mut Self {
+        assert!(n > 0, "Systems can not run every 0th execution");
+        let mut count = 0;
+
+        self.run_if(move || {
+            let run = count % n == 0;
+            count += 1;
+            run
+        })
+    }
+
+    pub(crate) fn add_internal(&mut self, system: DynamicSystem) {
+        self.nodes.push(Node::System(system));
+        self.last_startup = false;
+    }
+
+    fn last_system(&mut self) -> &mut DynamicSystem {
+        let nodes = if self.last_startup {
+            &mut self.startup
+        } else {
+            &mut self.nodes
+        };
+
+        nodes
+            .iter_mut()
+            .rev()
+            .find_map(|node| match node {
+                Node::System(system) => Some(system),
+                Node::Barrier => None,
+            })
+            .expect("No system has been added to the builder")
+    }
+
+    /// Add all systems of `set` to the builder, leaving `set` empty.
+    pub fn add_set(&mut self, set: &mut SystemSet) -> &mut Self {
+        std::mem::take(set)
+            .into_systems()
+            .for_each(|system| self.add_internal(system));
+        self
+    }
+
+    /// Append all system from `other` into self, leaving `other` empty.
+    /// This allows constructing smaller schedules in different modules and then
+    /// joining them together. Work will be paralellized between the two
+    /// schedules.
+    pub fn append(&mut self, other: &mut ScheduleBuilder) -> &mut Self {
+        self.nodes.append(&mut other.nodes);
+        self.startup.append(&mut other.startup);
+        for (id, transition) in other.states.drain(..) {
+            if self.states.iter().all(|(state, _)| *state != id) {
+                self.states.push((id, transition));
+            }
+        }
+        self.last_startup = false;
+        self
+    }
+
+    /// Appends all systems from `other`, consuming both builders. Labels and
+    /// ordering constraints of the systems are kept, and the options of `self`
+    /// take precedence.
+    ///
+    /// This is convenient for composing builders returned by functions, e.g;
+    /// from plugins.
+    pub fn merge(mut self, mut other: ScheduleBuilder) -> ScheduleBuilder {
+        self.append(&mut other);
+        self
+    }
+
+    /// Inserts a barrier that will divide the schedule pararell execution in
+    /// two dependant halves.
+    ///
+    /// Usually this is not required, as the borrows of the system automatically
+    /// creates dependencies, but sometimes a manual dependency is needed for things
+    /// such as interior mutability or channels.
+    pub fn barrier(&mut self) -> &mut Self {
+        self.nodes.push(Node::Barrier);
+        self
+    }
+
+    /// Flush the commandbuffer and apply the commands to the world.
+    ///
+    /// The flush acts as a [barrier](Self::barrier); all commands recorded by
+    /// systems added before the flush are applied before any system added
+    /// after it runs.
+    pub fn flush(&mut self) -> &mut Self {
+        self.nodes.push(Node::Barrier);
+        self.add_internal(DynamicSystem::flush());
+        self.nodes.push(Node::Barrier);
+        self
+    }
+
+    /// Sets how the schedule proceeds when a system fails. The default is
+    /// [ErrorPolicy::FailFast].
+    pub fn error_policy(&mut self, policy: ErrorPolicy) -> &mut Self {
+        self.options.error_policy = policy;
+        self
+    }
+
+    /// Adds a hook which is invoked before and after every system that runs.
+    /// Hooks are invoked in the order they were added, on the thread running
+    /// the system.
+    ///
+    /// This allows logging, profiling or checking assertions around systems
+    /// without modifying them.
+    pub fn with_hook<F>(&mut self, hook: F) -> &mut Self
+    where
+        F: Fn(&SystemInfo, HookPhase) + Send + Sync + 'static,
+    {
+        Arc::make_mut(&mut self.options.hooks).push(Arc::new(hook));
+        self
+    }
+
+    /// Catches panics of systems and returns them as [Error::SystemPanic]
+    /// rather than unwinding through the schedule. Panics are not caught by
+    /// default.
+    ///
+    /// Data borrowed by a panicking system may be left in an inconsistent
+    /// state.
+    pub fn catch_panics(&mut self, catch: bool) -> &mut Self {
+        self.options.catch_panics = catch;
+        self
+    }
+
+    /// Fails the execution with [Error::TrackedAccessConflict] when a
+    /// [TrackedAccess](crate::TrackedAccess) subworld accesses components which
+    /// conflict with another system of the same batch, rather than emitting a
+    /// warning. Conflicts are warnings by default, which are only emitted with
+    /// the `tracing` feature.
+    ///
+    /// The accesses are only known once the systems have run, so the conflict
+    /// is detected after the batch, and its systems are not undone.
+    pub fn strict_access(&mut self, strict: bool) -> &mut Self {
+        self.options.strict_access = strict;
+        self
+    }
+
+    /// Divides the systems into batches using `strategy` rather than
+    /// [Greedy](crate::strategy::Greedy) batching. The strategy is also used
+    /// when the built schedule is modified.
+    pub fn with_strategy<S: 'static + BatchingStrategy>(&mut self, strategy: S) -> &mut Self {
+        self.strategy = Some(Arc::new(strategy));
+        self
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Executes the schedule in parallel on `pool` rather than the global
+    /// rayon thread pool.
+    pub fn thread_pool(&mut self, pool: Arc<ThreadPool>) -> &mut Self {
+        self.options.pool = Some(pool);
+        self
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Executes the schedule in parallel on a dedicated thread pool with
+    /// `num_threads` threads, rather than the global rayon thread pool.
+    ///
+    /// # Panics
+    /// Panics if the thread pool could not be created.
+    pub fn num_threads(&mut self, num_threads: usize) -> &mut Self {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .expect("Failed to create thread pool");
+
+        self.thread_pool(Arc::new(pool))
+    }
+
+    /// Declares that data of type `T` is provided to the execution of the
+    /// schedule. If any data is declared, [validation](Self::validate) reports
+    /// systems which require data that is not declared.
+    ///
+    /// The commandbuffer is always provided.
+    pub fn provides<T: 'static>(&mut self) -> &mut Self {
+        self.provided.push(TypeId::of::<T>());
+        self
+    }
+
+    /// Checks the schedule for duplicate system names, ordering cycles,
+    /// ordering constraints referring to unknown labels, and missing data.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut provided = self.provided.clone();
+        if !provided.is_empty() {
+            provided.push(TypeId::of::<CommandBuffer>());
+        }
+
+        validate(&self.nodes, &self.startup, &provided)
+    }
+
+    /// Validates and builds the schedule. The builder is left untouched if
+    /// validation fails.
+    pub fn try_build(&mut self) -> std::result::Result<Schedule, Vec<ValidationError>> {
+        let errors = self.validate();
+        if errors.is_empty() {
+            Ok(self.build())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// FLushes the commandbuffer and builds the schedule.
+    ///
+    /// # Panics
+    /// Panics if the ordering constraints of the systems form a cycle, or if a
+    /// query declared by [declare_queries](Self::declare_queries) is not
+    /// accessible by its system.
+    pub fn build(&mut self) -> Schedule {
+        if let Some(error) = validate_queries(&self.nodes, &self.startup).first() {
+            panic!("{error}");
+        }
+
+        let mut builder = std::mem::take(self);
+
+        // The transitions conflict with all systems of their state, and thus
+        // run before them
+        let nodes = builder
+            .states
+            .into_iter()
+            .map(|(_, transition)| Node::System(transition))
+            .chain(builder.nodes)
+            .collect();
+
+        let strategy = builder.strategy.unwrap_or_else(|| Arc::new(Greedy));
+
+        let mut schedule = Schedule::from_nodes(nodes, strategy.clone());
+        schedule.options = builder.options;
+
+        if !builder.startup.is_empty() {
+            builder.startup.push(Node::Barrier);
+            builder.startup.push(Node::System(DynamicSystem::flush()));
+            schedule.startup = plan(builder.startup, &*strategy);
+        }
+
+        schedule
     }
 }
 
 // Flushes the commandbuffer
-fn flush_system(mut frame: MaybeWrite<Frame>, mut cmd: Write<CommandBuffer>) -> Result<()> {
+fn flush_system(
+    mut frame: MaybeWrite<Frame>,
+    mut cmd: Write<CommandBuffer>,
+    deferred: Deferred,
+    mut resources: MaybeWrite<Resources>,
+) -> Result<()> {
+    let mut buffers = deferred.take_buffers();
+    let mut errors = Vec::new();
+
     if let Some(world) = frame.option_mut() {
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Applying commandbuffer");
+
         cmd.execute(world);
+
+        for buffer in &mut buffers {
+            if let Err(failed) = buffer.try_execute(world) {
+                errors.push(Error::CommandsFailed(failed));
+            }
+        }
+
+        let failed = deferred.apply(world);
+        if !failed.is_empty() {
+            errors.push(Error::CommandsFailed(failed));
+        }
+    }
+
+    // The world may be one of the resources
+    drop(frame);
+
+    match resources.option_mut() {
+        Some(resources) => {
+            cmd.execute_resources(resources);
+            buffers
+                .iter_mut()
+                .for_each(|buffer| buffer.execute_resources(resources));
+        }
+        // The commands are discarded, rather than piling up
+        None => {
+            let discarded = buffers
+                .iter_mut()
+                .fold(cmd.discard_resources(), |discarded, buffer| {
+                    buffer.discard_resources() | discarded
+                });
+
+            if discarded {
+                errors.push(Error::NoResources);
+            }
+        }
     }
-    Ok(())
+
+    deferred.recycle(buffers);
+    collect_errors(errors)
 }
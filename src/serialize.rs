@@ -0,0 +1,175 @@
+use std::{any::TypeId, ops::Deref};
+
+use moss_hecs::{Component, EntityRef, Frame};
+use serde::{de::DeserializeOwned, ser::SerializeMap, Serialize, Serializer};
+
+use crate::{
+    access::Guard, borrow::ComponentBorrow, command_stream::StreamComponent, CommandBuffer,
+    CommandEntity, SubWorldRaw,
+};
+
+pub(crate) type DeserializeFn = for<'de> fn(
+    &mut dyn erased_serde::Deserializer<'de>,
+) -> Result<StreamComponent, erased_serde::Error>;
+
+pub(crate) struct Entry {
+    pub(crate) id: TypeId,
+    pub(crate) name: &'static str,
+    has: fn(&EntityRef) -> bool,
+    get: for<'a> fn(&EntityRef<'a>) -> Option<Box<dyn erased_serde::Serialize + 'a>>,
+    pub(crate) remove: fn(&mut CommandBuffer, CommandEntity),
+    pub(crate) deserialize: Option<DeserializeFn>,
+}
+
+#[derive(Default)]
+/// Names the component types which can be serialized from a subworld, see
+/// [SubWorldRaw::serialize], or serialized and deserialized as part of a
+/// [CommandStream](crate::CommandStream).
+pub struct ComponentRegistry {
+    entries: Vec<Entry>,
+}
+
+impl ComponentRegistry {
+    /// Creates a new empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the component `C` to be serialized by `name`, replacing any
+    /// previous registration of `C`.
+    pub fn register<C: Component + Serialize>(&mut self, name: &'static str) -> &mut Self {
+        self.insert(Entry {
+            id: TypeId::of::<C>(),
+            name,
+            has: has::<C>,
+            get: get::<C>,
+            remove: remove::<C>,
+            deserialize: None,
+        })
+    }
+
+    /// Registers the component `C` to be serialized by `name` like
+    /// [register](Self::register), and to be deserialized by `name` as part
+    /// of a [CommandStream](crate::CommandStream).
+    pub fn register_deserialize<C: Component + Serialize + DeserializeOwned>(
+        &mut self,
+        name: &'static str,
+    ) -> &mut Self {
+        self.insert(Entry {
+            id: TypeId::of::<C>(),
+            name,
+            has: has::<C>,
+            get: get::<C>,
+            remove: remove::<C>,
+            deserialize: Some(deserialize::<C>),
+        })
+    }
+
+    fn insert(&mut self, entry: Entry) -> &mut Self {
+        match self.entries.iter_mut().find(|other| other.id == entry.id) {
+            Some(other) => *other = entry,
+            None => self.entries.push(entry),
+        }
+
+        self
+    }
+
+    pub(crate) fn get(&self, id: TypeId) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    pub(crate) fn get_by_name(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// Returns true if the component `C` is registered
+    pub fn contains<C: Component>(&self) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.id == TypeId::of::<C>())
+    }
+}
+
+impl<A: Deref<Target = Frame>, T: ComponentBorrow> SubWorldRaw<A, T> {
+    /// Serializes the registered components the subworld may read, as a map
+    /// from entity bits to a map from component name to value. Entities
+    /// without any such component are skipped.
+    ///
+    /// # Panics
+    /// Panics if a serialized component is already borrowed mutably.
+    pub fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+        registry: &ComponentRegistry,
+    ) -> Result<S::Ok, S::Error> {
+        let components = registry
+            .entries
+            .iter()
+            .filter(|entry| T::has_dynamic(entry.id, false))
+            .collect::<Vec<_>>();
+
+        let mut map = serializer.serialize_map(None)?;
+
+        for entity in self.frame.iter() {
+            if !components.iter().any(|entry| (entry.has)(&entity)) {
+                continue;
+            }
+
+            map.serialize_entry(
+                &entity.entity().to_bits().get(),
+                &EntityComponents {
+                    entity,
+                    components: &components,
+                },
+            )?;
+        }
+
+        map.end()
+    }
+}
+
+struct EntityComponents<'a> {
+    entity: EntityRef<'a>,
+    components: &'a [&'a Entry],
+}
+
+impl<'a> Serialize for EntityComponents<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+
+        for entry in self.components {
+            if let Some(value) = (entry.get)(&self.entity) {
+                map.serialize_entry(entry.name, &*value)?;
+            }
+        }
+
+        map.end()
+    }
+}
+
+impl<'a, C: Serialize> Serialize for Guard<'a, C> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (*self.0).serialize(serializer)
+    }
+}
+
+fn has<C: Component>(entity: &EntityRef) -> bool {
+    entity.has::<C>()
+}
+
+fn get<'a, C: Component + Serialize>(
+    entity: &EntityRef<'a>,
+) -> Option<Box<dyn erased_serde::Serialize + 'a>> {
+    let component = entity.get::<&C>()?;
+    Some(Box::new(Guard(component)))
+}
+
+fn remove<C: Component>(cmd: &mut CommandBuffer, entity: CommandEntity) {
+    cmd.remove_one::<C>(entity)
+}
+
+fn deserialize<C: Component + Serialize + DeserializeOwned>(
+    deserializer: &mut dyn erased_serde::Deserializer<'_>,
+) -> Result<StreamComponent, erased_serde::Error> {
+    erased_serde::deserialize::<C>(deserializer).map(StreamComponent::new)
+}
@@ -0,0 +1,77 @@
+use std::mem;
+
+use crate::Write;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A resource which holds the current value of a state machine, such as the
+/// state of a game.
+///
+/// Transitions requested by [set](Self::set) are applied at the start of the
+/// next execution of a schedule with systems added by
+/// [on_enter](crate::ScheduleBuilder::on_enter),
+/// [on_update](crate::ScheduleBuilder::on_update) or
+/// [on_exit](crate::ScheduleBuilder::on_exit). The initial state is entered on
+/// the first execution.
+pub struct State<T> {
+    current: T,
+    next: Option<T>,
+    entered: Option<T>,
+    exited: Option<T>,
+    started: bool,
+}
+
+impl<T: Clone + Eq> State<T> {
+    /// Creates a new state machine in the `initial` state
+    pub fn new(initial: T) -> Self {
+        Self {
+            current: initial,
+            next: None,
+            entered: None,
+            exited: None,
+            started: false,
+        }
+    }
+
+    /// Returns the current state
+    pub fn current(&self) -> &T {
+        &self.current
+    }
+
+    /// Requests a transition to `next` at the start of the next execution.
+    /// Transitioning to the current state does nothing.
+    pub fn set(&mut self, next: T) {
+        self.next = Some(next);
+    }
+
+    /// Returns the state which was entered during the current execution
+    pub fn entered(&self) -> Option<&T> {
+        self.entered.as_ref()
+    }
+
+    /// Returns the state which was exited during the current execution
+    pub fn exited(&self) -> Option<&T> {
+        self.exited.as_ref()
+    }
+
+    fn apply(&mut self) {
+        self.entered = None;
+        self.exited = None;
+
+        if !self.started {
+            self.started = true;
+            self.entered = Some(self.current.clone());
+        }
+
+        if let Some(next) = self.next.take() {
+            if next != self.current {
+                self.entered = Some(next.clone());
+                self.exited = Some(mem::replace(&mut self.current, next));
+            }
+        }
+    }
+}
+
+/// Applies the requested transition of the state
+pub(crate) fn transition_system<T: Clone + Eq + 'static>(mut state: Write<State<T>>) {
+    state.apply()
+}
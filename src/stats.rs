@@ -0,0 +1,168 @@
+use std::{any::TypeId, ops::Deref};
+
+use moss_hecs::Frame;
+
+use crate::{borrow::ComponentBorrow, ComponentAccess, SubWorldRaw};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Entity count and memory usage of an archetype, see [WorldStats].
+pub struct ArchetypeStats {
+    components: Vec<TypeId>,
+    len: u32,
+    memory: usize,
+}
+
+impl ArchetypeStats {
+    /// Returns the types of the components of the archetype which the
+    /// subworld can access, in ascending order
+    pub fn components(&self) -> &[TypeId] {
+        &self.components
+    }
+
+    /// Returns the number of entities in the archetype
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Returns true if the archetype contains no entities
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of bytes used by the accessible components of the
+    /// entities. Unused capacity is not included.
+    pub fn memory(&self) -> usize {
+        self.memory
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Snapshot of the archetypes reachable by a subworld, see
+/// [SubWorldRaw::stats].
+pub struct WorldStats {
+    archetypes: Vec<ArchetypeStats>,
+}
+
+impl WorldStats {
+    /// Returns the statistics of each archetype which contains any component
+    /// the subworld can access
+    pub fn archetypes(&self) -> &[ArchetypeStats] {
+        &self.archetypes
+    }
+
+    /// Returns the number of entities in all archetypes
+    pub fn entities(&self) -> u32 {
+        self.archetypes.iter().map(ArchetypeStats::len).sum()
+    }
+
+    /// Returns the memory used by all archetypes, see
+    /// [ArchetypeStats::memory]
+    pub fn memory(&self) -> usize {
+        self.archetypes.iter().map(ArchetypeStats::memory).sum()
+    }
+
+    /// Compares the entity counts of the archetypes to an earlier snapshot,
+    /// e.g. one kept in a [Local](crate::Local) by a diagnostics system.
+    ///
+    /// Archetypes are matched by their accessible components. Entities which
+    /// were added and removed again between the snapshots are not counted.
+    pub fn churn_since(&self, previous: &WorldStats) -> Churn {
+        let len = |stats: &WorldStats, components: &[TypeId]| {
+            stats
+                .archetypes
+                .iter()
+                .filter(|archetype| archetype.components == components)
+                .map(ArchetypeStats::len)
+                .sum::<u32>()
+        };
+
+        let mut churn = Churn::default();
+        let mut seen: Vec<&[TypeId]> = Vec::new();
+
+        for archetype in self.archetypes.iter().chain(&previous.archetypes) {
+            let components = &archetype.components[..];
+            if seen.contains(&components) {
+                continue;
+            }
+            seen.push(components);
+
+            let (now, before) = (len(self, components), len(previous, components));
+            churn.added += now.saturating_sub(before);
+            churn.removed += before.saturating_sub(now);
+        }
+
+        churn
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Change of the entity counts between two [WorldStats]
+pub struct Churn {
+    added: u32,
+    removed: u32,
+}
+
+impl Churn {
+    /// Returns the number of entities added to archetypes, including
+    /// entities which moved from another archetype
+    pub fn added(&self) -> u32 {
+        self.added
+    }
+
+    /// Returns the number of entities removed from archetypes, including
+    /// entities which moved to another archetype
+    pub fn removed(&self) -> u32 {
+        self.removed
+    }
+}
+
+impl<A: Deref<Target = Frame>, T: ComponentBorrow + ComponentAccess> SubWorldRaw<A, T> {
+    /// Returns the entity count and memory usage of each archetype which
+    /// contains any component the subworld can access, e.g. to surface the
+    /// health of the world in a diagnostics overlay. Does not borrow any
+    /// components.
+    ///
+    /// # Example
+    /// ```rust
+    /// use moss_hecs::Frame;
+    /// use moss_hecs_schedule::*;
+    ///
+    /// let mut frame = Frame::default();
+    /// frame.spawn((1_u32,));
+    ///
+    /// let previous = SubWorldRef::<&u32>::new(&frame).stats();
+    ///
+    /// frame.spawn((2_u32,));
+    /// let stats = SubWorldRef::<&u32>::new(&frame).stats();
+    ///
+    /// assert_eq!(stats.entities(), 2);
+    /// assert_eq!(stats.memory(), 8);
+    /// assert_eq!(stats.churn_since(&previous).added(), 1);
+    /// ```
+    pub fn stats(&self) -> WorldStats {
+        let mut components = Vec::new();
+        T::for_each_component(&mut |info| components.push(info));
+
+        let archetypes = self
+            .archetypes()
+            .map(|archetype| {
+                let mut types: Vec<TypeId> = archetype.accessible_types().collect();
+                types.sort_unstable();
+
+                let size: usize = types
+                    .iter()
+                    .filter_map(|&id| components.iter().find(|info| info.id() == id))
+                    .map(|info| info.size())
+                    .sum();
+
+                ArchetypeStats {
+                    len: archetype.len(),
+                    memory: archetype.len() as usize * size,
+                    components: types,
+                }
+            })
+            .collect();
+
+        WorldStats { archetypes }
+    }
+}
@@ -0,0 +1,151 @@
+//! Strategies which decide how the systems of a schedule are divided into
+//! batches.
+use std::{cmp::Reverse, time::Duration};
+
+use crate::DynamicSystem;
+
+/// Decides which batch each system of a schedule runs in.
+///
+/// Systems are given in an order which satisfies all ordering constraints,
+/// together with the indices of the earlier systems each system depends on
+/// due to conflicting borrows, ordering constraints or barriers. A system must
+/// be placed in a later batch than all its dependencies.
+///
+/// Strategies are set by
+/// [ScheduleBuilder::with_strategy](crate::ScheduleBuilder::with_strategy).
+pub trait BatchingStrategy: Send + Sync {
+    /// Returns the index of the batch of each system
+    fn assign(&self, systems: &[DynamicSystem], dependencies: &[Vec<usize>]) -> Vec<usize>;
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// Places each system in the most recent batch unless it depends on a system
+/// in it, in which case a new batch is started. This is the default.
+///
+/// Systems never run before systems added earlier in another batch, which
+/// keeps the order of execution close to the order the systems were added.
+pub struct Greedy;
+
+impl BatchingStrategy for Greedy {
+    fn assign(&self, _: &[DynamicSystem], dependencies: &[Vec<usize>]) -> Vec<usize> {
+        let mut batches: Vec<usize> = Vec::with_capacity(dependencies.len());
+        let mut current = 0;
+
+        for deps in dependencies {
+            if deps.iter().any(|&j| batches[j] == current) {
+                current += 1;
+            }
+
+            batches.push(current);
+        }
+
+        batches
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// Places each system in the earliest batch after all its dependencies, which
+/// results in the least number of batches possible.
+///
+/// Independent systems added after a long chain of conflicting systems run in
+/// the first batch, rather than alongside the end of the chain.
+pub struct MinimalBatches;
+
+impl BatchingStrategy for MinimalBatches {
+    fn assign(&self, _: &[DynamicSystem], dependencies: &[Vec<usize>]) -> Vec<usize> {
+        earliest(dependencies, &vec![None; dependencies.len()])
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+/// Uses the least number of batches possible, and places the systems such that
+/// the durations of the batches are as short as possible, where the duration
+/// of a batch is that of its longest system.
+///
+/// The durations of the systems are the averages of their measured
+/// [timings](crate::Schedule::timings). Systems without measurements are
+/// assumed to take no time, in which case this behaves like
+/// [MinimalBatches]. See [Schedule::rebalance](crate::Schedule::rebalance).
+pub struct LatencyOptimized;
+
+impl BatchingStrategy for LatencyOptimized {
+    fn assign(&self, systems: &[DynamicSystem], dependencies: &[Vec<usize>]) -> Vec<usize> {
+        let costs: Vec<_> = systems
+            .iter()
+            .map(|system| system.timing().average().unwrap_or_default())
+            .collect();
+
+        balanced(dependencies, &costs)
+    }
+}
+
+// Returns the earliest possible batch of each system, given the systems which
+// are already placed
+fn earliest(deps: &[Vec<usize>], placed: &[Option<usize>]) -> Vec<usize> {
+    let mut lo = vec![0; deps.len()];
+    for i in 0..deps.len() {
+        lo[i] = placed[i].unwrap_or_else(|| deps[i].iter().map(|&j| lo[j] + 1).max().unwrap_or(0));
+    }
+
+    lo
+}
+
+// Returns the latest possible batch of each system, given the systems which
+// are already placed and the number of batches
+fn latest(deps: &[Vec<usize>], placed: &[Option<usize>], count: usize) -> Vec<usize> {
+    let mut hi = vec![count.saturating_sub(1); deps.len()];
+    for i in (0..deps.len()).rev() {
+        if let Some(batch) = placed[i] {
+            hi[i] = batch;
+        }
+
+        for &j in &deps[i] {
+            hi[j] = hi[j].min(hi[i].saturating_sub(1));
+        }
+    }
+
+    hi
+}
+
+/// Places each system in the batch with the least increase in duration while
+/// keeping the minimal number of batches.
+///
+/// Systems are placed from the longest to the shortest, and each placement
+/// narrows the batches available to the systems depending on it.
+fn balanced(deps: &[Vec<usize>], costs: &[Duration]) -> Vec<usize> {
+    let n = deps.len();
+    let mut placed: Vec<Option<usize>> = vec![None; n];
+
+    let count = earliest(deps, &placed)
+        .into_iter()
+        .max()
+        .map_or(0, |batch| batch + 1);
+
+    let mut longest = vec![Duration::ZERO; count];
+    let mut total = vec![Duration::ZERO; count];
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by_key(|&i| Reverse(costs[i]));
+
+    for i in order {
+        let lo = earliest(deps, &placed);
+        let hi = latest(deps, &placed, count);
+        let cost = costs[i];
+
+        let batch = (lo[i]..=hi[i])
+            .min_by_key(|&batch| {
+                let increase = longest[batch].max(cost) - longest[batch];
+                (increase, total[batch], batch)
+            })
+            .expect("Every system has at least one possible batch");
+
+        longest[batch] = longest[batch].max(cost);
+        total[batch] += cost;
+        placed[i] = Some(batch);
+    }
+
+    placed
+        .into_iter()
+        .map(|batch| batch.expect("All systems are placed"))
+        .collect()
+}
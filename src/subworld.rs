@@ -1,22 +1,135 @@
 use atomic_refcell::AtomicRef;
-use std::{any::type_name, marker::PhantomData, ops::Deref};
+use std::{any::type_name, marker::PhantomData, ops::Deref, sync::Arc};
 
-use crate::{access::*, borrow::ComponentBorrow, Error, Result};
+use crate::{
+    access::*, borrow::ComponentBorrow, deferred::DeferredQueue, tracking::AccessLog, Error, Result,
+};
 
-use crate::{GenericWorld, QueryOne};
-use moss_hecs::{Component, Entity, Frame, Query, QueryBorrow};
+#[cfg(feature = "parallel")]
+use crate::ParQueryBorrow;
+use crate::{
+    ArchetypeInfo, CloneComponents, Columns, CommandBuffer, CommandError, GenericWorld,
+    PreparedQuery, QueryChunk, QueryOne, QueryView, SortedQuery, SubEntityRef,
+};
+use moss_hecs::{
+    Component, DynamicBundle, Entity, Frame, PreparedQueryBorrow, Query, QueryBorrow, QueryShared,
+};
+#[cfg(feature = "parallel")]
+use rayon::iter::ParallelIterator;
+
+/// Query filters which can be used in subworld queries, e.g;
+/// `subworld.query::<With<&Position, &Velocity>>()`.
+///
+/// The filtered components are not borrowed, and thus do not need to be
+/// accessible by the subworld.
+pub use moss_hecs::{Satisfies, With, Without};
+
+/// Query which matches entities with either or both of `L` and `R`, e.g;
+/// `SubWorld<Or<&Position, &Velocity>>`.
+///
+/// As which of the two is accessed is only known while querying, a subworld
+/// of `Or<L, R>` borrows both `L` and `R` when scheduled, and is batched
+/// exactly like a subworld of `(L, R)`. Accesses are scheduled per component
+/// type rather than per entity, so any entity with `L` may be read, and the
+/// subworld can not share a batch with a writer of `L`, even if that writer
+/// only touches entities without `R`.
+pub use moss_hecs::Or;
+
+/// Excludes the entities with the component `C` from a subworld, without
+/// borrowing it, e.g; `SubWorld<(&Position, Exclude<Frozen>)>`. The exclusion
+/// is applied by [SubWorldRaw::query_filtered].
+///
+/// Other queries, such as [SubWorldRaw::query] and [SubWorldRaw::query_one],
+/// still yield excluded entities. Their items are typed by the query alone,
+/// so applying the filter would change the type of every subworld query and
+/// require all subworld accesses to implement
+/// [SubWorldFilter](crate::SubWorldFilter). The schedule does not rely on the
+/// exclusion either, so unfiltered queries remain sound.
+pub type Exclude<C> = Without<(), &'static C>;
 
 /// Type alias for a subworld referencing the world by an [atomic_refcell::AtomicRef]. Most
-/// common for schedules
-pub type SubWorld<'a, T> = SubWorldRaw<AtomicRef<'a, Frame>, T>;
+/// common for schedules.
+///
+/// The subworld accesses the [Frame] of the schedule unless another [World] is
+/// selected by `W`, e.g; `SubWorld<&Mesh, Of<RenderWorld>>`.
+pub type SubWorld<'a, T, W = Of<Frame>> = SubWorldRaw<<W as WorldOf<'a>>::Ref, T>;
 /// Type alias for a subworld referencing the world by a [std::cell::Ref]
 pub type SubWorldRefCell<'a, T> = SubWorldRaw<std::cell::Ref<'a, Frame>, T>;
+/// Type alias for a subworld referencing the world by a
+/// [std::sync::RwLockReadGuard].
+///
+/// As the guard can not be cloned, the subworld is split by converting it
+/// into a [SubWorldRef] with [GenericWorld::to_ref] or [From].
+pub type SubWorldRwLock<'a, T> = SubWorldRaw<std::sync::RwLockReadGuard<'a, Frame>, T>;
+#[cfg(feature = "parking_lot")]
+/// Type alias for a subworld referencing the world by a
+/// [parking_lot::RwLockReadGuard]. The guard is cloned by a recursive read,
+/// which allows splitting the subworld.
+pub type SubWorldParkingLot<'a, T> = SubWorldRaw<parking_lot::RwLockReadGuard<'a, Frame>, T>;
 /// Type alias for a subworld referencing the world by a reference
 pub type SubWorldRef<'a, T> = SubWorldRaw<&'a Frame, T>;
 
+/// Type alias for a subworld which shares ownership of the world, and can thus
+/// be held beyond the borrow of a schedule, e.g. by worker threads or
+/// background schedules. The frame can be recovered with
+/// [into_inner](SubWorldRaw::into_inner) and [Arc::try_unwrap].
+pub type SubWorldOwned<T> = SubWorldRaw<Arc<Frame>, T>;
+
 /// An empty subworld, can not access any components
 pub type EmptyWorld<'a> = SubWorldRef<'a, ()>;
 
+/// A [Frame] which is passed to a schedule in addition to, and distinguished
+/// from, the main frame. This is usually implemented for a newtype, e.g;
+/// `struct RenderWorld(Frame)`.
+///
+/// Each world is a separate resource, so systems accessing different worlds
+/// never conflict. The commandbuffer of a schedule is only applied to the
+/// main frame.
+pub trait World: Component {
+    /// Returns the frame of the world
+    fn frame(&self) -> &Frame;
+}
+
+/// Selects the world a [SubWorld] accesses
+pub struct Of<W>(PhantomData<W>);
+
+/// Maps a world selected by [Of] to the reference held by a [SubWorld]
+pub trait WorldOf<'a> {
+    /// The reference to the frame
+    type Ref: Deref<Target = Frame>;
+}
+
+impl<'a> WorldOf<'a> for Of<Frame> {
+    type Ref = AtomicRef<'a, Frame>;
+}
+
+impl<'a, W: World> WorldOf<'a> for Of<W> {
+    type Ref = WorldRef<'a, W>;
+}
+
+/// A reference to the frame of the [World] `W`
+pub struct WorldRef<'a, W> {
+    pub(crate) frame: AtomicRef<'a, Frame>,
+    marker: PhantomData<W>,
+}
+
+impl<'a, W> WorldRef<'a, W> {
+    pub(crate) fn new(frame: AtomicRef<'a, Frame>) -> Self {
+        Self {
+            frame,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, W> Deref for WorldRef<'a, W> {
+    type Target = Frame;
+
+    fn deref(&self) -> &Self::Target {
+        &self.frame
+    }
+}
+
 /// Represents a borrow of the world which can only access a subset of
 /// components (unless [`AllAccess`] is used).
 ///
@@ -28,6 +141,11 @@ pub type EmptyWorld<'a> = SubWorldRef<'a, ()>;
 /// the one used by [Schedule](crate::Schedule).
 pub struct SubWorldRaw<A, T> {
     pub(crate) frame: A,
+    deferred: Option<Arc<DeferredQueue>>,
+    log: Option<Arc<AccessLog>>,
+    // Whether the subworld may be widened beyond its access, which is only
+    // the case when it is not borrowed from a schedule
+    widen: bool,
     marker: PhantomData<T>,
 }
 
@@ -37,25 +155,171 @@ impl<A, T> SubWorldRaw<A, T> {
     pub fn new(frame: A) -> Self {
         Self {
             frame,
+            deferred: None,
+            log: None,
+            widen: true,
             marker: PhantomData,
         }
     }
+
+    /// Marks the subworld as borrowed from a schedule, which forbids
+    /// widening it, as other systems may access the remaining components
+    /// concurrently
+    pub(crate) fn scheduled(mut self) -> Self {
+        self.widen = false;
+        self
+    }
+
+    /// Returns true if the subworld may be widened beyond its access
+    pub(crate) fn may_widen(&self) -> bool {
+        self.widen
+    }
+
+    /// Defers commands of the subworld to `deferred`
+    pub(crate) fn with_deferred(mut self, deferred: Option<&Arc<DeferredQueue>>) -> Self {
+        self.deferred = deferred.cloned();
+        self
+    }
+
+    /// Records the accesses of the subworld to `log`, if it is tracked
+    pub(crate) fn with_log(mut self, log: Option<&Arc<AccessLog>>) -> Self {
+        self.log = log.cloned();
+        self
+    }
+
+    /// Creates a subworld referencing `frame`, which defers commands to the
+    /// same schedule
+    pub(crate) fn derive<B, U>(&self, frame: B) -> SubWorldRaw<B, U> {
+        SubWorldRaw {
+            frame,
+            deferred: self.deferred.clone(),
+            log: self.log.clone(),
+            widen: self.widen,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the reference to the world held by the subworld
+    pub fn into_inner(self) -> A {
+        self.frame
+    }
+}
+
+impl<A, T: ComponentAccess> SubWorldRaw<A, T> {
+    /// Describes the components the subworld can access, e.g. for error
+    /// messages or debugging. Components which are accessed both immutably
+    /// and mutably are described once as mutable.
+    pub fn component_names(&self) -> Vec<AccessInfo> {
+        let mut components: Vec<AccessInfo> = Vec::new();
+
+        T::for_each_component(&mut |info| match components
+            .iter_mut()
+            .find(|other| other.id() == info.id())
+        {
+            Some(other) if info.mutable() => *other = info,
+            Some(_) => {}
+            None => components.push(info),
+        });
+
+        components
+    }
 }
 
 impl<A, T: ComponentBorrow> SubWorldRaw<A, T> {
     /// Returns true if the subworld can access the borrow of T
     pub fn has<U: IntoAccess>(&self) -> bool {
+        if let Some(log) = self.tracked_log() {
+            log.record(U::access());
+        }
+
         T::has::<U>()
     }
 
     /// Returns true if the world satisfies the whole query
     pub fn has_all<U: Subset>(&self) -> bool {
+        if let Some(log) = self.tracked_log() {
+            U::for_each_borrow(&mut |id, exclusive| {
+                log.record(Access::new(type_name::<U>(), id, exclusive))
+            });
+        }
+
         U::is_subset::<T>()
     }
+
+    // The access log, if the subworld records its accesses
+    fn tracked_log(&self) -> Option<&AccessLog> {
+        self.log.as_deref().filter(|_| T::tracked())
+    }
 }
 
 impl<'w, A: 'w + Deref<Target = Frame>, T: ComponentBorrow> SubWorldRaw<A, T> {
-    /// Query the subworld.
+    /// Combines two subworlds of the same world into one which can access the
+    /// components of both. Fails if the subworlds reference different worlds.
+    pub fn join<U>(self, other: SubWorldRaw<A, U>) -> Result<SubWorldRaw<A, (T, U)>>
+    where
+        (T, U): ComponentBorrow,
+    {
+        if !std::ptr::eq(&*self.frame, &*other.frame) {
+            return Err(Error::DifferentWorlds);
+        }
+
+        Ok(SubWorldRaw {
+            frame: self.frame,
+            deferred: self.deferred.or(other.deferred),
+            log: self.log.or(other.log),
+            widen: self.widen && other.widen,
+            marker: PhantomData,
+        })
+    }
+
+    /// Spawns an entity with `components` when the schedule the subworld was
+    /// borrowed from is next flushed. The entity is reserved immediately.
+    /// If the entity was despawned before the flush, the flush fails with
+    /// [Error::CommandsFailed].
+    ///
+    /// Unlike the [CommandBuffer](crate::CommandBuffer), this requires no
+    /// resource, and can thus be used by systems which only read.
+    ///
+    /// Fails if the subworld was not borrowed from an executing schedule.
+    pub fn spawn_deferred(
+        &self,
+        components: impl DynamicBundle + Send + 'static,
+    ) -> Result<Entity> {
+        let deferred = self.deferred.as_ref().ok_or(Error::NoSchedule)?;
+        let entity = self.frame.reserve_entity();
+
+        deferred.push(move |frame, index| {
+            frame
+                .insert(entity, components)
+                .map_err(|_| CommandError::NoSuchEntity(index, entity))
+        });
+
+        Ok(entity)
+    }
+
+    /// Despawns the entity when the schedule the subworld was borrowed from is
+    /// next flushed. If the entity no longer exists by then, the flush fails
+    /// with [Error::CommandsFailed].
+    ///
+    /// Fails if the subworld was not borrowed from an executing schedule.
+    pub fn despawn_deferred(&self, entity: Entity) -> Result<()> {
+        let deferred = self.deferred.as_ref().ok_or(Error::NoSchedule)?;
+
+        deferred.push(move |frame, index| {
+            frame
+                .despawn(entity)
+                .map_err(|_| CommandError::DespawnFailed(index, entity))
+        });
+
+        Ok(())
+    }
+
+    /// Query the subworld. The query may be filtered by [With], [Without] and
+    /// [Satisfies], of which only the fetched components need to be
+    /// accessible.
+    ///
+    /// The filters of the subworld, such as [Exclude], are not applied, see
+    /// [query_filtered](Self::query_filtered).
     /// # Panics
     /// Panics if the query items are not a compatible subset of the subworld.
     pub fn query<Q: Query + Subset>(&self) -> QueryBorrow<'_, Q> {
@@ -63,8 +327,227 @@ impl<'w, A: 'w + Deref<Target = Frame>, T: ComponentBorrow> SubWorldRaw<A, T> {
             .expect("Failed to execute query on subworld")
     }
 
+    /// Query the subworld, skipping the entities excluded by the filters of
+    /// the subworld such as [Exclude].
+    /// # Panics
+    /// Panics if the query items are not a compatible subset of the subworld.
+    pub fn query_filtered<Q: Query + Subset>(&self) -> QueryBorrow<'_, With<Q, T::Filter>>
+    where
+        T: SubWorldFilter,
+    {
+        self.try_query_filtered()
+            .expect("Failed to execute query on subworld")
+    }
+
+    /// Query the subworld, skipping the entities excluded by the filters of
+    /// the subworld. Fails if the query items are not a compatible subset of
+    /// the subworld.
+    pub fn try_query_filtered<Q: Query + Subset>(
+        &self,
+    ) -> Result<QueryBorrow<'_, With<Q, T::Filter>>>
+    where
+        T: SubWorldFilter,
+    {
+        self.try_query::<Q>().map(|query| query.with::<T::Filter>())
+    }
+
+    /// Returns the entities matching `Q` ordered by the key of each item,
+    /// e.g. the depth of sprites for rendering. Entities with equal keys keep
+    /// the order of the query.
+    ///
+    /// The entities are collected into `sorted`, which is reused between
+    /// calls. Fails if the query is not a compatible subset of the subworld.
+    ///
+    /// # Example
+    /// ```rust
+    /// use moss_hecs::Frame;
+    /// use moss_hecs_schedule::*;
+    ///
+    /// let mut frame = Frame::default();
+    /// let a = frame.spawn((3_i32,));
+    /// let b = frame.spawn((1_i32,));
+    ///
+    /// let w = SubWorldRef::<&i32>::new(&frame);
+    /// let mut sorted = SortedQuery::new();
+    /// let entities = w.query_sorted::<&i32, _>(&mut sorted, |val| *val).unwrap();
+    ///
+    /// assert!(entities.eq([b, a]));
+    /// ```
+    pub fn query_sorted<'q, Q: Query + Subset, K: Ord>(
+        &self,
+        sorted: &'q mut SortedQuery<K>,
+        mut key: impl FnMut(Q::Item<'_>) -> K,
+    ) -> Result<impl ExactSizeIterator<Item = Entity> + 'q> {
+        let mut query = self.try_query::<Q>()?;
+
+        sorted.entities.clear();
+        sorted
+            .entities
+            .extend(query.iter().map(|(entity, item)| (key(item), entity)));
+        sorted.entities.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(sorted.entities.iter().map(|&(_, entity)| entity))
+    }
+
+    /// Splits the entities matching `Q` into owned chunks of at most
+    /// `chunk_size` entities which never span multiple archetypes, e.g. to
+    /// distribute the work to a custom thread pool or upload queue. Fails if
+    /// the query is not a compatible subset of the subworld.
+    ///
+    /// No components are borrowed until the chunks are processed, see
+    /// [QueryChunk::for_each].
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is zero.
+    ///
+    /// # Example
+    /// ```rust
+    /// use moss_hecs::Frame;
+    /// use moss_hecs_schedule::*;
+    ///
+    /// let mut frame = Frame::default();
+    /// frame.spawn_batch((0..10).map(|i| (i,)));
+    ///
+    /// let w = SubWorldRef::<&i32>::new(&frame);
+    /// let chunks = w.iter_chunks::<&i32>(4).unwrap();
+    /// assert_eq!(chunks.len(), 3);
+    ///
+    /// let sum = std::thread::scope(|scope| {
+    ///     let handles: Vec<_> = chunks
+    ///         .into_iter()
+    ///         .map(|chunk| {
+    ///             let w = &w;
+    ///             scope.spawn(move || {
+    ///                 let mut sum = 0;
+    ///                 chunk.for_each(w, |_, val| sum += *val).unwrap();
+    ///                 sum
+    ///             })
+    ///         })
+    ///         .collect();
+    ///
+    ///     handles.into_iter().map(|handle| handle.join().unwrap()).sum::<i32>()
+    /// });
+    ///
+    /// assert_eq!(sum, 45);
+    /// ```
+    pub fn iter_chunks<Q: Query + Subset>(&self, chunk_size: u32) -> Result<Vec<QueryChunk<Q>>> {
+        assert!(chunk_size > 0, "Chunk size must be nonzero");
+
+        if !self.has_all::<Q>() {
+            return Err(Error::IncompatibleSubworld {
+                subworld: type_name::<T>(),
+                query: type_name::<Q>(),
+            });
+        }
+
+        let mut query = self.frame.query::<()>().with::<Q>();
+        let chunks = query
+            .iter_batched(chunk_size)
+            .map(|batch| QueryChunk {
+                entities: batch.map(|(entity, _)| entity).collect(),
+                marker: PhantomData,
+            })
+            .filter(|chunk| !chunk.is_empty())
+            .collect();
+
+        Ok(chunks)
+    }
+
+    #[cfg(feature = "rand")]
+    /// Returns up to `n` distinct entities matching `Q`, sampled uniformly
+    /// using `rng`, in the order of the query. Fails if the query is not a
+    /// compatible subset of the subworld.
+    pub fn sample<Q: Query + Subset>(
+        &self,
+        rng: &mut impl rand::Rng,
+        n: usize,
+    ) -> Result<Vec<Entity>> {
+        let mut query = self.try_query::<Q>()?;
+        let iter = query.iter();
+
+        let len = iter.len();
+        let mut indices = rand::seq::index::sample(rng, len, n.min(len)).into_vec();
+        indices.sort_unstable();
+
+        let mut indices = indices.into_iter().peekable();
+        let entities = iter
+            .enumerate()
+            .filter(|(i, _)| indices.next_if_eq(i).is_some())
+            .map(|(_, (entity, _))| entity)
+            .collect();
+
+        Ok(entities)
+    }
+
+    /// Returns a view of the entities matching `Q`, which supports random
+    /// access by entity.
+    /// # Panics
+    /// Panics if the query items are not a compatible subset of the subworld.
+    pub fn view<Q: Query + QueryShared + Subset>(&self) -> QueryView<'_, Q> {
+        self.try_view()
+            .expect("Failed to execute query on subworld")
+    }
+
+    /// Returns a view of the entities matching `Q`, which may access
+    /// components mutably through [QueryView::get_mut].
+    /// # Panics
+    /// Panics if the query items are not a compatible subset of the subworld.
+    pub fn view_mut<Q: Query + Subset>(&self) -> QueryView<'_, Q> {
+        self.try_view()
+            .expect("Failed to execute query on subworld")
+    }
+
+    /// Returns a view of the entities matching `Q`. Fails if the query items
+    /// are not a compatible subset of the subworld.
+    pub fn try_view<Q: Query + Subset>(&self) -> Result<QueryView<'_, Q>> {
+        if !self.has_all::<Q>() {
+            return Err(Error::IncompatibleSubworld {
+                subworld: type_name::<T>(),
+                query: type_name::<Q>(),
+            });
+        }
+
+        Ok(QueryView::new(&self.frame))
+    }
+
+    /// Query the subworld using a prepared query, which avoids repeating the
+    /// archetype matching and subworld compatibility check of each query.
+    /// # Panics
+    /// Panics if the query items are not a compatible subset of the subworld.
+    pub fn prepared_query<'q, Q: Query + Subset>(
+        &'q self,
+        prepared: &'q mut PreparedQuery<Q>,
+    ) -> PreparedQueryBorrow<'q, Q> {
+        self.try_prepared_query(prepared)
+            .expect("Failed to execute query on subworld")
+    }
+
+    /// Query the subworld using a prepared query. Fails if the query items
+    /// are not a compatible subset of the subworld.
+    pub fn try_prepared_query<'q, Q: Query + Subset>(
+        &'q self,
+        prepared: &'q mut PreparedQuery<Q>,
+    ) -> Result<PreparedQueryBorrow<'q, Q>> {
+        let subworld = type_name::<T>();
+
+        if prepared.subworld != Some(subworld) {
+            if !self.has_all::<Q>() {
+                return Err(Error::IncompatibleSubworld {
+                    subworld,
+                    query: type_name::<Q>(),
+                });
+            }
+
+            prepared.subworld = Some(subworld);
+        }
+
+        Ok(prepared.inner.query(&self.frame))
+    }
+
     /// Query the subworld for a single entity.
     /// Wraps the hecs::NoSuchEntity error and provides the entity id
+    ///
+    /// The filters of the subworld, such as [Exclude], are not applied.
     pub fn query_one<Q: Query + Subset>(&'w self, entity: Entity) -> Result<QueryOne<'w, Q>> {
         if !self.has_all::<Q>() {
             return Err(Error::IncompatibleSubworld {
@@ -81,6 +564,36 @@ impl<'w, A: 'w + Deref<Target = Frame>, T: ComponentBorrow> SubWorldRaw<A, T> {
         Ok(QueryOne::new(entity, query))
     }
 
+    /// Query the subworld for a single entity and pass the result to `f`,
+    /// without keeping a [QueryOne] around. The components are borrowed once
+    /// for the duration of `f`.
+    ///
+    /// As the result borrows the world, it can not outlive the call, hence the
+    /// closure.
+    ///
+    /// # Example
+    /// ```rust
+    /// use moss_hecs::Frame;
+    /// use moss_hecs_schedule::*;
+    ///
+    /// let mut frame = Frame::default();
+    /// let entity = frame.spawn((1_i32, 2.0_f32));
+    ///
+    /// let w = SubWorldRef::<(&mut i32, &f32)>::new(&frame);
+    /// w.query_one_mut::<(&mut i32, &f32), _>(entity, |(a, b)| *a += *b as i32)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(*w.get::<i32>(entity).unwrap(), 3);
+    /// ```
+    pub fn query_one_mut<Q: Query + Subset, R>(
+        &self,
+        entity: Entity,
+        f: impl FnOnce(Q::Item<'_>) -> R,
+    ) -> Result<R> {
+        let mut query = self.query_one::<Q>(entity)?;
+        Ok(f(query.get()?))
+    }
+
     /// Get a single component from the world.
     ///
     /// Wraps the hecs::NoSuchEntity error and provides the entity id
@@ -105,10 +618,10 @@ impl<'w, A: 'w + Deref<Target = Frame>, T: ComponentBorrow> SubWorldRaw<A, T> {
     ///
     /// Wraps the hecs::NoSuchEntity error and provides the entity id
     pub fn get_mut<C: Component>(&self, entity: Entity) -> Result<moss_hecs::RefMut<C>> {
-        if !self.has::<&C>() {
+        if !self.has::<&mut C>() {
             return Err(Error::IncompatibleSubworld {
                 subworld: type_name::<T>(),
-                query: type_name::<&C>(),
+                query: type_name::<&mut C>(),
             });
         }
 
@@ -121,6 +634,217 @@ impl<'w, A: 'w + Deref<Target = Frame>, T: ComponentBorrow> SubWorldRaw<A, T> {
         }
     }
 
+    /// Get a component from each of the entities at once, failing on the
+    /// first entity which does not exist or does not have the component.
+    pub fn get_many<C: Component, const N: usize>(
+        &self,
+        entities: [Entity; N],
+    ) -> Result<[moss_hecs::Ref<C>; N]> {
+        if !self.has::<&C>() {
+            return Err(Error::IncompatibleSubworld {
+                subworld: type_name::<T>(),
+                query: type_name::<&C>(),
+            });
+        }
+
+        collect_array(entities.map(|entity| {
+            self.frame
+                .get::<&C>(entity)
+                .map_err(|e| component_error(entity, e))
+        }))
+    }
+
+    /// Mutably borrows a component of each of the entities at once and passes
+    /// them to `f`, failing on the first entity which does not exist or does
+    /// not have the component.
+    ///
+    /// The components are borrowed through a single borrow of each column, so
+    /// the entities may share an archetype.
+    ///
+    /// Fails with [Error::DuplicateEntity] if an entity occurs more than once.
+    ///
+    /// # Panics
+    /// Panics if the components are already borrowed.
+    pub fn get_many_mut<C: Component, R, const N: usize>(
+        &self,
+        entities: [Entity; N],
+        f: impl FnOnce([&mut C; N]) -> R,
+    ) -> Result<R> {
+        if !self.has::<&mut C>() {
+            return Err(Error::IncompatibleSubworld {
+                subworld: type_name::<T>(),
+                query: type_name::<&mut C>(),
+            });
+        }
+
+        for (i, entity) in entities.iter().enumerate() {
+            if entities[..i].contains(entity) {
+                return Err(Error::DuplicateEntity(*entity));
+            }
+        }
+
+        let frame = &*self.frame;
+        let mut view = frame.view::<&mut C>();
+        let mut entity = entities.iter();
+        let components = collect_array(view.get_many_mut(entities).map(|component| {
+            let entity = *entity.next().expect("One entity per component");
+            component.ok_or_else(|| {
+                if frame.contains(entity) {
+                    Error::MissingComponent(entity, moss_hecs::MissingComponent::new::<C>())
+                } else {
+                    Error::NoSuchEntity(entity)
+                }
+            })
+        }))?;
+
+        Ok(f(components))
+    }
+
+    /// Returns a handle to the entity, which permits access to the
+    /// components accessible by the subworld
+    pub fn entity(&self, entity: Entity) -> Result<SubEntityRef<'_, T>> {
+        self.frame
+            .entity(entity)
+            .map(SubEntityRef::new)
+            .map_err(|_| Error::NoSuchEntity(entity))
+    }
+
+    /// Copies the components of `entity` accessible by the subworld onto a
+    /// newly reserved entity, which is returned. The components are inserted
+    /// when `cmd` is executed.
+    ///
+    /// # Example
+    /// ```rust
+    /// use moss_hecs::Frame;
+    /// use moss_hecs_schedule::*;
+    ///
+    /// let mut frame = Frame::default();
+    /// let prefab = frame.spawn((1_i32, 2.0_f32, "hidden"));
+    ///
+    /// let mut cmd = CommandBuffer::new();
+    /// let w = SubWorldRef::<(&i32, &f32)>::new(&frame);
+    /// let copy = w.clone_entity_into(prefab, &mut cmd).unwrap();
+    ///
+    /// cmd.execute(&mut frame);
+    ///
+    /// assert_eq!(*frame.get::<&f32>(copy).unwrap(), 2.0);
+    /// assert!(frame.get::<&&str>(copy).is_err());
+    /// ```
+    pub fn clone_entity_into(&self, entity: Entity, cmd: &mut CommandBuffer) -> Result<Entity>
+    where
+        T: CloneComponents,
+    {
+        let entity = self
+            .frame
+            .entity(entity)
+            .map_err(|_| Error::NoSuchEntity(entity))?;
+
+        let target = self.frame.reserve_entity();
+        T::clone_components(&entity, target, cmd);
+
+        Ok(target)
+    }
+
+    /// Returns true if the entity matches the query, without borrowing any
+    /// components. Fails if the query is not a compatible subset of the
+    /// subworld, or if the entity does not exist.
+    pub fn satisfies<Q: Query + Subset>(&self, entity: Entity) -> Result<bool> {
+        if !self.has_all::<Q>() {
+            return Err(Error::IncompatibleSubworld {
+                subworld: type_name::<T>(),
+                query: type_name::<Q>(),
+            });
+        }
+
+        self.frame
+            .satisfies::<Q>(entity)
+            .map_err(|_| Error::NoSuchEntity(entity))
+    }
+
+    /// Returns true if the entity exists in the world. Does not require any
+    /// components to be accessible.
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.frame.contains(entity)
+    }
+
+    /// Returns the number of entities in the world. Does not require any
+    /// components to be accessible.
+    pub fn len(&self) -> u32 {
+        self.frame.len()
+    }
+
+    /// Returns true if the world contains no entities
+    pub fn is_empty(&self) -> bool {
+        self.frame.is_empty()
+    }
+
+    /// Returns the number of entities matching the query, without borrowing
+    /// any components. The queried components are thus not required to be
+    /// accessible.
+    pub fn count<Q: Query>(&self) -> u32 {
+        self.frame
+            .archetypes()
+            .filter(|archetype| archetype.satisfies::<Q>())
+            .map(|archetype| archetype.len())
+            .sum()
+    }
+
+    /// Describes the archetypes of the world which contain any component the
+    /// subworld can access, e.g. for diagnostics.
+    pub fn archetypes(&self) -> impl Iterator<Item = ArchetypeInfo<'_, T>> + '_ {
+        self.frame
+            .archetypes()
+            .map(ArchetypeInfo::new)
+            .filter(|archetype| archetype.accessible_types().next().is_some())
+    }
+
+    /// Borrows the columns of the components of `Q` from each non-empty
+    /// archetype which has all of them, e.g. to run SIMD kernels over
+    /// contiguous slices of components. Fails if the query is not a compatible
+    /// subset of the subworld.
+    ///
+    /// The columns of an archetype are borrowed until dropped. Entries of
+    /// the columns of an archetype with the same index belong to the same
+    /// entity.
+    ///
+    /// # Panics
+    /// Panics if a column is already borrowed incompatibly.
+    ///
+    /// # Example
+    /// ```rust
+    /// use moss_hecs::Frame;
+    /// use moss_hecs_schedule::*;
+    ///
+    /// let mut frame = Frame::default();
+    /// frame.spawn_batch((0..4).map(|i| (i as f32, 1.0_f64)));
+    ///
+    /// let w = SubWorldRef::<(&mut f32, &f64)>::new(&frame);
+    /// for (mut a, b) in w.columns::<(&mut f32, &f64)>().unwrap() {
+    ///     a.iter_mut().zip(b.iter()).for_each(|(a, b)| *a += *b as f32);
+    /// }
+    ///
+    /// assert_eq!(w.query::<&f32>().iter().map(|(_, a)| *a).sum::<f32>(), 10.0);
+    /// ```
+    pub fn columns<Q: Columns + Subset>(&self) -> Result<impl Iterator<Item = Q::Column<'_>> + '_> {
+        if !self.has_all::<Q>() {
+            return Err(Error::IncompatibleSubworld {
+                subworld: type_name::<T>(),
+                query: type_name::<Q>(),
+            });
+        }
+
+        Ok(self
+            .frame
+            .archetypes()
+            .filter(|archetype| !archetype.is_empty())
+            .filter_map(Q::columns))
+    }
+
+    /// Reserve an entity concurrently
+    pub fn reserve_entity(&self) -> Entity {
+        self.frame.reserve_entity()
+    }
+
     /// Reserve multiple entities concurrently
     pub fn reserve_entities(&self, count: u32) -> impl Iterator<Item = Entity> + '_ {
         self.frame.reserve_entities(count)
@@ -133,4 +857,53 @@ impl<'w, A: 'w + Deref<Target = Frame>, T: ComponentBorrow> SubWorldRaw<A, T> {
         self.try_query()
             .expect("Failed to execute query on subworld")
     }
+
+    #[cfg(feature = "parallel")]
+    /// Query the subworld for iteration with rayon, see [ParQueryBorrow].
+    /// # Panics
+    /// Panics if the query items are not a compatible subset of the subworld.
+    pub fn par_query<Q: Query + Subset>(&self) -> ParQueryBorrow<'_, Q> {
+        ParQueryBorrow::new(self.query())
+    }
+
+    #[cfg(feature = "parallel")]
+    /// Calls `f` for each entity matching the query in parallel on the
+    /// current thread pool, without requiring a batch size.
+    /// # Panics
+    /// Panics if the query items are not a compatible subset of the subworld.
+    pub fn par_for_each_auto<Q, F>(&self, f: F)
+    where
+        Q: Query + Subset,
+        F: for<'q> Fn(Entity, Q::Item<'q>) + Send + Sync,
+        for<'q> Q::Item<'q>: Send,
+    {
+        self.par_query::<Q>()
+            .par_iter()
+            .for_each(|(entity, item)| f(entity, item))
+    }
+}
+
+// Maps the hecs error of getting a component of `entity`
+pub(crate) fn component_error(entity: Entity, error: moss_hecs::ComponentError) -> Error {
+    match error {
+        moss_hecs::ComponentError::NoSuchEntity => Error::NoSuchEntity(entity),
+        moss_hecs::ComponentError::MissingComponent(name) => Error::MissingComponent(entity, name),
+    }
+}
+
+// Returns the first error, without requiring an allocation
+pub(crate) fn collect_array<V, const N: usize>(results: [Result<V>; N]) -> Result<[V; N]> {
+    let mut error = None;
+    let values = results.map(|result| match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            error.get_or_insert(e);
+            None
+        }
+    });
+
+    match error {
+        Some(e) => Err(e),
+        None => Ok(values.map(|value| value.expect("All results are Ok"))),
+    }
 }
@@ -1,13 +1,18 @@
-use std::{any::type_name, ops::Deref};
+use std::{
+    any::{type_name, TypeId},
+    cell::Ref,
+    ops::Deref,
+    sync::Arc,
+};
 
 use atomic_refcell::AtomicRef;
 use moss_hecs::{Component, Entity, Frame, Query, QueryBorrow};
 
 use crate::{
-    borrow::{Borrows, ComponentBorrow, ContextBorrow},
+    borrow::{Borrows, ComponentBorrow, ContextBorrow, Locals},
     traits::View,
-    Access, Context, EmptyWorld, Error, IntoAccess, QueryOne, Result, SubWorld, SubWorldRaw,
-    SubWorldRef, Subset,
+    Access, Context, EmptyWorld, Error, Intersect, IntoAccess, QueryOne, ReadOnly, Result,
+    SubWorld, SubWorldRaw, SubWorldRef, SubWorldRwLock, Subset, World, WorldRef,
 };
 
 impl<A: Deref<Target = Frame>, T: Query> SubWorldRaw<A, T> {
@@ -29,7 +34,43 @@ impl<A: ExternalClone, T: ComponentBorrow> SubWorldRaw<A, T> {
             });
         }
 
-        Ok(SubWorldRaw::new(A::external_clone(&self.frame)))
+        Ok(self.derive(A::external_clone(&self.frame)))
+    }
+
+    /// Splits the subworld into one which can access the components
+    /// accessible by both the subworld and `U`. See [Intersect].
+    pub fn intersect<U: ComponentBorrow>(&self) -> SubWorldRaw<A, Intersect<T, U>> {
+        self.derive(A::external_clone(&self.frame))
+    }
+
+    /// Returns a view of the subworld which can only read its components, see
+    /// [ReadOnly].
+    pub fn as_read_only(&self) -> SubWorldRaw<A, ReadOnly<T>> {
+        self.derive(A::external_clone(&self.frame))
+    }
+}
+
+impl<A: ExternalClone, T: ComponentBorrow> SubWorldRaw<A, T> {
+    /// Converts the subworld into one with access to `U`, which may include
+    /// components outside of the subworld. Succeeds if `U` is a subset of the
+    /// subworld, or if the subworld was created by [new](SubWorldRaw::new)
+    /// rather than borrowed from a schedule.
+    ///
+    /// Subworlds borrowed from a schedule, and the subworlds split or
+    /// converted from them, such as by [GenericWorld::to_ref], can not be
+    /// widened, as other systems may access the remaining components
+    /// concurrently.
+    ///
+    /// The components are still borrow checked at runtime when accessed.
+    pub fn try_widen<U: ComponentBorrow + Subset>(&self) -> Result<SubWorldRaw<A, U>> {
+        if !self.has_all::<U>() && !self.may_widen() {
+            return Err(Error::IncompatibleSubworld {
+                subworld: type_name::<T>(),
+                query: type_name::<SubWorldRaw<A, U>>(),
+            });
+        }
+
+        Ok(self.derive(A::external_clone(&self.frame)))
     }
 }
 
@@ -57,6 +98,67 @@ impl<T> ExternalClone for AtomicRef<'_, T> {
     }
 }
 
+impl<T> ExternalClone for Arc<T> {
+    fn external_clone(&self) -> Self {
+        Arc::clone(self)
+    }
+}
+
+#[cfg(feature = "parking_lot")]
+// Recursive reads do not block on pending writes, which would deadlock
+impl<T> ExternalClone for parking_lot::RwLockReadGuard<'_, T> {
+    fn external_clone(&self) -> Self {
+        parking_lot::RwLockReadGuard::rwlock(self).read_recursive()
+    }
+}
+
+impl<W> ExternalClone for WorldRef<'_, W> {
+    fn external_clone(&self) -> Self {
+        WorldRef::new(AtomicRef::clone(&self.frame))
+    }
+}
+
+/// Describes which world a reference held by a subworld refers to,
+/// determining the access of the subworld
+pub trait WorldAccess {
+    /// Returns the access to the world
+    fn access() -> Access;
+
+    /// Returns the world which components are accessed in, or None for the
+    /// main frame
+    fn world() -> Option<TypeId> {
+        None
+    }
+}
+
+impl WorldAccess for &Frame {
+    fn access() -> Access {
+        Access::of::<&Frame>().requires::<Frame>()
+    }
+}
+
+impl WorldAccess for Ref<'_, Frame> {
+    fn access() -> Access {
+        Access::of::<&Frame>().requires::<Frame>()
+    }
+}
+
+impl WorldAccess for AtomicRef<'_, Frame> {
+    fn access() -> Access {
+        Access::of::<&Frame>().requires::<Frame>()
+    }
+}
+
+impl<W: World> WorldAccess for WorldRef<'_, W> {
+    fn access() -> Access {
+        Access::of::<&W>().requires::<W>()
+    }
+
+    fn world() -> Option<TypeId> {
+        Some(TypeId::of::<W>())
+    }
+}
+
 impl<'a, A, T> View<'a> for SubWorldRaw<A, T>
 where
     A: Deref<Target = Frame>,
@@ -79,7 +181,24 @@ impl<'a, T> ContextBorrow<'a> for SubWorld<'a, T> {
             .map_err(|_| Error::Borrow(type_name::<T>()))
             .map(|cell| AtomicRef::map(cell, |val| unsafe { val.cast().as_ref() }))?;
 
-        Ok(Self::new(val))
+        Ok(Self::new(val)
+            .scheduled()
+            .with_deferred(context.deferred())
+            .with_log(context.locals().map(Locals::accessed)))
+    }
+}
+
+impl<'a, T, W: World> ContextBorrow<'a> for SubWorldRaw<WorldRef<'a, W>, T> {
+    type Target = Self;
+
+    fn borrow(context: &'a Context) -> Result<Self> {
+        let val = context
+            .cell::<&W>()?
+            .try_borrow()
+            .map_err(|_| Error::Borrow(type_name::<W>()))
+            .map(|cell| AtomicRef::map(cell, |val| unsafe { val.cast::<W>().as_ref() }.frame()))?;
+
+        Ok(Self::new(WorldRef::new(val)).scheduled())
     }
 }
 
@@ -106,7 +225,7 @@ impl<'a, T> From<&'a Context<'a>> for SubWorldRaw<AtomicRef<'a, Frame>, T> {
 
         let val = AtomicRef::map(borrow, |val| unsafe { val.cast().as_ref() });
 
-        Self::new(val)
+        Self::new(val).scheduled().with_deferred(context.deferred())
     }
 }
 
@@ -118,10 +237,21 @@ impl<'a, 'b, T: ComponentBorrow, U: ComponentBorrow + Subset> From<&'b SubWorld<
     }
 }
 
-impl<A, T: ComponentBorrow + Query> ComponentBorrow for SubWorldRaw<A, T> {
+impl<'a, 'b, T: ComponentBorrow, U: ComponentBorrow + Subset> From<&'b SubWorldRwLock<'a, T>>
+    for SubWorldRef<'b, U>
+{
+    fn from(subworld: &'b SubWorldRwLock<'a, T>) -> Self {
+        subworld.to_ref()
+    }
+}
+
+impl<A: WorldAccess, T: ComponentBorrow> ComponentBorrow for SubWorldRaw<A, T> {
     fn borrows() -> Borrows {
-        let mut access = T::borrows();
-        access.push(Access::of::<&Frame>());
+        let mut access: Borrows = T::borrows()
+            .into_iter()
+            .map(|access| access.in_world(A::world()))
+            .collect();
+        access.push(A::access());
         access
     }
 
@@ -129,7 +259,7 @@ impl<A, T: ComponentBorrow + Query> ComponentBorrow for SubWorldRaw<A, T> {
         T::has::<U>()
     }
 
-    fn has_dynamic(id: std::any::TypeId, exclusive: bool) -> bool {
+    fn has_dynamic(id: TypeId, exclusive: bool) -> bool {
         T::has_dynamic(id, exclusive)
     }
 }
@@ -159,14 +289,22 @@ pub trait GenericWorld {
     /// anyway
     fn try_get_mut<C: Component>(&self, entity: Entity) -> Result<moss_hecs::RefMut<C>>;
 
-    /// Reserve an entity
-    fn reserve(&self) -> Entity;
+    /// Reserve an entity, like [Frame::reserve_entity]
+    fn reserve_entity(&self) -> Entity;
+
+    /// Reserve an entity, same as [Self::reserve_entity]
+    fn reserve(&self) -> Entity {
+        self.reserve_entity()
+    }
+
+    /// Returns true if the entity exists in the world
+    fn contains(&self, entity: Entity) -> bool;
 }
 
 impl<A: Deref<Target = Frame>, T: ComponentBorrow> GenericWorld for SubWorldRaw<A, T> {
     fn to_ref<U: ComponentBorrow + Subset>(&self) -> SubWorldRef<U> {
         let frame = self.frame.deref();
-        SubWorldRef::<T>::new(frame).split().unwrap()
+        self.derive::<_, T>(frame).split().unwrap()
     }
 
     fn try_query<Q: Query + Subset>(&self) -> Result<QueryBorrow<'_, Q>> {
@@ -193,9 +331,13 @@ impl<A: Deref<Target = Frame>, T: ComponentBorrow> GenericWorld for SubWorldRaw<
     }
 
     /// Reserve an entity
-    fn reserve(&self) -> Entity {
+    fn reserve_entity(&self) -> Entity {
         self.frame.reserve_entity()
     }
+
+    fn contains(&self, entity: Entity) -> bool {
+        SubWorldRaw::contains(self, entity)
+    }
 }
 
 impl GenericWorld for Frame {
@@ -235,7 +377,11 @@ impl GenericWorld for Frame {
     }
 
     /// Reserve an entity
-    fn reserve(&self) -> Entity {
-        self.reserve_entity()
+    fn reserve_entity(&self) -> Entity {
+        Frame::reserve_entity(self)
+    }
+
+    fn contains(&self, entity: Entity) -> bool {
+        Frame::contains(self, entity)
     }
 }
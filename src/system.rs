@@ -2,14 +2,19 @@
 //! against a [Context](crate::Context).
 use std::{any::type_name, borrow::Cow};
 
+use moss_hecs::Frame;
+
 use crate::{
     borrow::{Borrows, ComponentBorrow, ContextBorrow, IntoBorrow},
-    Context, Result,
+    Access, Context, Result, Write,
 };
 
 /// System name alias
 pub type SystemName = Cow<'static, str>;
 
+/// Label used to refer to systems in ordering constraints
+pub type SystemLabel = Cow<'static, str>;
+
 /// Trait which defines any function or type that can operate on a world or
 /// other context.
 pub trait System<Args, Ret> {
@@ -72,7 +77,10 @@ macro_rules! tuple_impl {
                 let mut func = self;
                 match (&mut func)($($name::Borrow::borrow(context)?), *) {
                     Ok(()) => Ok(()),
-                    Err(e) => Err(crate::Error::SystemError(<Self as System<($($name,)*), std::result::Result<(), Err>>>::name(func), e.into())),
+                    Err(e) => Err(crate::Error::SystemError(
+                        <Self as System<($($name,)*), std::result::Result<(), Err>>>::name(func),
+                        e.into(),
+                    )),
                 }
             }
 
@@ -143,6 +151,125 @@ impl<F: System<Args, Ret>, Args, Ret> System<Args, Ret> for NamedSystem<F> {
 
 impl_for_tuples!(tuple_impl);
 
+/// Trait for systems which require exclusive access to the whole [Frame].
+///
+/// The frame is always passed as the first argument, followed by any other
+/// borrows. Since the frame is borrowed mutably, the other arguments can not
+/// borrow the frame as well, e.g; through a [SubWorld](crate::SubWorld).
+pub trait ExclusiveSystem<Args, Ret> {
+    /// Executes the by borrowing from context
+    fn execute(&mut self, context: &Context) -> Result<()>;
+    /// Returns the system name. Used for debug purposes
+    fn name(&self) -> SystemName;
+
+    /// Returns which data will be accessed, including the frame
+    fn borrows() -> Borrows;
+}
+
+fn borrow_frame<'a>(context: &'a Context) -> Result<Write<'a, Frame>> {
+    Write::try_from_untyped(context.cell::<&mut Frame>()?)
+}
+
+macro_rules! exclusive_tuple_impl {
+    ($($name: ident), *) => {
+        impl<Func, $($name,)  *> ExclusiveSystem<($($name,)*), ()> for Func
+        where
+            for<'a, 'b> &'b mut Func:
+                FnMut(&mut Frame, $($name,)*) +
+                FnMut(&mut Frame, $(<$name::Borrow as ContextBorrow<'a>>::Target),*),
+                $($name: IntoBorrow + ComponentBorrow,)*
+        {
+            fn execute(&mut self, context: &Context) -> Result<()> {
+                let mut frame = borrow_frame(context)?;
+                let mut func = self;
+                (&mut func)(&mut *frame, $($name::Borrow::borrow(context)?), *);
+                Ok(())
+            }
+
+            fn name(&self) -> SystemName {
+                type_name::<Func>().into()
+            }
+
+            fn borrows() -> Borrows {
+                ([Access::of::<&mut Frame>().requires::<Frame>()].iter()
+                    $(.chain($name::borrows().iter())) *).cloned()
+                .collect()
+            }
+        }
+
+        impl<Err, Func, $($name,) *> ExclusiveSystem<($($name,)*), std::result::Result<(), Err>> for Func
+        where
+            Err: Into<anyhow::Error>,
+            for<'a, 'b> &'b mut Func:
+                FnMut(&mut Frame, $($name,)*) -> std::result::Result<(), Err> +
+                FnMut(&mut Frame, $(<$name::Borrow as ContextBorrow<'a>>::Target),*) -> std::result::Result<(), Err>,
+                $($name: IntoBorrow + ComponentBorrow,)*
+        {
+            fn execute(&mut self, context: &Context) -> Result<()> {
+                let mut frame = borrow_frame(context)?;
+                let mut func = self;
+                match (&mut func)(&mut *frame, $($name::Borrow::borrow(context)?), *) {
+                    Ok(()) => Ok(()),
+                    Err(e) => Err(crate::Error::SystemError(
+                        <Self as ExclusiveSystem<($($name,)*), std::result::Result<(), Err>>>::name(func),
+                        e.into(),
+                    )),
+                }
+            }
+
+            fn name(&self) -> SystemName {
+                type_name::<Func>().into()
+            }
+
+            fn borrows() -> Borrows {
+                ([Access::of::<&mut Frame>().requires::<Frame>()].iter()
+                    $(.chain($name::borrows().iter())) *).cloned()
+                .collect()
+            }
+        }
+    };
+}
+
+impl<F: FnMut(&mut Frame)> ExclusiveSystem<(), ()> for F {
+    fn execute(&mut self, context: &Context) -> Result<()> {
+        let mut frame = borrow_frame(context)?;
+        (self)(&mut *frame);
+        Ok(())
+    }
+
+    fn name(&self) -> SystemName {
+        type_name::<F>().into()
+    }
+
+    fn borrows() -> Borrows {
+        Borrows::from_slice(&[Access::of::<&mut Frame>().requires::<Frame>()])
+    }
+}
+
+impl<Err: Into<anyhow::Error>, F: FnMut(&mut Frame) -> std::result::Result<(), Err>>
+    ExclusiveSystem<(), std::result::Result<(), Err>> for F
+{
+    fn execute(&mut self, context: &Context) -> Result<()> {
+        let mut frame = borrow_frame(context)?;
+        (self)(&mut *frame).map_err(|e| {
+            crate::Error::SystemError(
+                <F as ExclusiveSystem<(), std::result::Result<(), Err>>>::name(self),
+                e.into(),
+            )
+        })
+    }
+
+    fn name(&self) -> SystemName {
+        type_name::<F>().into()
+    }
+
+    fn borrows() -> Borrows {
+        Borrows::from_slice(&[Access::of::<&mut Frame>().requires::<Frame>()])
+    }
+}
+
+impl_for_tuples!(exclusive_tuple_impl);
+
 #[cfg(test)]
 mod tests {
     use crate::{system::System, Context, GenericWorld, IntoData, Read, SubWorld};
@@ -0,0 +1,91 @@
+use crate::{
+    condition::{DynamicCondition, SharedCondition},
+    Condition, DynamicSystem, System, SystemLabel,
+};
+
+#[derive(Default)]
+/// A group of systems which share labels, run conditions and ordering
+/// constraints.
+///
+/// The configuration of the set applies to every system in it, in addition to
+/// the configuration of the individual systems. Shared run conditions are
+/// evaluated at most once per execution of the schedule.
+///
+/// Sets are added to a schedule by
+/// [ScheduleBuilder::add_set](crate::ScheduleBuilder::add_set).
+pub struct SystemSet {
+    systems: Vec<DynamicSystem>,
+    labels: Vec<SystemLabel>,
+    before: Vec<SystemLabel>,
+    after: Vec<SystemLabel>,
+    conditions: Vec<SharedCondition>,
+}
+
+impl SystemSet {
+    /// Creates a new empty system set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a system to the set
+    pub fn add_system<Args, Ret, S>(&mut self, system: S) -> &mut Self
+    where
+        S: 'static + System<Args, Ret> + Send,
+    {
+        self.systems.push(DynamicSystem::new(system));
+        self
+    }
+
+    /// Labels all systems in the set
+    pub fn label<L: Into<SystemLabel>>(&mut self, label: L) -> &mut Self {
+        self.labels.push(label.into());
+        self
+    }
+
+    /// Requires all systems in the set to run before all systems labeled
+    /// `label`
+    pub fn before<L: Into<SystemLabel>>(&mut self, label: L) -> &mut Self {
+        self.before.push(label.into());
+        self
+    }
+
+    /// Requires all systems in the set to run after all systems labeled
+    /// `label`
+    pub fn after<L: Into<SystemLabel>>(&mut self, label: L) -> &mut Self {
+        self.after.push(label.into());
+        self
+    }
+
+    /// Only runs the systems in the set when `condition` evaluates to true
+    pub fn run_if<Args, C>(&mut self, condition: C) -> &mut Self
+    where
+        C: 'static + Condition<Args> + Send,
+    {
+        self.conditions
+            .push(SharedCondition::new(DynamicCondition::new(condition)));
+        self
+    }
+
+    /// Applies the shared configuration and returns the systems of the set
+    pub(crate) fn into_systems(self) -> impl Iterator<Item = DynamicSystem> {
+        let Self {
+            systems,
+            labels,
+            before,
+            after,
+            conditions,
+        } = self;
+
+        systems.into_iter().map(move |mut system| {
+            system.labels.extend(labels.iter().cloned());
+            system.before.extend(before.iter().cloned());
+            system.after.extend(after.iter().cloned());
+
+            for condition in &conditions {
+                system.add_condition(condition.instance());
+            }
+
+            system
+        })
+    }
+}
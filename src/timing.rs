@@ -0,0 +1,132 @@
+use std::{
+    collections::VecDeque,
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+use crate::{clock::Instant, SystemName};
+
+/// The number of executions timings are kept for by default
+pub(crate) const DEFAULT_TIMING_WINDOW: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+/// When and where the most recent execution of a system took place
+pub(crate) struct Span {
+    pub(crate) run: u64,
+    pub(crate) start: Instant,
+    pub(crate) end: Instant,
+    pub(crate) thread: ThreadId,
+}
+
+#[derive(Debug, Clone)]
+/// Wall-clock durations of the most recent executions of a system.
+///
+/// Executions skipped due to a run condition or the system being disabled are
+/// not recorded.
+pub struct SystemTiming {
+    name: SystemName,
+    samples: VecDeque<Duration>,
+    window: usize,
+    overruns: usize,
+    span: Option<Span>,
+}
+
+impl SystemTiming {
+    pub(crate) fn new(name: SystemName) -> Self {
+        Self {
+            name,
+            samples: VecDeque::new(),
+            window: DEFAULT_TIMING_WINDOW,
+            overruns: 0,
+            span: None,
+        }
+    }
+
+    pub(crate) fn record(&mut self, run: u64, start: Instant, end: Instant) {
+        if self.window == 0 {
+            return;
+        }
+
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(end.duration_since(start));
+        self.span = Some(Span {
+            run,
+            start,
+            end,
+            thread: thread::current().id(),
+        });
+    }
+
+    pub(crate) fn record_overrun(&mut self) {
+        self.overruns += 1;
+    }
+
+    pub(crate) fn span(&self) -> Option<&Span> {
+        self.span.as_ref()
+    }
+
+    /// Allocates room for a full window of samples
+    pub(crate) fn reserve(&mut self) {
+        let missing = self.window.saturating_sub(self.samples.len());
+        self.samples.reserve_exact(missing);
+    }
+
+    pub(crate) fn set_window(&mut self, window: usize) {
+        let excess = self.samples.len().saturating_sub(window);
+        self.samples.drain(..excess);
+        self.window = window;
+    }
+
+    /// Returns the name of the system
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the recorded durations, from oldest to newest
+    pub fn samples(&self) -> impl Iterator<Item = Duration> + '_ {
+        self.samples.iter().copied()
+    }
+
+    /// Returns the number of recorded executions
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns true if no executions have been recorded
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Returns the duration of the most recent execution
+    pub fn last(&self) -> Option<Duration> {
+        self.samples.back().copied()
+    }
+
+    /// Returns the number of executions which exceeded the timeout of the
+    /// system, including executions outside of the window
+    pub fn overruns(&self) -> usize {
+        self.overruns
+    }
+
+    /// Returns the shortest recorded duration
+    pub fn min(&self) -> Option<Duration> {
+        self.samples().min()
+    }
+
+    /// Returns the longest recorded duration
+    pub fn max(&self) -> Option<Duration> {
+        self.samples().max()
+    }
+
+    /// Returns the mean of the recorded durations
+    pub fn average(&self) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+
+        Some(self.samples().sum::<Duration>() / self.samples.len() as u32)
+    }
+}
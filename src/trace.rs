@@ -0,0 +1,79 @@
+use std::{fmt::Write, thread::ThreadId};
+
+use crate::Schedule;
+
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for c in name.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Schedule {
+    /// Returns the systems which ran during the most recent execution in the
+    /// JSON trace event format of chrome://tracing, which can also be opened
+    /// in speedscope or perfetto.
+    ///
+    /// Each system is a complete event on the thread it ran on, with
+    /// timestamps relative to the start of the first system. Systems which are
+    /// not timed, see [set_timing_window](Self::set_timing_window), are
+    /// omitted.
+    pub fn trace_json(&self) -> String {
+        let mut out = String::new();
+        self.write_trace(&mut out)
+            .expect("Writing to a string does not fail");
+        out
+    }
+
+    fn write_trace(&self, out: &mut String) -> std::fmt::Result {
+        let timings = self.timings();
+        let spans = timings
+            .iter()
+            .filter_map(|timing| Some((timing.name(), timing.span()?)));
+
+        let last = spans.clone().map(|(_, span)| span.run).max();
+        let spans: Vec<_> = spans.filter(|(_, span)| Some(span.run) == last).collect();
+
+        let origin = spans.iter().map(|(_, span)| span.start).min();
+        let mut threads: Vec<ThreadId> = Vec::new();
+
+        write!(out, "{{\"traceEvents\":[")?;
+
+        for (i, (name, span)) in spans.iter().enumerate() {
+            let tid = match threads.iter().position(|&thread| thread == span.thread) {
+                Some(tid) => tid,
+                None => {
+                    threads.push(span.thread);
+                    threads.len() - 1
+                }
+            };
+
+            let origin = origin.expect("Origin exists if there are spans");
+            let ts = span.start.duration_since(origin).as_secs_f64() * 1e6;
+            let dur = span.end.duration_since(span.start).as_secs_f64() * 1e6;
+
+            if i > 0 {
+                write!(out, ",")?;
+            }
+
+            write!(
+                out,
+                "{{\"name\":\"{}\",\"cat\":\"system\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":0,\"tid\":{}}}",
+                escape(name),
+                ts,
+                dur,
+                tid
+            )?;
+        }
+
+        write!(out, "],\"displayTimeUnit\":\"ms\"}}")
+    }
+}
@@ -0,0 +1,66 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+};
+
+use crate::{borrow::Borrows, Access};
+
+/// Records the components accessed by the [TrackedAccess](crate::TrackedAccess)
+/// subworlds of a system while it runs
+#[derive(Default)]
+pub(crate) struct AccessLog {
+    accessed: Mutex<Borrows>,
+}
+
+impl AccessLog {
+    pub(crate) fn record(&self, access: Access) {
+        let mut accessed = self.accessed.lock().expect("Access log was poisoned");
+
+        match accessed.iter_mut().find(|other| other.id() == access.id()) {
+            Some(other) => other.exclusive |= access.exclusive(),
+            None => accessed.push(access),
+        }
+    }
+
+    /// Removes all recorded accesses
+    pub(crate) fn take(&self) -> Borrows {
+        std::mem::take(&mut *self.accessed.lock().expect("Access log was poisoned"))
+    }
+}
+
+/// Counts the writes to the data watched by
+/// [run_if_changed](crate::ScheduleBuilder::run_if_changed)
+#[derive(Default)]
+pub(crate) struct ChangeTicks {
+    // Boxed and never removed, so references to the ticks outlive the guard
+    ticks: RwLock<HashMap<TypeId, Box<AtomicU64>>>,
+}
+
+impl ChangeTicks {
+    /// Starts counting the writes to `id`, and returns the current tick
+    pub(crate) fn watch(&self, id: TypeId) -> u64 {
+        if let Some(tick) = self.get(id) {
+            return tick.load(Ordering::Acquire);
+        }
+
+        self.ticks
+            .write()
+            .expect("Change ticks were poisoned")
+            .entry(id)
+            .or_default()
+            .load(Ordering::Acquire)
+    }
+
+    /// Returns the tick of `id`, if it is watched
+    pub(crate) fn get(&self, id: TypeId) -> Option<&AtomicU64> {
+        let ticks = self.ticks.read().expect("Change ticks were poisoned");
+        let tick: *const AtomicU64 = &**ticks.get(&id)?;
+
+        // The box is never dropped or moved while self is alive
+        Some(unsafe { &*tick })
+    }
+}
@@ -0,0 +1,148 @@
+use std::{any::TypeId, collections::HashSet};
+
+use thiserror::Error;
+
+use moss_hecs::Frame;
+
+use crate::{
+    planner::{find_cycle, Node},
+    Access, DynamicSystem,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+/// A problem with a schedule which is detected before it is built
+pub enum ValidationError {
+    /// Several systems were added with the same name
+    #[error("Multiple systems are named {0:?}")]
+    DuplicateName(String),
+
+    /// The ordering constraints of the systems form a cycle
+    #[error("Ordering constraints form a cycle between: {0:?}")]
+    Cycle(Vec<String>),
+
+    /// A system is ordered relative to a label which no system has
+    #[error("System {system:?} is ordered relative to {label:?}, which no system is labeled with")]
+    UnknownLabel {
+        /// The name of the system
+        system: String,
+        /// The unknown label
+        label: String,
+    },
+
+    /// A system requires data which is not provided to the execution
+    #[error("System {system:?} requires data of type {data:?}, which is not provided")]
+    MissingData {
+        /// The name of the system
+        system: String,
+        /// The typename of the data
+        data: &'static str,
+    },
+
+    /// A system declares a query which it can not access
+    #[error("System {system:?} declares the query {query:?}, which is not a subset of its access")]
+    IncompatibleQuery {
+        /// The name of the system
+        system: String,
+        /// The typename of the query
+        query: &'static str,
+    },
+}
+
+fn systems(nodes: &[Node]) -> impl Iterator<Item = &DynamicSystem> {
+    nodes.iter().filter_map(|node| match node {
+        Node::System(system) => Some(system),
+        Node::Barrier => None,
+    })
+}
+
+/// Validates the main and startup nodes of a schedule.
+///
+/// Data is only validated if `provided` is not empty.
+pub(crate) fn validate(
+    nodes: &[Node],
+    startup: &[Node],
+    provided: &[TypeId],
+) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    // Only systems added by name have a meaningful name
+    let mut names = HashSet::new();
+    for system in systems(nodes).chain(systems(startup)) {
+        if system.has_label(system.name()) && !names.insert(system.name()) {
+            errors.push(ValidationError::DuplicateName(system.name().to_string()));
+        }
+    }
+
+    for nodes in [nodes, startup] {
+        for system in systems(nodes) {
+            for label in system.before.iter().chain(&system.after) {
+                if !systems(nodes).any(|other| other.has_label(label)) {
+                    errors.push(ValidationError::UnknownLabel {
+                        system: system.name().to_string(),
+                        label: label.to_string(),
+                    });
+                }
+            }
+        }
+
+        if let Some(cycle) = find_cycle(nodes) {
+            errors.push(ValidationError::Cycle(cycle));
+        }
+    }
+
+    errors.extend(validate_queries(nodes, startup));
+
+    if !provided.is_empty() {
+        for system in systems(nodes).chain(systems(startup)) {
+            for (id, name) in system
+                .borrows
+                .iter()
+                .filter(|access| !access.optional())
+                .filter_map(|access| access.data())
+            {
+                if !provided.contains(&id) {
+                    errors.push(ValidationError::MissingData {
+                        system: system.name().to_string(),
+                        data: name,
+                    });
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+/// Validates that the declared queries of each system are accessible by it
+pub(crate) fn validate_queries(nodes: &[Node], startup: &[Node]) -> Vec<ValidationError> {
+    let mut errors = Vec::new();
+
+    for system in systems(nodes).chain(systems(startup)) {
+        for (query, borrows) in &system.queries {
+            if !borrows
+                .iter()
+                .all(|access| permits(&system.borrows, access))
+            {
+                errors.push(ValidationError::IncompatibleQuery {
+                    system: system.name().to_string(),
+                    query,
+                });
+            }
+        }
+    }
+
+    errors
+}
+
+// Exclusive access to the frame permits any component access, otherwise the
+// component must be accessed in the same world, rather than as data
+fn permits(borrows: &[Access], access: &Access) -> bool {
+    borrows.iter().any(|other| {
+        let frame =
+            other.id() == TypeId::of::<Frame>() && other.exclusive() && other.world().is_none();
+        let component =
+            other.data().is_none() && other.id() == access.id() && other.world() == access.world();
+
+        (frame || component) && (other.exclusive() || !access.exclusive())
+    })
+}
@@ -0,0 +1,2776 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use moss_hecs::Frame;
+use moss_hecs_schedule::*;
+
+#[test]
+fn ordering() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let render = {
+        let log = log.clone();
+        move || log.lock().unwrap().push("render")
+    };
+
+    let input = {
+        let log = log.clone();
+        move || log.lock().unwrap().push("input")
+    };
+
+    let physics = {
+        let log = log.clone();
+        move || log.lock().unwrap().push("physics")
+    };
+
+    let mut schedule = Schedule::builder()
+        .add_system_named("render", render)
+        .add_system_named("physics", physics)
+        .before("render")
+        .after("input")
+        .add_system_named("input", input)
+        .build();
+
+    schedule.execute_seq(()).unwrap();
+
+    assert_eq!(*log.lock().unwrap(), ["input", "physics", "render"]);
+}
+
+#[test]
+fn run_if() {
+    struct GameState {
+        paused: bool,
+    }
+
+    let mut state = GameState { paused: true };
+    let mut ticks = 0_u32;
+
+    let mut schedule = Schedule::builder()
+        .add_system(|mut ticks: Write<u32>| *ticks += 1)
+        .run_if(|state: Read<GameState>| !state.paused)
+        .build();
+
+    schedule.execute_seq((&mut state, &mut ticks)).unwrap();
+    assert_eq!(ticks, 0);
+
+    state.paused = false;
+    schedule.execute_seq((&mut state, &mut ticks)).unwrap();
+    assert_eq!(ticks, 1);
+}
+
+#[test]
+fn exclusive() {
+    let mut frame = Frame::default();
+    let entity = frame.spawn((1_i32,));
+
+    let mut schedule = Schedule::builder()
+        .add_system(|w: SubWorld<&i32>| assert_eq!(w.query::<&i32>().iter().count(), 1))
+        .add_exclusive_system(move |frame: &mut Frame| {
+            frame.despawn(entity).unwrap();
+        })
+        .add_system(|w: SubWorld<&i32>| assert_eq!(w.query::<&i32>().iter().count(), 0))
+        .build();
+
+    schedule.execute((&mut frame,)).unwrap();
+}
+
+#[test]
+fn fixed_timestep() {
+    let mut steps = 0_u32;
+
+    let inner = Schedule::builder()
+        .add_system(|mut steps: Write<u32>| *steps += 1)
+        .build();
+
+    let mut fixed = FixedTimestep::new(inner, Duration::from_secs(1));
+
+    fixed.advance(Duration::from_millis(3500));
+    assert_eq!(fixed.execute_seq((&mut steps,)).unwrap(), 3);
+    assert_eq!(steps, 3);
+
+    assert_eq!(fixed.execute_seq((&mut steps,)).unwrap(), 0);
+    assert_eq!(steps, 3);
+}
+
+#[test]
+fn startup() {
+    let mut runs = 0_u32;
+
+    let mut schedule = Schedule::builder()
+        .add_system(|runs: Read<u32>| assert_ne!(*runs, 0))
+        .add_startup_system(|mut runs: Write<u32>| *runs += 1)
+        .build();
+
+    schedule.execute_seq((&mut runs,)).unwrap();
+    schedule.execute_seq((&mut runs,)).unwrap();
+
+    assert_eq!(runs, 1);
+}
+
+#[test]
+fn startup_retry() {
+    let mut runs = 0_u32;
+
+    let mut schedule = Schedule::builder()
+        .add_startup_system(|mut runs: Write<u32>| -> anyhow::Result<()> {
+            *runs += 1;
+            anyhow::ensure!(*runs > 1, "First attempt");
+            Ok(())
+        })
+        .build();
+
+    assert!(schedule.execute_seq((&mut runs,)).is_err());
+    schedule.execute_seq((&mut runs,)).unwrap();
+    schedule.execute_seq((&mut runs,)).unwrap();
+
+    assert_eq!(runs, 2);
+}
+
+#[test]
+fn flush_barrier() {
+    let mut frame = Frame::default();
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let spawn = {
+        let log = log.clone();
+        move |mut cmd: Write<CommandBuffer>| {
+            cmd.spawn((5_i32,));
+            log.lock().unwrap().push("spawn");
+        }
+    };
+
+    let unrelated = {
+        let log = log.clone();
+        move || log.lock().unwrap().push("unrelated")
+    };
+
+    let mut schedule = Schedule::builder()
+        .add_system(spawn)
+        .flush()
+        .add_system(unrelated)
+        .add_system(|w: SubWorld<&i32>| assert_eq!(w.query::<&i32>().iter().count(), 1))
+        .build();
+
+    schedule.execute((&mut frame,)).unwrap();
+
+    assert_eq!(*log.lock().unwrap(), ["spawn", "unrelated"]);
+}
+
+#[test]
+fn system_set() {
+    struct Enabled(bool);
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let logger = |name: &'static str| {
+        let log = log.clone();
+        move || log.lock().unwrap().push(name)
+    };
+
+    let mut schedule = Schedule::builder()
+        .add_system_named("last", logger("last"))
+        .after("group")
+        .add_set(
+            SystemSet::new()
+                .add_system(logger("a"))
+                .add_system(logger("b"))
+                .label("group")
+                .run_if(|enabled: Read<Enabled>| enabled.0),
+        )
+        .build();
+
+    let mut enabled = Enabled(false);
+    schedule.execute_seq((&mut enabled,)).unwrap();
+    assert_eq!(*log.lock().unwrap(), ["last"]);
+
+    log.lock().unwrap().clear();
+    enabled.0 = true;
+    schedule.execute_seq((&mut enabled,)).unwrap();
+    assert_eq!(*log.lock().unwrap(), ["a", "b", "last"]);
+}
+
+#[test]
+fn dynamic_systems() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let logger = |name: &'static str| {
+        let log = log.clone();
+        move || log.lock().unwrap().push(name)
+    };
+
+    let mut schedule = Schedule::builder()
+        .add_system_named("a", logger("a"))
+        .build();
+
+    schedule.add_system_dynamic_named("b", logger("b"));
+
+    schedule.execute_seq(()).unwrap();
+    assert_eq!(*log.lock().unwrap(), ["a", "b"]);
+
+    log.lock().unwrap().clear();
+    assert_eq!(schedule.remove_system("a"), 1);
+    assert_eq!(schedule.remove_system("a"), 0);
+
+    schedule.execute_seq(()).unwrap();
+    assert_eq!(*log.lock().unwrap(), ["b"]);
+}
+
+#[test]
+fn enable_disable() {
+    let mut ticks = 0_u32;
+
+    let mut schedule = Schedule::builder()
+        .add_system_named("debug_overlay", |mut ticks: Write<u32>| *ticks += 1)
+        .build();
+
+    assert_eq!(schedule.set_enabled("debug_overlay", false), 1);
+    schedule.execute_seq((&mut ticks,)).unwrap();
+    assert_eq!(ticks, 0);
+
+    assert_eq!(schedule.set_enabled("debug_overlay", true), 1);
+    schedule.execute_seq((&mut ticks,)).unwrap();
+    assert_eq!(ticks, 1);
+
+    assert_eq!(schedule.set_enabled("missing", true), 0);
+}
+
+#[test]
+fn batch_descriptions() {
+    let mut schedule = Schedule::builder()
+        .add_system(|_: Write<u32>| {})
+        .add_system(|_: Read<u32>| {})
+        .add_system(|_: Read<f32>| {})
+        .build();
+
+    let batches = schedule.batches();
+    assert_eq!(batches.len(), 3);
+
+    assert_eq!(batches[0].index(), 0);
+    assert_eq!(batches[0].systems().len(), 1);
+    assert_eq!(batches[0].systems()[0].writes().count(), 1);
+    assert_eq!(batches[0].systems()[0].reads().count(), 0);
+
+    assert_eq!(batches[1].systems().len(), 2);
+    assert!(batches[1]
+        .systems()
+        .iter()
+        .all(|system| system.writes().count() == 0));
+
+    assert!(batches[2].has_flush());
+
+    drop(batches);
+    schedule.execute_seq((&mut 0_u32, &mut 0.0_f32)).unwrap();
+}
+
+#[test]
+fn dot() {
+    let schedule = Schedule::builder()
+        .add_system(|_: Write<u32>| {})
+        .add_system(|_: Read<u32>| {})
+        .build();
+
+    let dot = schedule.to_dot();
+
+    assert!(dot.starts_with("digraph schedule {"));
+    assert!(dot.contains("subgraph cluster_1"));
+    assert!(dot.contains("label=\"write\""));
+    assert!(dot.contains("label=\"read\", style=dashed"));
+}
+
+#[test]
+fn timings() {
+    let mut schedule = Schedule::builder()
+        .add_system_named("sleep", || std::thread::sleep(Duration::from_millis(1)))
+        .build();
+
+    schedule.set_timing_window(2);
+
+    for _ in 0..3 {
+        schedule.execute_seq(()).unwrap();
+    }
+
+    let timings = schedule.timings();
+    let sleep = timings.iter().find(|t| t.name() == "sleep").unwrap();
+
+    assert_eq!(sleep.len(), 2);
+    assert!(sleep.min().unwrap() >= Duration::from_millis(1));
+    assert!(sleep.min() <= sleep.average());
+    assert!(sleep.average() <= sleep.max());
+}
+
+#[test]
+fn error_policy() {
+    let fail = |name: &'static str| move || -> anyhow::Result<()> { Err(anyhow::anyhow!(name)) };
+
+    let mut schedule = Schedule::builder()
+        .add_system(fail("a"))
+        .add_system(fail("b"))
+        .error_policy(ErrorPolicy::CollectAll)
+        .build();
+
+    match schedule.execute_seq(()) {
+        Err(Error::Multiple(errors)) => assert_eq!(errors.len(), 2),
+        other => panic!("Expected multiple errors, got {:?}", other),
+    }
+
+    let mut schedule = Schedule::builder()
+        .add_system(fail("a"))
+        .add_system(fail("b"))
+        .build();
+
+    assert!(matches!(
+        schedule.execute_seq(()),
+        Err(Error::SystemError(..))
+    ));
+}
+
+#[test]
+fn catch_panics() {
+    let mut schedule = Schedule::builder()
+        .add_system_named("panics", || panic!("Oh no"))
+        .catch_panics(true)
+        .build();
+
+    match schedule.execute_seq(()) {
+        Err(Error::SystemPanic { name, payload }) => {
+            assert_eq!(name, "panics");
+            assert_eq!(payload, "Oh no");
+        }
+        other => panic!("Expected a panic error, got {:?}", other),
+    }
+}
+
+#[test]
+fn retry() {
+    let mut attempts = 0;
+    let flaky = move || -> anyhow::Result<()> {
+        attempts += 1;
+        if attempts < 3 {
+            Err(anyhow::anyhow!("Transient failure"))
+        } else {
+            Ok(())
+        }
+    };
+
+    let mut schedule = Schedule::builder()
+        .add_system(flaky)
+        .with_retry(RetryPolicy::times(2).backoff(Duration::from_millis(1)))
+        .build();
+
+    schedule.execute_seq(()).unwrap();
+
+    let mut schedule = Schedule::builder()
+        .add_system(|| -> anyhow::Result<()> { Err(anyhow::anyhow!("Permanent failure")) })
+        .with_retry(RetryPolicy::times(2))
+        .build();
+
+    assert!(schedule.execute_seq(()).is_err());
+}
+
+#[test]
+fn deterministic() {
+    let run = || {
+        let mut log = Vec::new();
+
+        let mut schedule = Schedule::builder()
+            .add_system(|mut log: Write<Vec<u32>>| log.push(1))
+            .add_system(|mut log: Write<Vec<u32>>| log.push(2))
+            .add_system_named("first", |mut log: Write<Vec<u32>>| log.push(3))
+            .before("second")
+            .add_system_named("second", |mut log: Write<Vec<u32>>| log.push(4))
+            .build();
+
+        schedule.execute_deterministic((&mut log,)).unwrap();
+        schedule.execute_deterministic((&mut log,)).unwrap();
+        log
+    };
+
+    assert_eq!(run(), [1, 2, 3, 4, 1, 2, 3, 4]);
+    assert_eq!(run(), run());
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn thread_pool() {
+    let pool = Arc::new(
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap(),
+    );
+
+    let mut schedule = Schedule::builder()
+        .add_system(|| assert_eq!(rayon::current_thread_index(), Some(0)))
+        .add_system(|| assert_eq!(rayon::current_num_threads(), 1))
+        .thread_pool(pool)
+        .build();
+
+    schedule.execute(()).unwrap();
+
+    let mut schedule = Schedule::builder()
+        .add_system(|| assert_eq!(rayon::current_num_threads(), 3))
+        .num_threads(3)
+        .build();
+
+    schedule.execute(()).unwrap();
+}
+
+async fn increment(mut value: Write<'_, u32>) -> anyhow::Result<()> {
+    *value += 1;
+    Ok(())
+}
+
+#[test]
+fn async_systems() {
+    use std::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Wake, Waker},
+    };
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let mut schedule = Schedule::builder()
+        .add_async_system(increment)
+        .add_async_system(increment)
+        .build();
+
+    let mut value = 0_u32;
+    schedule.execute_seq((&mut value,)).unwrap();
+    assert_eq!(value, 2);
+
+    {
+        let waker = Waker::from(Arc::new(Noop));
+        let mut future = pin!(schedule.execute_async((&mut value,)));
+
+        while future
+            .as_mut()
+            .poll(&mut Context::from_waker(&waker))
+            .map(Result::unwrap)
+            .is_pending()
+        {}
+    }
+
+    assert_eq!(value, 4);
+}
+
+#[test]
+fn nested_schedule() {
+    let inner = Schedule::builder()
+        .add_system(|mut value: Write<u32>| *value += 1)
+        .add_system(|mut value: Write<u32>| *value *= 2)
+        .build();
+
+    let mut schedule = Schedule::builder()
+        .add_schedule(inner)
+        .add_system(|_: Read<f32>| {})
+        .add_system(|value: Read<u32>| assert_eq!(*value, 2))
+        .build();
+
+    let batches = schedule.batches();
+    assert_eq!(batches[0].systems().len(), 2);
+    assert_eq!(batches[1].systems().len(), 1);
+
+    schedule.execute((&mut 0_u32, &mut 0.0_f32)).unwrap();
+}
+
+#[test]
+fn merge() {
+    fn plugin() -> ScheduleBuilder {
+        let mut builder = ScheduleBuilder::new();
+        builder
+            .add_system_named("double", |mut value: Write<u32>| *value *= 2)
+            .after("increment");
+        builder
+    }
+
+    let mut builder = ScheduleBuilder::new();
+    builder.add_system_named("increment", |mut value: Write<u32>| *value += 1);
+
+    let mut schedule = plugin().merge(builder).build();
+
+    let mut value = 1_u32;
+    schedule.execute_seq((&mut value,)).unwrap();
+    assert_eq!(value, 4);
+}
+
+#[test]
+fn execute_until() {
+    struct Converged;
+
+    let mut schedule = Schedule::builder()
+        .add_system(
+            |mut value: Write<u32>, mut converged: Write<Option<Converged>>| {
+                *value += 1;
+                if *value == 3 {
+                    *converged = Some(Converged);
+                }
+            },
+        )
+        .build();
+
+    let mut value = 0_u32;
+    let mut converged = None;
+
+    let runs = schedule
+        .execute_until((&mut value, &mut converged), |context| {
+            context
+                .borrow::<Read<Option<Converged>>>()
+                .map_or(false, |converged| converged.is_some())
+        })
+        .unwrap();
+
+    assert_eq!(runs, 3);
+    assert_eq!(value, 3);
+}
+
+#[test]
+fn priority() {
+    let schedule = Schedule::builder()
+        .add_system_named("light", || {})
+        .add_system_named("heavy", || {})
+        .priority(10)
+        .build();
+
+    let batches = schedule.batches();
+    let names: Vec<_> = batches[0].systems().iter().map(|s| s.name()).collect();
+
+    assert_eq!(names, ["heavy", "light"]);
+}
+
+impl_into_data! {
+    struct TestContext<'a> {
+        frame: &'a mut Frame,
+        value: &'a mut u32,
+    }
+}
+
+#[test]
+fn context_struct() {
+    let mut schedule = Schedule::builder()
+        .add_system(|mut value: Write<u32>| *value += 1)
+        .add_system(|mut cmd: Write<CommandBuffer>| cmd.spawn((5_i32,)))
+        .build();
+
+    let mut frame = Frame::default();
+    let mut value = 0;
+
+    schedule
+        .execute_seq(&mut TestContext {
+            frame: &mut frame,
+            value: &mut value,
+        })
+        .unwrap();
+
+    assert_eq!(value, 1);
+    assert_eq!(frame.len(), 1);
+}
+
+#[test]
+fn resources() {
+    let mut schedule = Schedule::builder()
+        .add_system(|mut value: Write<u32>, name: Read<&'static str>| *value += name.len() as u32)
+        .add_system(|mut cmd: Write<CommandBuffer>| cmd.spawn((5_i32,)))
+        .build();
+
+    let mut resources = Resources::new();
+    resources.insert(Frame::default());
+    resources.insert(1_u32);
+    resources.insert("name");
+
+    schedule.execute(&mut resources).unwrap();
+
+    assert_eq!(resources.get::<u32>(), Some(&5));
+    assert_eq!(resources.get::<Frame>().unwrap().len(), 1);
+    assert_eq!(resources.remove::<u32>(), Some(5));
+    assert!(!resources.contains::<u32>());
+}
+
+#[test]
+fn hooks() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let mut schedule = Schedule::builder()
+        .add_system_named("a", || {})
+        .add_system_named("b", || {})
+        .run_if(|| false)
+        .with_hook({
+            let log = log.clone();
+            move |info: &SystemInfo, phase: HookPhase| {
+                if info.name() != "a" && info.name() != "b" {
+                    return;
+                }
+
+                log.lock().unwrap().push((info.name().to_string(), phase))
+            }
+        })
+        .build();
+
+    schedule.execute_seq(()).unwrap();
+
+    assert_eq!(
+        *log.lock().unwrap(),
+        [
+            ("a".to_string(), HookPhase::Before),
+            ("a".to_string(), HookPhase::After)
+        ]
+    );
+}
+
+#[test]
+fn timeout() {
+    async fn pending() {
+        std::future::pending::<()>().await
+    }
+
+    let mut schedule = Schedule::builder()
+        .add_async_system(pending)
+        .timeout(Duration::from_millis(1))
+        .build();
+
+    assert!(matches!(
+        schedule.execute_seq(()),
+        Err(Error::SystemTimeout { .. })
+    ));
+
+    let mut schedule = Schedule::builder()
+        .add_system_named("slow", || std::thread::sleep(Duration::from_millis(2)))
+        .timeout(Duration::from_millis(1))
+        .build();
+
+    schedule.execute_seq(()).unwrap();
+
+    let timings = schedule.timings();
+    let slow = timings.iter().find(|t| t.name() == "slow").unwrap();
+    assert_eq!(slow.overruns(), 1);
+}
+
+#[test]
+fn budgeted() {
+    let sleep = |mut count: Write<u32>| {
+        std::thread::sleep(Duration::from_millis(2));
+        *count += 1;
+    };
+
+    let mut schedule = Schedule::builder()
+        .add_system(sleep)
+        .add_system(sleep)
+        .add_system(sleep)
+        .build();
+
+    let mut count = 0_u32;
+
+    // Three batches of systems, followed by the flush
+    assert!(!schedule
+        .execute_seq_budgeted((&mut count,), Duration::from_millis(1))
+        .unwrap());
+    assert_eq!(count, 1);
+
+    assert!(!schedule
+        .execute_seq_budgeted((&mut count,), Duration::from_millis(1))
+        .unwrap());
+    assert_eq!(count, 2);
+
+    assert!(schedule
+        .execute_seq_budgeted((&mut count,), Duration::from_secs(10))
+        .unwrap());
+    assert_eq!(count, 3);
+}
+
+#[test]
+fn pipelined() {
+    let mut schedule = Schedule::builder()
+        .add_system(|mut count: Write<u32>| *count += 1)
+        .add_system(|mut log: Write<Vec<u32>>, count: Read<u32>| log.push(*count))
+        .build();
+
+    let mut count = 0_u32;
+    let mut log = Vec::new();
+
+    schedule
+        .execute_pipelined((&mut count, &mut log), 3)
+        .unwrap();
+
+    assert_eq!(count, 3);
+    assert_eq!(log, [1, 2, 3]);
+}
+
+#[test]
+fn pipelined_no_runs() {
+    let mut schedule = Schedule::builder()
+        .add_startup_system(|mut count: Write<u32>| *count += 1)
+        .build();
+
+    let mut count = 0_u32;
+
+    schedule.execute_pipelined((&mut count,), 0).unwrap();
+    assert_eq!(count, 0);
+
+    schedule.execute_pipelined((&mut count,), 1).unwrap();
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn validation() {
+    let errors = Schedule::builder()
+        .add_system_named("a", || {})
+        .after("b")
+        .add_system_named("a", || {})
+        .add_system_named("b", |_: Read<u32>| {})
+        .after("a")
+        .add_system_named("c", || {})
+        .before("missing")
+        .provides::<Frame>()
+        .validate();
+
+    assert!(errors.contains(&ValidationError::DuplicateName("a".into())));
+    assert!(errors.contains(&ValidationError::UnknownLabel {
+        system: "c".into(),
+        label: "missing".into(),
+    }));
+    assert!(errors
+        .iter()
+        .any(|error| matches!(error, ValidationError::Cycle(_))));
+    assert!(errors.contains(&ValidationError::MissingData {
+        system: "b".into(),
+        data: "u32",
+    }));
+
+    let schedule = Schedule::builder()
+        .add_system_named("a", |_: Read<u32>| {})
+        .provides::<u32>()
+        .try_build();
+
+    assert!(schedule.is_ok());
+}
+
+#[test]
+fn join() {
+    let mut a = Schedule::builder()
+        .add_system(|mut value: Write<u32>| *value += 1)
+        .build();
+
+    let mut b = Schedule::builder()
+        .add_system(|mut value: Write<f32>| *value += 1.0)
+        .build();
+
+    let mut c = Schedule::builder()
+        .add_system(|value: Read<u32>| assert_eq!(*value, 1))
+        .build();
+
+    let mut int = 0_u32;
+    let mut float = 0.0_f32;
+
+    Schedule::join(&mut [&mut a, &mut b], (&mut int, &mut float)).unwrap();
+    assert_eq!(int, 1);
+    assert_eq!(float, 1.0);
+
+    assert!(matches!(
+        Schedule::join(&mut [&mut a, &mut c], (&mut int, &mut float)),
+        Err(Error::ConflictingSchedules(0, 1))
+    ));
+}
+
+#[test]
+fn locals() {
+    let count = |mut count: Local<u32>, mut log: Write<Vec<u32>>| {
+        *count += 1;
+        log.push(*count);
+    };
+
+    let mut schedule = Schedule::builder()
+        .add_system(count)
+        .add_system(count)
+        .build();
+
+    let mut log = Vec::new();
+
+    schedule.execute_seq((&mut log,)).unwrap();
+    schedule.execute_seq((&mut log,)).unwrap();
+
+    assert_eq!(log, [1, 1, 2, 2]);
+}
+
+#[test]
+fn states() {
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum GameState {
+        Menu,
+        InGame,
+    }
+
+    let mut schedule = Schedule::builder()
+        .on_enter(GameState::Menu, |mut log: Write<Vec<&str>>| {
+            log.push("enter menu")
+        })
+        .on_update(GameState::Menu, |mut state: Write<State<GameState>>| {
+            state.set(GameState::InGame)
+        })
+        .on_exit(GameState::Menu, |mut log: Write<Vec<&str>>| {
+            log.push("exit menu")
+        })
+        .on_enter(GameState::InGame, |mut log: Write<Vec<&str>>| {
+            log.push("enter game")
+        })
+        .on_update(GameState::InGame, |mut log: Write<Vec<&str>>| {
+            log.push("update game")
+        })
+        .build();
+
+    let mut state = State::new(GameState::Menu);
+    let mut log = Vec::new();
+
+    schedule.execute_seq((&mut state, &mut log)).unwrap();
+    assert_eq!(log, ["enter menu"]);
+    assert_eq!(state.current(), &GameState::Menu);
+
+    schedule.execute_seq((&mut state, &mut log)).unwrap();
+    assert_eq!(
+        log,
+        ["enter menu", "exit menu", "enter game", "update game"]
+    );
+    assert_eq!(state.current(), &GameState::InGame);
+
+    schedule.execute_seq((&mut state, &mut log)).unwrap();
+    assert_eq!(
+        log,
+        [
+            "enter menu",
+            "exit menu",
+            "enter game",
+            "update game",
+            "update game"
+        ]
+    );
+}
+
+#[test]
+fn throttling() {
+    let mut schedule = Schedule::builder()
+        .add_system(|mut value: Write<u32>| *value += 1)
+        .every_nth(3)
+        .add_system(|mut value: Write<u64>| *value += 1)
+        .every(Duration::from_secs(3600))
+        .build();
+
+    let mut a = 0_u32;
+    let mut b = 0_u64;
+
+    for _ in 0..7 {
+        schedule.execute_seq((&mut a, &mut b)).unwrap();
+    }
+
+    assert_eq!(a, 3);
+    assert_eq!(b, 1);
+}
+
+#[test]
+fn trace() {
+    let mut schedule = Schedule::builder()
+        .add_system_named("first", |mut value: Write<u32>| *value += 1)
+        .add_system_named("second", |mut value: Write<u32>| *value += 1)
+        .build();
+
+    assert_eq!(
+        schedule.trace_json(),
+        "{\"traceEvents\":[],\"displayTimeUnit\":\"ms\"}"
+    );
+
+    let mut value = 0_u32;
+    schedule.execute_seq((&mut value,)).unwrap();
+
+    let trace = schedule.trace_json();
+    assert!(trace.starts_with("{\"traceEvents\":[{\"name\":\"first\""));
+    assert!(trace.contains("\"name\":\"second\""));
+    assert_eq!(trace.matches("\"ph\":\"X\"").count(), 2);
+    assert!(trace.contains("\"ts\":0.000"));
+}
+
+#[test]
+fn rebalance() {
+    let slow = Duration::from_millis(5);
+
+    let mut schedule = Schedule::builder()
+        .add_system_named("slow_a", move |_: Write<u32>| std::thread::sleep(slow))
+        .add_system_named("fast_a", |_: Write<u32>| {})
+        .add_system_named("fast_b", |_: Write<f32>| {})
+        .add_system_named("slow_b", move |_: Write<f32>| std::thread::sleep(slow))
+        .build();
+
+    let names = |schedule: &Schedule| -> Vec<Vec<String>> {
+        schedule
+            .batches()
+            .iter()
+            .map(|batch| {
+                batch
+                    .systems()
+                    .iter()
+                    .map(|system| system.name().to_string())
+                    .collect()
+            })
+            .collect()
+    };
+
+    let before = names(&schedule);
+    assert_eq!(
+        before[..3],
+        [vec!["slow_a"], vec!["fast_a", "fast_b"], vec!["slow_b"]]
+    );
+
+    let mut a = 0_u32;
+    let mut b = 0.0_f32;
+    schedule.execute_seq((&mut a, &mut b)).unwrap();
+    schedule.rebalance();
+
+    let after = names(&schedule);
+    assert_eq!(after.len(), before.len() - 1);
+    assert_eq!(
+        after[..2],
+        [vec!["slow_a", "fast_b"], vec!["fast_a", "slow_b"]]
+    );
+
+    schedule.execute_seq((&mut a, &mut b)).unwrap();
+}
+
+#[test]
+fn registry() {
+    let mut registry = SystemRegistry::new();
+    registry
+        .register("increment", |mut value: Write<u32>| *value += 1)
+        .register("double", |mut value: Write<u32>| *value *= 2);
+
+    assert_eq!(registry.len(), 2);
+    assert!(registry.borrows("double").unwrap()[0].exclusive());
+
+    let mut schedule = registry.build(["increment", "double"]).unwrap();
+    let mut value = 1_u32;
+    schedule.execute_seq((&mut value,)).unwrap();
+    assert_eq!(value, 4);
+
+    registry.register("double", |mut value: Write<u32>| *value *= 3);
+    let mut schedule = registry.build(["double", "increment"]).unwrap();
+    schedule.execute_seq((&mut value,)).unwrap();
+    assert_eq!(value, 13);
+
+    assert!(matches!(
+        registry.build(["missing"]),
+        Err(Error::UnknownSystem(name)) if name == "missing"
+    ));
+}
+
+#[test]
+fn execute_seq_all() {
+    let mut schedule = Schedule::builder()
+        .add_system_named("fail", || -> anyhow::Result<()> {
+            Err(anyhow::anyhow!("Failure"))
+        })
+        .add_system_named("succeed", |mut value: Write<u32>| *value += 1)
+        .add_system_named("missing", |_: Read<f32>| {})
+        .build();
+
+    let mut value = 0_u32;
+    let results = schedule.execute_seq_all((&mut value,));
+
+    let names: Vec<_> = results.iter().map(|(name, _)| name.as_ref()).collect();
+    assert_eq!(names[..3], ["fail", "succeed", "missing"]);
+
+    assert!(matches!(results[0].1, Err(Error::SystemError(..))));
+    assert!(results[1].1.is_ok());
+    assert!(matches!(results[2].1, Err(Error::MissingData(_))));
+    assert_eq!(value, 1);
+}
+
+#[test]
+fn deadline() {
+    let mut schedule = Schedule::builder()
+        .add_system_named("slow", |mut value: Write<u32>| {
+            std::thread::sleep(Duration::from_millis(20));
+            *value += 1;
+        })
+        .add_system_named("skipped", |mut value: Write<u32>| *value += 1)
+        .build();
+
+    let mut value = 0_u32;
+
+    let deadline = std::time::Instant::now() + Duration::from_millis(5);
+    let report = schedule
+        .execute_with_deadline((&mut value,), deadline)
+        .unwrap();
+
+    assert_eq!(value, 1);
+    assert_eq!(report.executed(), ["slow"]);
+    assert_eq!(report.skipped()[0], "skipped");
+    assert!(!report.met_deadline());
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(60);
+    let report = schedule
+        .execute_with_deadline((&mut value,), deadline)
+        .unwrap();
+
+    assert_eq!(value, 3);
+    assert!(report.met_deadline());
+}
+
+#[test]
+fn strategy() {
+    let build = |builder: &mut ScheduleBuilder| {
+        builder
+            .add_system_named("a", |_: Write<u32>| {})
+            .add_system_named("b", |_: Write<u32>| {})
+            .add_system_named("c", |_: Write<f32>| {})
+            .add_system_named("d", |_: Write<f32>| {})
+            .build()
+    };
+
+    let first_batch = |schedule: &Schedule| -> Vec<String> {
+        schedule.batches()[0]
+            .systems()
+            .iter()
+            .map(|system| system.name().to_string())
+            .collect()
+    };
+
+    let greedy = build(&mut Schedule::builder());
+    assert_eq!(first_batch(&greedy), ["a"]);
+
+    let minimal = build(Schedule::builder().with_strategy(strategy::MinimalBatches));
+    assert_eq!(first_batch(&minimal), ["a", "c"]);
+    assert_eq!(minimal.batches().len(), greedy.batches().len() - 1);
+}
+
+#[test]
+fn yielding() {
+    let mut schedule = Schedule::builder()
+        .add_system(|yielder: Yield, mut chunks: Write<Vec<u32>>| {
+            for chunk in 0..4 {
+                chunks.push(chunk);
+                yielder.now();
+            }
+        })
+        .add_system(|mut value: Write<u32>| *value += 1)
+        .build();
+
+    let mut chunks = Vec::new();
+    let mut value = 0_u32;
+
+    schedule.execute((&mut chunks, &mut value)).unwrap();
+    schedule.execute_seq((&mut chunks, &mut value)).unwrap();
+
+    assert_eq!(chunks, [0, 1, 2, 3, 0, 1, 2, 3]);
+    assert_eq!(value, 2);
+}
+
+struct RenderWorld(Frame);
+
+impl World for RenderWorld {
+    fn frame(&self) -> &Frame {
+        &self.0
+    }
+}
+
+#[test]
+fn multiple_worlds() {
+    let mut schedule = Schedule::builder()
+        .add_system(|w: SubWorld<&mut i32>| {
+            w.query::<&mut i32>().iter().for_each(|(_, val)| *val += 1)
+        })
+        .add_system(|w: SubWorld<&mut i32, Of<RenderWorld>>| {
+            w.query::<&mut i32>().iter().for_each(|(_, val)| *val *= 10)
+        })
+        .build();
+
+    // The systems access different worlds, and thus do not conflict
+    assert_eq!(schedule.batches()[0].systems().len(), 2);
+
+    let mut frame = Frame::default();
+    let main = frame.spawn((1_i32,));
+
+    let mut render = RenderWorld(Frame::default());
+    let rendered = render.0.spawn((1_i32,));
+
+    schedule.execute((&mut frame, &mut render)).unwrap();
+
+    assert_eq!(*frame.get::<&i32>(main).unwrap(), 2);
+    assert_eq!(*render.0.get::<&i32>(rendered).unwrap(), 10);
+}
+
+#[test]
+fn run_if_changed() {
+    struct Config {
+        scale: u32,
+    }
+
+    struct Enabled(bool);
+
+    let mut schedule = Schedule::builder()
+        .add_system(|mut config: Write<Config>, target: Read<u64>| {
+            // Only mutable accesses count as changes
+            if u64::from(config.scale) != *target {
+                config.scale = *target as u32;
+            }
+        })
+        .add_system(|config: Read<Config>, mut value: Write<u32>| *value += config.scale)
+        .run_if(|enabled: Read<Enabled>| enabled.0)
+        .run_if_changed::<Config>()
+        .build();
+
+    let mut config = Config { scale: 1 };
+    let mut target = 1_u64;
+    let mut enabled = Enabled(true);
+    let mut value = 0_u32;
+
+    schedule
+        .execute_seq((&mut config, &mut target, &mut enabled, &mut value))
+        .unwrap();
+    schedule
+        .execute_seq((&mut config, &mut target, &mut enabled, &mut value))
+        .unwrap();
+    assert_eq!(value, 1);
+
+    // Changes made outside of the schedule are not detected
+    config.scale = 2;
+    target = 2;
+    schedule
+        .execute_seq((&mut config, &mut target, &mut enabled, &mut value))
+        .unwrap();
+    assert_eq!(value, 1);
+
+    // A change is kept while the system is skipped by another condition
+    target = 5;
+    enabled.0 = false;
+    schedule
+        .execute_seq((&mut config, &mut target, &mut enabled, &mut value))
+        .unwrap();
+    assert_eq!(value, 1);
+
+    enabled.0 = true;
+    schedule
+        .execute_seq((&mut config, &mut target, &mut enabled, &mut value))
+        .unwrap();
+    schedule
+        .execute_seq((&mut config, &mut target, &mut enabled, &mut value))
+        .unwrap();
+    assert_eq!(value, 6);
+}
+
+#[test]
+fn error_attribution() {
+    let mut schedule = Schedule::builder()
+        .add_system_named("fails", || -> anyhow::Result<()> {
+            Err(anyhow::anyhow!("Failure"))
+        })
+        .add_system_named("borrows", |_: Read<f32>| {})
+        .error_policy(ErrorPolicy::CollectAll)
+        .build();
+
+    let mut value = 0_u32;
+    match schedule.execute((&mut value,)) {
+        Err(Error::Multiple(errors)) => {
+            let mut names: Vec<_> = errors.iter().filter_map(Error::system).collect();
+            names.sort_unstable();
+            assert_eq!(names, ["borrows", "fails"]);
+        }
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn introspection() {
+    let schedule = Schedule::builder()
+        .add_system_named("physics", |_: SubWorld<&mut i32>, _: Read<u32>| {})
+        .build();
+
+    let physics = schedule
+        .systems()
+        .find(|system| system.name() == "physics")
+        .unwrap();
+
+    let components: Vec<_> = physics.components().map(|access| access.name()).collect();
+    assert_eq!(components, ["&mut i32"]);
+    assert!(physics.components().all(|access| access.exclusive()));
+
+    let reads: Vec<_> = physics
+        .resource_reads()
+        .filter_map(|access| access.data())
+        .map(|(_, name)| name)
+        .collect();
+    assert!(reads.contains(&"u32"));
+    assert_eq!(physics.resource_writes().count(), 0);
+
+    // The final flush writes the commandbuffer
+    assert!(schedule
+        .systems()
+        .any(|system| system.resource_writes().next().is_some()));
+}
+
+#[test]
+fn declared_access() {
+    let schedule = Schedule::builder()
+        .add_system_with_access(|| {}, Access::writes::<u32>().reads_component::<i32>())
+        .add_system(|_: SubWorld<&i32>| {})
+        .add_system(|_: Read<u32>| {})
+        .build();
+
+    let batches = schedule.batches();
+    assert_eq!(batches[0].systems().len(), 2);
+    assert_eq!(batches[1].systems().len(), 1);
+
+    let declared = batches[0].systems()[0];
+    assert_eq!(declared.writes().count(), 1);
+    assert_eq!(declared.components().count(), 1);
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn main_thread() {
+    let main = std::thread::current().id();
+    let pool = Arc::new(
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap(),
+    );
+
+    let mut schedule = Schedule::builder()
+        .add_system(move || assert_eq!(std::thread::current().id(), main))
+        .on_main_thread()
+        .add_system(|| assert_eq!(rayon::current_num_threads(), 2))
+        .add_system(|| assert!(rayon::current_thread_index().is_some()))
+        .thread_pool(pool)
+        .build();
+
+    assert_eq!(schedule.batches()[0].systems().len(), 3);
+
+    for _ in 0..10 {
+        schedule.execute(()).unwrap();
+    }
+}
+
+#[test]
+fn warm_up() {
+    let mut schedule = Schedule::builder()
+        .add_startup_system(|mut value: Write<i32>| *value += 10)
+        .add_system(|mut value: Write<i32>| *value += 1)
+        .build();
+
+    schedule.warm_up();
+    schedule.warm_up();
+
+    assert!(schedule.timings().iter().all(|timing| timing.is_empty()));
+
+    let mut value = 0_i32;
+    for _ in 0..3 {
+        schedule.execute((&mut value,)).unwrap();
+    }
+
+    assert_eq!(value, 13);
+    assert_eq!(schedule.timings()[0].len(), 3);
+}
+
+#[test]
+fn query_filters() {
+    let mut frame = Frame::default();
+    frame.spawn((1_i32, 1.0_f32));
+    frame.spawn((2_i32,));
+    frame.spawn((3_i32, "c"));
+
+    let mut schedule = Schedule::builder()
+        .add_system(|w: SubWorld<&i32>| {
+            assert!(w.has_all::<With<&i32, &f32>>());
+            assert_eq!(w.query::<With<&i32, &f32>>().iter().count(), 1);
+            assert_eq!(w.query::<Without<&i32, &f32>>().iter().count(), 2);
+
+            let satisfied = w
+                .query::<(&i32, Satisfies<&f32>)>()
+                .iter()
+                .filter(|(_, (_, satisfied))| *satisfied)
+                .count();
+            assert_eq!(satisfied, 1);
+        })
+        .add_system(|w: SubWorld<&mut f32>| {
+            w.query::<&mut f32>()
+                .iter()
+                .for_each(|(_, val)| *val += 1.0)
+        })
+        .build();
+
+    assert_eq!(schedule.batches()[0].systems().len(), 2);
+
+    schedule.execute((&mut frame,)).unwrap();
+}
+
+#[test]
+fn contains() {
+    fn exists(world: &impl GenericWorld, entity: moss_hecs::Entity) -> bool {
+        world.contains(entity)
+    }
+
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32,));
+    let b = frame.spawn((2_i32,));
+    frame.despawn(b).unwrap();
+
+    assert!(exists(&frame, a));
+    assert!(!exists(&frame, b));
+
+    let mut schedule = Schedule::builder()
+        .add_system(move |w: SubWorld<()>| {
+            assert!(w.contains(a));
+            assert!(!w.contains(b));
+            assert!(exists(&w, a));
+        })
+        .build();
+
+    schedule.execute((&mut frame,)).unwrap();
+}
+
+#[test]
+#[cfg(feature = "parallel")]
+fn par_for_each_auto() {
+    use rayon::iter::ParallelIterator;
+
+    let mut frame = Frame::default();
+    for i in 0..1000 {
+        frame.spawn((i as i32,));
+    }
+
+    for i in 0..100 {
+        frame.spawn((i as i32, i as f32));
+    }
+
+    let mut schedule = Schedule::builder()
+        .add_system(|w: SubWorld<&mut i32>| w.par_for_each_auto::<&mut i32, _>(|_, val| *val += 1))
+        .add_system(|w: SubWorld<&i32>| {
+            let sum: i64 = w
+                .par_query::<&i32>()
+                .par_iter()
+                .map(|(_, val)| *val as i64)
+                .sum();
+
+            assert_eq!(sum, (1..=1000).sum::<i64>() + (1..=100).sum::<i64>());
+            assert_eq!(w.par_query::<&i32>().par_iter_batched(7).count(), 1100);
+        })
+        .build();
+
+    schedule.execute((&mut frame,)).unwrap();
+}
+
+#[test]
+fn prepared_query() {
+    let mut frame = Frame::default();
+    frame.spawn((1_i32,));
+    frame.spawn((2_i32, 1.0_f32));
+
+    let mut prepared = PreparedQuery::<&i32>::new();
+
+    let w = SubWorldRef::<&i32>::new(&frame);
+    for _ in 0..2 {
+        assert_eq!(w.prepared_query(&mut prepared).iter().count(), 2);
+    }
+
+    let w = SubWorldRef::<&f32>::new(&frame);
+    assert!(w.try_prepared_query(&mut prepared).is_err());
+}
+
+#[test]
+fn get_many() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32,));
+    let b = frame.spawn((2_i32,));
+    let c = frame.spawn((1.0_f32,));
+
+    let w = SubWorldRef::<&mut i32>::new(&frame);
+
+    let [x, y] = w.get_many::<i32, 2>([a, b]).unwrap();
+    assert_eq!((*x, *y), (1, 2));
+    drop((x, y));
+
+    assert!(matches!(
+        w.get_many::<i32, 2>([a, c]),
+        Err(Error::MissingComponent(entity, _)) if entity == c
+    ));
+
+    // Both entities are in the same archetype
+    w.get_many_mut([a, b], |[x, y]: [&mut i32; 2]| std::mem::swap(x, y))
+        .unwrap();
+
+    assert_eq!(*w.get::<i32>(a).unwrap(), 2);
+    assert_eq!(*w.get::<i32>(b).unwrap(), 1);
+    assert!(matches!(
+        w.get_many_mut([a, a], |_: [&mut i32; 2]| ()),
+        Err(Error::DuplicateEntity(entity)) if entity == a
+    ));
+    assert!(matches!(
+        w.get_many_mut([a, c], |_: [&mut i32; 2]| ()),
+        Err(Error::MissingComponent(entity, _)) if entity == c
+    ));
+}
+
+#[test]
+fn archetypes() {
+    use std::any::{type_name, TypeId};
+
+    let mut frame = Frame::default();
+    frame.spawn((1_i32,));
+    frame.spawn((2_i32,));
+    frame.spawn((3_i32, 1.0_f32));
+    frame.spawn(("a",));
+
+    let w = SubWorldRef::<&i32>::new(&frame);
+    let mut lens: Vec<_> = w.archetypes().map(|archetype| archetype.len()).collect();
+    lens.sort_unstable();
+    assert_eq!(lens, [1, 2]);
+
+    assert!(w.archetypes().all(|archetype| archetype.has::<i32>()
+        && archetype.accessible_types().eq([TypeId::of::<i32>()])));
+
+    let mut names: Vec<_> = w
+        .archetypes()
+        .flat_map(|archetype| archetype.named_types().collect::<Vec<_>>())
+        .map(|(_, name)| name)
+        .collect();
+    names.sort_unstable();
+    assert_eq!(
+        names,
+        [None, Some(type_name::<i32>()), Some(type_name::<i32>())]
+    );
+
+    let all = SubWorldRef::<AllAccess>::new(&frame);
+    assert_eq!(
+        all.archetypes()
+            .map(|archetype| archetype.len())
+            .sum::<u32>(),
+        4
+    );
+}
+
+#[test]
+fn satisfies() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, 1.0_f32));
+    let b = frame.spawn((2_i32,));
+
+    let w = SubWorldRef::<(&i32, &f32)>::new(&frame);
+    assert!(w.satisfies::<(&i32, &f32)>(a).unwrap());
+    assert!(!w.satisfies::<(&i32, &f32)>(b).unwrap());
+    assert!(w.satisfies::<With<&i32, &f32>>(a).unwrap());
+
+    assert!(matches!(
+        w.satisfies::<&mut i32>(a),
+        Err(Error::IncompatibleSubworld { .. })
+    ));
+
+    frame.despawn(b).unwrap();
+    let w = SubWorldRef::<&i32>::new(&frame);
+    assert!(matches!(w.satisfies::<&i32>(b), Err(Error::NoSuchEntity(entity)) if entity == b));
+}
+
+#[test]
+fn entity_ref() {
+    fn describe<T: borrow::ComponentBorrow>(entity: &SubEntityRef<T>) -> Result<i32, Error> {
+        entity.get::<i32>().map(|val| *val)
+    }
+
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, 1.0_f32));
+
+    let w = SubWorldRef::<&mut i32>::new(&frame);
+    let entity = w.entity(a).unwrap();
+
+    assert_eq!(entity.entity(), a);
+    assert!(entity.has::<f32>());
+    assert_eq!(describe(&entity).unwrap(), 1);
+
+    *entity.get_mut::<i32>().unwrap() += 1;
+    assert_eq!(*entity.get::<i32>().unwrap(), 2);
+
+    assert!(matches!(
+        entity.get::<f32>(),
+        Err(Error::IncompatibleSubworld { .. })
+    ));
+    assert!(matches!(
+        entity.get::<u8>(),
+        Err(Error::IncompatibleSubworld { .. })
+    ));
+    assert!(entity.satisfies::<&i32>().unwrap());
+}
+
+#[test]
+fn owned_subworld() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, 1.0_f32));
+
+    let w = SubWorldOwned::<(&i32, &f32)>::new(Arc::new(frame));
+    let split: SubWorldOwned<&i32> = w.split().unwrap();
+
+    let handle = std::thread::spawn(move || *split.get::<i32>(a).unwrap());
+    assert_eq!(handle.join().unwrap(), 1);
+
+    let frame = Arc::try_unwrap(w.into_inner()).ok().unwrap();
+    assert!(frame.contains(a));
+}
+
+#[test]
+fn dyn_subworld() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, 1.0_f32));
+
+    let access = DynAccess::new().read::<i32>().write::<f32>();
+
+    let system = {
+        let access = access.clone();
+        move |frame: Read<Frame>| {
+            let w = DynSubWorld::new(&frame, access.clone());
+
+            assert_eq!(*w.get::<i32>(a).unwrap(), 1);
+            assert!(w.get_mut::<i32>(a).is_err());
+            assert!(w.try_query::<&u8>().is_err());
+
+            w.query::<(&i32, &mut f32)>()
+                .iter()
+                .for_each(|(_, (x, y))| *y += *x as f32);
+        }
+    };
+
+    let mut schedule = Schedule::builder()
+        .add_system_with_access(system, access.access_set())
+        .add_system(|w: SubWorld<&i32>| assert_eq!(w.len(), 1))
+        .add_system(|w: SubWorld<&f32>| assert_eq!(*w.get::<f32>(a).unwrap(), 2.0))
+        .build();
+
+    assert_eq!(schedule.batches()[0].systems().len(), 2);
+
+    schedule.execute((&mut frame,)).unwrap();
+}
+
+#[test]
+fn try_widen() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, 1.0_f32));
+
+    let narrow = SubWorldRef::<&i32>::new(&frame);
+    let wide: SubWorldRef<(&i32, &mut f32)> = narrow.try_widen().unwrap();
+    *wide.get_mut::<f32>(a).unwrap() += 1.0;
+    assert_eq!(*wide.get::<f32>(a).unwrap(), 2.0);
+
+    let mut schedule = Schedule::builder()
+        .add_system(|w: SubWorld<(&i32, &f32)>| {
+            assert!(w.try_widen::<&i32>().is_ok());
+            assert!(matches!(
+                w.try_widen::<&mut f32>(),
+                Err(Error::IncompatibleSubworld { .. })
+            ));
+
+            // Converting to a reference does not permit widening either
+            let narrow: SubWorldRef<&i32> = w.to_ref();
+            assert!(matches!(
+                narrow.try_widen::<&mut f32>(),
+                Err(Error::IncompatibleSubworld { .. })
+            ));
+        })
+        .build();
+
+    schedule.execute((&mut frame,)).unwrap();
+}
+
+#[test]
+fn join_subworlds() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, 1.0_f32));
+    let other = Frame::default();
+
+    let x = SubWorldRef::<&i32>::new(&frame);
+    let y = SubWorldRef::<&mut f32>::new(&frame);
+
+    let joined = x.join(y).unwrap();
+    assert!(joined.has_all::<(&i32, &mut f32)>());
+    *joined.get_mut::<f32>(a).unwrap() += *joined.get::<i32>(a).unwrap() as f32;
+    assert_eq!(*joined.get::<f32>(a).unwrap(), 2.0);
+
+    let x = SubWorldRef::<&i32>::new(&frame);
+    let y = SubWorldRef::<&f32>::new(&other);
+    assert!(matches!(x.join(y), Err(Error::DifferentWorlds)));
+}
+
+#[test]
+fn intersect() {
+    use moss_hecs_schedule::borrow::ComponentBorrow;
+
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, 1.0_f32, "a"));
+
+    let w = SubWorldRef::<(&mut i32, &mut f32, &&str)>::new(&frame);
+    let common = w.intersect::<(&i32, &mut f32, &u8)>();
+
+    assert!(common.has::<&i32>());
+    assert!(!common.has::<&mut i32>());
+    assert!(common.has::<&mut f32>());
+    assert!(!common.has::<&&str>());
+    assert!(!common.has::<&u8>());
+    assert_eq!(
+        <Intersect<(&mut i32, &mut f32, &&str), (&i32, &mut f32, &u8)>>::borrows().len(),
+        2
+    );
+
+    let split: SubWorldRef<&mut f32> = common.split().unwrap();
+    *split.get_mut::<f32>(a).unwrap() += 1.0;
+    assert!(common.try_query::<&mut i32>().is_err());
+    assert_eq!(common.query::<&i32>().iter().count(), 1);
+}
+
+#[test]
+fn deferred() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32,));
+
+    let spawned = Arc::new(Mutex::new(None));
+
+    let mut schedule = Schedule::builder()
+        .add_system({
+            let spawned = spawned.clone();
+            move |w: SubWorld<&i32>| {
+                let entity = w.spawn_deferred((2_i32, "b")).unwrap();
+                w.despawn_deferred(a).unwrap();
+
+                assert_eq!(w.query::<&i32>().iter().count(), 1);
+                *spawned.lock().unwrap() = Some(entity);
+            }
+        })
+        .build();
+
+    schedule.execute((&mut frame,)).unwrap();
+
+    let b = spawned.lock().unwrap().unwrap();
+    assert!(!frame.contains(a));
+    assert_eq!(*frame.get::<&i32>(b).unwrap(), 2);
+
+    // The entity was despawned by the previous flush
+    match schedule.execute((&mut frame,)).unwrap_err() {
+        Error::SystemError(_, source) => assert!(matches!(
+            source.downcast_ref::<Error>(),
+            Some(Error::CommandsFailed(errors)) if errors == &[CommandError::DespawnFailed(1, a)]
+        )),
+        error => panic!("Unexpected error: {error}"),
+    }
+
+    let w = SubWorldRef::<&i32>::new(&frame);
+    assert!(matches!(w.spawn_deferred((3_i32,)), Err(Error::NoSchedule)));
+}
+
+#[test]
+fn reserve_entity() {
+    fn reserve(world: &impl GenericWorld) -> moss_hecs::Entity {
+        GenericWorld::reserve_entity(world)
+    }
+
+    let mut frame = Frame::default();
+
+    let w = SubWorldRef::<()>::new(&frame);
+    let a = w.reserve_entity();
+    let b = reserve(&w);
+    assert_ne!(a, b);
+
+    frame.insert_one(a, 1_i32).unwrap();
+    frame.insert_one(b, 2_i32).unwrap();
+    assert_eq!(*frame.get::<&i32>(b).unwrap(), 2);
+}
+
+#[test]
+fn component_names() {
+    let frame = Frame::default();
+
+    let w = SubWorldRef::<(&i32, Option<&mut f32>, &mut i32, Satisfies<&u8>)>::new(&frame);
+    let names = w.component_names();
+
+    assert_eq!(names.len(), 2);
+    assert_eq!(names[0].name(), std::any::type_name::<i32>());
+    assert!(names[0].mutable());
+    assert_eq!(names[1].id(), std::any::TypeId::of::<f32>());
+    assert!(names[1].mutable());
+}
+
+#[test]
+fn query_one_mut() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, 2.0_f32));
+    let b = frame.spawn((3_i32,));
+
+    let w = SubWorldRef::<(&mut i32, &f32)>::new(&frame);
+    let sum = w
+        .query_one_mut::<(&mut i32, &f32), _>(a, |(x, y)| {
+            *x += 1;
+            *x as f32 + *y
+        })
+        .unwrap();
+
+    assert_eq!(sum, 4.0);
+    assert_eq!(*w.get::<i32>(a).unwrap(), 2);
+    assert!(matches!(
+        w.query_one_mut::<(&mut i32, &f32), _>(b, |_| ()),
+        Err(Error::UnsatisfiedQuery(entity, _)) if entity == b
+    ));
+    assert!(matches!(
+        w.query_one_mut::<&mut f32, _>(a, |_| ()),
+        Err(Error::IncompatibleSubworld { .. })
+    ));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn serialize() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, String::from("a"), 1.0_f32));
+    let b = frame.spawn((2_i32,));
+    frame.spawn((3.0_f32,));
+
+    let mut registry = ComponentRegistry::new();
+    registry
+        .register::<i32>("i32")
+        .register::<String>("name")
+        .register::<f32>("f32");
+
+    let w = SubWorldRef::<(&i32, &String)>::new(&frame);
+
+    let mut bytes = Vec::new();
+    let mut serializer = serde_json::Serializer::new(&mut bytes);
+    w.serialize(&mut serializer, &registry).unwrap();
+
+    let value: serde_json::Value = serde_json::from_slice(&bytes).unwrap();
+    let a = &value[a.to_bits().get().to_string()];
+    let b = &value[b.to_bits().get().to_string()];
+
+    assert_eq!(value.as_object().unwrap().len(), 2);
+    assert_eq!(*a, serde_json::json!({ "i32": 1, "name": "a" }));
+    assert_eq!(*b, serde_json::json!({ "i32": 2 }));
+}
+
+#[test]
+fn clone_entity_into() {
+    let mut frame = Frame::default();
+    let prefab = frame.spawn((1_i32, String::from("prefab"), 1.0_f32));
+
+    let mut cmd = CommandBuffer::new();
+    let w = SubWorldRef::<(&i32, Option<&mut String>, Satisfies<&f32>)>::new(&frame);
+    let copy = w.clone_entity_into(prefab, &mut cmd).unwrap();
+
+    let missing = frame.reserve_entity();
+    frame.despawn(missing).unwrap();
+    assert!(matches!(
+        SubWorldRef::<&i32>::new(&frame).clone_entity_into(missing, &mut cmd),
+        Err(Error::NoSuchEntity(entity)) if entity == missing
+    ));
+
+    cmd.execute(&mut frame);
+
+    assert_ne!(copy, prefab);
+    assert_eq!(*frame.get::<&i32>(copy).unwrap(), 1);
+    assert_eq!(*frame.get::<&String>(copy).unwrap(), "prefab");
+    assert!(!frame.satisfies::<&f32>(copy).unwrap());
+}
+
+#[test]
+fn exclude() {
+    struct Frozen;
+
+    let mut frame = Frame::default();
+    frame.spawn((1_i32,));
+    frame.spawn((2_i32, Frozen));
+    frame.spawn((3_i32, 1.0_f32));
+
+    let mut schedule = Schedule::builder()
+        .add_system(|w: SubWorld<(&mut i32, Exclude<Frozen>)>| {
+            assert_eq!(w.query::<&i32>().iter().count(), 3);
+            assert!(!w.has::<&Frozen>());
+
+            for (_, val) in w.query_filtered::<&mut i32>().iter() {
+                *val *= 10;
+            }
+        })
+        .add_system(|w: SubWorld<Without<&i32, &f32>>| {
+            let vals = w
+                .query_filtered::<&i32>()
+                .iter()
+                .map(|(_, val)| *val)
+                .collect::<Vec<_>>();
+
+            assert!(!vals.contains(&30));
+            assert_eq!(vals.len(), 2);
+        })
+        .build();
+
+    schedule.execute((&mut frame,)).unwrap();
+
+    let mut vals = frame
+        .query::<&i32>()
+        .iter()
+        .map(|(_, val)| *val)
+        .collect::<Vec<_>>();
+    vals.sort();
+    assert_eq!(vals, [2, 10, 30]);
+}
+
+#[test]
+fn or_access() {
+    let mut frame = Frame::default();
+    frame.spawn((1_i32,));
+    frame.spawn((1.0_f32,));
+    frame.spawn((1_i32, 1.0_f32));
+    frame.spawn((1_u8,));
+
+    let mut schedule = Schedule::builder()
+        .add_system(|w: SubWorld<Or<&i32, &f32>>| {
+            assert_eq!(w.query::<Or<&i32, &f32>>().iter().count(), 3);
+            assert!(w.has::<&i32>() && w.has::<&f32>());
+        })
+        .add_system(|_: SubWorld<&i32>| {})
+        .add_system(|_: SubWorld<&mut f32>| {})
+        .build();
+
+    assert_eq!(schedule.batches()[0].systems().len(), 2);
+
+    schedule.execute((&mut frame,)).unwrap();
+
+    // Both sides are borrowed, like reading the pair
+    let schedule = Schedule::builder()
+        .add_system(|_: SubWorld<Or<&i32, &f32>>| {})
+        .add_system(|_: SubWorld<(&i32, &f32)>| {})
+        .add_system(|_: SubWorld<&mut f32>| {})
+        .build();
+
+    assert_eq!(schedule.batches()[0].systems().len(), 2);
+    assert_eq!(schedule.batches()[1].systems().len(), 1);
+}
+
+#[test]
+fn read_only() {
+    fn sum(w: &SubWorldRef<ReadOnly<(&mut i32, &f32)>>) -> i32 {
+        assert!(w.has::<&i32>());
+        assert!(!w.has::<&mut i32>());
+        assert!(w.try_query::<&mut i32>().is_err());
+
+        w.query::<&i32>().iter().map(|(_, val)| *val).sum()
+    }
+
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32,));
+    frame.spawn((2_i32, 1.0_f32));
+
+    let w = SubWorldRef::<(&mut i32, &f32)>::new(&frame);
+    let read_only = w.as_read_only();
+
+    assert_eq!(sum(&read_only), 3);
+    assert!(matches!(
+        read_only.get_mut::<i32>(a),
+        Err(Error::IncompatibleSubworld { .. })
+    ));
+    assert!(matches!(
+        read_only.try_get_mut::<i32>(a),
+        Err(Error::IncompatibleSubworld { .. })
+    ));
+    assert_eq!(*w.get_mut::<i32>(a).unwrap(), 1);
+    assert!(
+        <ReadOnly<(&mut i32, &f32)> as borrow::ComponentBorrow>::borrows()
+            .iter()
+            .all(|access| !access.exclusive())
+    );
+}
+
+#[test]
+fn dump() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, String::from("a"), 1.0_f32));
+    frame.spawn((1.0_f32,));
+
+    let w = SubWorldRef::<(&i32, Option<&mut String>)>::new(&frame);
+
+    assert_eq!(
+        format!("{:?}", w.dump()),
+        format!("{{{a:?}: {{i32: 1, alloc::string::String: \"a\"}}}}")
+    );
+    assert_eq!(
+        w.dump().to_string(),
+        format!("{a:?}: {{i32: 1, alloc::string::String: \"a\"}}\n")
+    );
+}
+
+#[test]
+fn progressive_query() {
+    let mut frame = Frame::default();
+    frame.spawn_batch((0..5).map(|_| (0_i32,)));
+
+    let mut progress = ProgressiveQuery::new();
+    let mut finished = Vec::new();
+
+    for _ in 0..4 {
+        let w = SubWorldRef::<&mut i32>::new(&frame);
+        let done = progress
+            .for_each::<&mut i32>(&w, 2, |_, val| *val += 1)
+            .unwrap();
+
+        finished.push(done);
+    }
+
+    assert_eq!(finished, [false, false, true, false]);
+    assert_eq!(progress.cursor(), 2);
+
+    let mut vals = frame
+        .query::<&i32>()
+        .iter()
+        .map(|(_, val)| *val)
+        .collect::<Vec<_>>();
+    vals.sort();
+    assert_eq!(vals, [1, 1, 1, 2, 2]);
+
+    let w = SubWorldRef::<&i32>::new(&frame);
+    assert!(progress.for_each::<&mut i32>(&w, 2, |_, _| {}).is_err());
+}
+
+#[test]
+fn declare_queries() {
+    let mut builder = Schedule::builder();
+    builder
+        .add_system(|_: SubWorld<(&i32, &mut f32)>| {})
+        .declare_queries::<(&i32, &f32)>()
+        .declare_queries::<&mut f32>()
+        .add_exclusive_system(|_: &mut Frame| {})
+        .declare_queries::<&mut u8>();
+
+    assert!(builder.validate().is_empty());
+
+    builder
+        .add_system_named("reader", |_: SubWorld<&i32>| {})
+        .declare_queries::<&mut i32>();
+
+    assert_eq!(
+        builder.validate(),
+        [ValidationError::IncompatibleQuery {
+            system: "reader".into(),
+            query: std::any::type_name::<&mut i32>(),
+        }]
+    );
+
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| builder.build()));
+    assert!(result.is_err());
+}
+
+#[test]
+fn declare_queries_of_data() {
+    let mut builder = Schedule::builder();
+    builder
+        .add_system_named("resource", |_: borrow::MaybeRead<i32>| {})
+        .declare_queries::<&i32>();
+
+    assert_eq!(
+        builder.validate(),
+        [ValidationError::IncompatibleQuery {
+            system: "resource".into(),
+            query: std::any::type_name::<&i32>(),
+        }]
+    );
+}
+
+#[test]
+fn rwlock_subworld() {
+    let mut frame = Frame::default();
+    frame.spawn((1_i32, 1.0_f32));
+
+    let lock = std::sync::RwLock::new(frame);
+    let w = SubWorldRwLock::<(&i32, &f32)>::new(lock.read().unwrap());
+    assert_eq!(w.query::<&i32>().iter().count(), 1);
+
+    let split: SubWorldRef<&f32> = (&w).into();
+    assert_eq!(split.query::<&f32>().iter().count(), 1);
+    assert!(split.try_query::<&i32>().is_err());
+}
+
+#[test]
+#[cfg(feature = "parking_lot")]
+fn parking_lot_subworld() {
+    let mut frame = Frame::default();
+    frame.spawn((1_i32, 1.0_f32));
+
+    let lock = parking_lot::RwLock::new(frame);
+    let w = SubWorldParkingLot::<(&i32, &f32)>::new(lock.read());
+
+    let split = w.split::<&f32>().unwrap();
+    assert_eq!(split.query::<&f32>().iter().count(), 1);
+
+    drop((w, split));
+    assert!(lock.try_write().is_some());
+}
+
+#[test]
+fn archetype_columns() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, 1.0_f32));
+    let b = frame.spawn((2_i32, 2.0_f32));
+
+    let w = SubWorldRef::<(&mut i32, &f32, &u8)>::new(&frame);
+    let archetype = w
+        .archetypes()
+        .find(|archetype| archetype.has::<f32>())
+        .unwrap();
+
+    assert_eq!(archetype.ids(), [a.id(), b.id()]);
+
+    {
+        let mut column = archetype.column_mut::<i32>().unwrap().unwrap();
+        let ptr = column.as_mut_ptr();
+        unsafe { *ptr.add(1) = 20 };
+    }
+
+    assert_eq!(*archetype.column::<f32>().unwrap().unwrap(), [1.0, 2.0]);
+    assert!(archetype.column::<u8>().unwrap().is_none());
+    assert!(archetype.column_mut::<f32>().is_err());
+    assert!(archetype.column::<String>().is_err());
+    assert_eq!(*frame.get::<&i32>(b).unwrap(), 20);
+}
+
+#[test]
+fn query_sorted() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((2_i32, 1.0_f32));
+    let b = frame.spawn((1_i32,));
+    let c = frame.spawn((2_i32,));
+
+    let mut schedule = Schedule::builder()
+        .add_system(
+            move |w: SubWorld<&i32>, mut sorted: Local<SortedQuery<i32>>| {
+                let entities = w
+                    .query_sorted::<&i32, _>(&mut sorted, |val| *val)
+                    .unwrap()
+                    .collect::<Vec<_>>();
+
+                assert_eq!(entities[0], b);
+                assert_eq!(entities.len(), 3);
+                assert!(entities[1..].contains(&a) && entities[1..].contains(&c));
+
+                assert!(w.query_sorted::<&f32, _>(&mut sorted, |_| 0).is_err());
+            },
+        )
+        .build();
+
+    schedule.execute((&mut frame,)).unwrap();
+    schedule.execute((&mut frame,)).unwrap();
+}
+
+#[test]
+#[cfg(feature = "rand")]
+fn sample() {
+    use rand::SeedableRng;
+
+    let mut frame = Frame::default();
+    let entities = frame.spawn_batch((0..10).map(|i| (i,))).collect::<Vec<_>>();
+    frame.spawn((1.0_f32,));
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let w = SubWorldRef::<&i32>::new(&frame);
+
+    let sampled = w.sample::<&i32>(&mut rng, 4).unwrap();
+    assert_eq!(sampled.len(), 4);
+    assert!(sampled.iter().all(|entity| entities.contains(entity)));
+    assert!(sampled.windows(2).all(|pair| pair[0] != pair[1]));
+
+    assert_eq!(w.sample::<&i32>(&mut rng, 20).unwrap(), entities);
+    assert!(w.sample::<&f32>(&mut rng, 1).is_err());
+}
+
+#[test]
+fn columns() {
+    let mut frame = Frame::default();
+    frame.spawn_batch((0..3).map(|i| (i, 1_u8)));
+    frame.spawn_batch((0..2).map(|i| (i, 2_u8, 1.0_f32)));
+    frame.spawn((5_i32,));
+
+    let w = SubWorldRef::<(&mut i32, &u8)>::new(&frame);
+
+    let mut lens = Vec::new();
+    for (mut vals, scales) in w.columns::<(&mut i32, &u8)>().unwrap() {
+        lens.push(vals.len());
+        for (val, scale) in vals.iter_mut().zip(scales.iter()) {
+            *val *= *scale as i32;
+        }
+    }
+
+    lens.sort_unstable();
+    assert_eq!(lens, [2, 3]);
+    assert_eq!(
+        w.query::<&i32>().iter().map(|(_, val)| *val).sum::<i32>(),
+        3 + 2 + 5
+    );
+    assert!(w.columns::<&mut u8>().is_err());
+}
+
+#[test]
+#[cfg(feature = "macros")]
+fn system_data() {
+    struct Gravity(f32);
+
+    #[derive(SystemData)]
+    struct PhysicsData<'a> {
+        world: SubWorld<'a, (&'a f32, &'a mut i32)>,
+        gravity: Read<'a, Gravity>,
+    }
+
+    let mut frame = Frame::default();
+    frame.spawn((2.0_f32, 0_i32));
+
+    let mut gravity = Gravity(-1.0);
+
+    let mut schedule = Schedule::builder()
+        .add_system(|data: PhysicsData| {
+            for (_, (mass, val)) in data.world.query::<(&f32, &mut i32)>().iter() {
+                *val = (*mass * data.gravity.0) as i32;
+            }
+        })
+        .add_system(|_: Write<Gravity>| {})
+        .build();
+
+    assert!(<PhysicsData as borrow::ComponentBorrow>::has::<&mut i32>());
+    // The gravity is borrowed through the struct
+    assert_eq!(schedule.batches()[0].systems().len(), 1);
+
+    schedule.execute((&mut frame, &mut gravity)).unwrap();
+
+    assert!(frame
+        .query_mut::<&i32>()
+        .into_iter()
+        .all(|(_, val)| *val == -2));
+}
+
+#[test]
+fn views() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, 1.0_f32));
+    let b = frame.spawn((2_i32,));
+    let c = frame.spawn((3.0_f32,));
+
+    let w = SubWorldRef::<(&mut i32, &f32)>::new(&frame);
+
+    {
+        let view = w.view::<&i32>();
+        assert_eq!(*view.get(b).unwrap(), 2);
+        assert!(view.contains(a) && !view.contains(c));
+        assert!(matches!(
+            view.get(c),
+            Err(Error::UnsatisfiedQuery(entity, _)) if entity == c
+        ));
+    }
+
+    {
+        let mut view = w.view_mut::<&mut i32>();
+        let [x, y] = view.get_many_mut([a, b]).unwrap();
+        std::mem::swap(x, y);
+
+        assert!(matches!(
+            view.get_many_mut([a, a]),
+            Err(Error::DuplicateEntity(entity)) if entity == a
+        ));
+    }
+
+    assert_eq!(*w.get::<i32>(a).unwrap(), 2);
+    assert!(w.try_view::<&mut f32>().is_err());
+
+    frame.despawn(b).unwrap();
+    let w = SubWorldRef::<&i32>::new(&frame);
+    assert!(matches!(
+        w.view::<&i32>().get(b),
+        Err(Error::NoSuchEntity(entity)) if entity == b
+    ));
+}
+
+#[test]
+fn tracked_access() {
+    let mut frame = Frame::default();
+    frame.spawn((1_i32, 2_u32));
+
+    let increment = |w: SubWorld<&mut i32>| {
+        for (_, val) in w.query::<&mut i32>().iter() {
+            *val += 1;
+        }
+    };
+
+    let mut schedule = Schedule::builder()
+        .add_system(increment)
+        .add_system(|w: SubWorld<TrackedAccess>| {
+            assert_eq!(w.query::<&u32>().iter().count(), 1);
+        })
+        .strict_access(true)
+        .build();
+
+    // The tracked system does not declare any components
+    assert_eq!(schedule.batches()[0].systems().len(), 2);
+    schedule.execute_seq((&mut frame,)).unwrap();
+
+    let mut schedule = Schedule::builder()
+        .add_system(increment)
+        .add_system(|w: SubWorld<TrackedAccess>| {
+            assert_eq!(w.query::<&i32>().iter().count(), 1);
+        })
+        .strict_access(true)
+        .build();
+
+    assert!(matches!(
+        schedule.execute_seq((&mut frame,)),
+        Err(Error::TrackedAccessConflict { access, .. }) if access.id() == std::any::TypeId::of::<i32>()
+    ));
+}
+
+#[test]
+fn stats() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_u32, 1_u8));
+    frame.spawn((2_u32,));
+    frame.spawn(("hidden",));
+
+    let previous = SubWorldRef::<(&u32, &u8)>::new(&frame).stats();
+    assert_eq!(previous.archetypes().len(), 2);
+    assert_eq!(previous.entities(), 2);
+    assert_eq!(previous.memory(), 4 + 1 + 4);
+
+    frame.remove_one::<u8>(a).unwrap();
+    let stats = SubWorldRef::<(&u32, &u8)>::new(&frame).stats();
+
+    // The entity moved between archetypes
+    let churn = stats.churn_since(&previous);
+    assert_eq!((churn.added(), churn.removed()), (1, 1));
+    assert_eq!(stats.entities(), 2);
+}
+
+#[test]
+fn iter_chunks() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32,));
+    frame.spawn((2_i32,));
+    frame.spawn((3_i32, 1_u8));
+
+    let w = SubWorldRef::<&i32>::new(&frame);
+    let chunks = w.iter_chunks::<&i32>(8).unwrap();
+
+    // Chunks do not span archetypes
+    assert_eq!(chunks.iter().map(QueryChunk::len).sum::<usize>(), 3);
+    assert_eq!(chunks.len(), 2);
+    assert!(w.iter_chunks::<&mut i32>(8).is_err());
+
+    frame.despawn(a).unwrap();
+    let w = SubWorldRef::<&i32>::new(&frame);
+
+    let mut visited = Vec::new();
+    for chunk in &chunks {
+        chunk.for_each(&w, |_, val| visited.push(*val)).unwrap();
+    }
+
+    visited.sort_unstable();
+    assert_eq!(visited, [2, 3]);
+}
+
+#[test]
+fn despawn_many() {
+    let mut frame = Frame::default();
+    let entities: Vec<_> = frame.spawn_batch((0..4).map(|i| (i,))).collect();
+
+    let mut schedule = Schedule::builder()
+        .add_system({
+            let entities = entities.clone();
+            move |mut cmd: Write<CommandBuffer>| {
+                cmd.despawn(entities[0]);
+                cmd.despawn_many(entities[2..].iter().copied());
+            }
+        })
+        .build();
+
+    schedule.execute_seq((&mut frame,)).unwrap();
+
+    assert_eq!(frame.len(), 1);
+    assert!(frame.contains(entities[1]));
+}
+
+#[test]
+fn insert_one_remove_bundle() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32, 2_u32, 3_u8));
+
+    let mut cmd = CommandBuffer::new();
+    cmd.insert_one(a, "name");
+    cmd.remove::<(i32, u32)>(a);
+    cmd.execute(&mut frame);
+
+    let entity = frame.entity(a).unwrap();
+    assert!(entity.has::<&str>() && entity.has::<u8>());
+    assert!(!entity.has::<i32>() && !entity.has::<u32>());
+}
+
+#[test]
+fn spawn_batch_deferred() {
+    let mut frame = Frame::default();
+
+    let mut cmd = CommandBuffer::new();
+    cmd.spawn_batch((0..100).map(|i| (i, i as f32)));
+    assert!(frame.is_empty());
+
+    cmd.execute(&mut frame);
+
+    assert_eq!(frame.len(), 100);
+    assert_eq!(frame.query_mut::<(&i32, &f32)>().into_iter().count(), 100);
+}
+
+#[test]
+fn commands() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32,));
+
+    let mut schedule = Schedule::builder()
+        .add_system(move |mut cmd: Commands| cmd.insert_one(a, 2_u32))
+        .add_system(|mut cmd: Commands| cmd.spawn((3_u32,)))
+        .add_system(|_: Commands| {})
+        .build();
+
+    // Each system records into its own buffer
+    assert_eq!(schedule.batches()[0].systems().len(), 3);
+
+    schedule.execute((&mut frame,)).unwrap();
+
+    assert_eq!(*frame.get::<&u32>(a).unwrap(), 2);
+    assert_eq!(frame.query_mut::<&u32>().into_iter().count(), 2);
+}
+
+#[test]
+fn commands_order() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32,));
+
+    // The buffers are applied in the order the systems were added
+    let mut schedule = Schedule::builder()
+        .add_system(move |mut cmd: Commands| cmd.insert_one(a, 2_u32))
+        .add_system(move |mut cmd: Commands| cmd.insert_one(a, 3_u32))
+        .add_system(move |mut cmd: Commands| cmd.insert_one(a, 4_u32))
+        .build();
+
+    for _ in 0..10 {
+        schedule.execute((&mut frame,)).unwrap();
+        assert_eq!(*frame.get::<&u32>(a).unwrap(), 4);
+    }
+}
+
+#[test]
+fn commands_failed() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32,));
+
+    let mut schedule = Schedule::builder()
+        .add_system(move |mut cmd: Commands| cmd.despawn(a))
+        .add_system(move |mut cmd: Commands| cmd.despawn(a))
+        .build();
+
+    let error = schedule.execute((&mut frame,)).unwrap_err();
+
+    match error {
+        Error::SystemError(_, source) => assert!(matches!(
+            source.downcast_ref::<Error>(),
+            Some(Error::CommandsFailed(errors)) if errors == &[CommandError::DespawnFailed(0, a)]
+        )),
+        error => panic!("Unexpected error: {error}"),
+    }
+
+    assert!(!frame.contains(a));
+}
+
+#[test]
+fn deferred_entities() {
+    let mut frame = Frame::default();
+    let existing = frame.spawn((0_i32,));
+
+    let mut cmd = CommandBuffer::new();
+    let a = cmd.spawn_deferred((1_i32, 1_u8));
+    let b = cmd.spawn_deferred((2_i32,));
+
+    cmd.insert_one(a, "a");
+    cmd.remove_one::<u8>(a);
+    cmd.insert_one(existing, "existing");
+    cmd.despawn(b);
+
+    cmd.execute(&mut frame);
+
+    let mut names: Vec<_> = frame
+        .query_mut::<(&i32, &&str)>()
+        .into_iter()
+        .map(|(_, (val, name))| (*val, *name))
+        .collect();
+    names.sort_unstable();
+
+    assert_eq!(names, [(0, "existing"), (1, "a")]);
+    assert_eq!(frame.len(), 2);
+    assert_eq!(frame.query_mut::<&u8>().into_iter().count(), 0);
+}
+
+#[test]
+fn foreign_deferred_entities() {
+    let mut frame = Frame::default();
+
+    let mut cmd = CommandBuffer::new();
+    let mut other = CommandBuffer::new();
+    let a = cmd.spawn_deferred((1_i32,));
+    let b = other.spawn_deferred((2_i32,));
+
+    cmd.insert_one(b, "b");
+    other.insert_one(b, "b");
+    cmd.append(&mut other);
+
+    // The placeholders of other were moved along with its commands
+    other.despawn(b);
+    assert_eq!(
+        other.try_execute(&mut frame).unwrap_err(),
+        [CommandError::ForeignEntity(0)]
+    );
+
+    cmd.try_execute(&mut frame).unwrap_err();
+    assert_eq!(frame.query_mut::<&&str>().into_iter().count(), 1);
+
+    // The placeholders are invalidated once applied
+    cmd.despawn(a);
+    assert_eq!(
+        cmd.try_execute(&mut frame).unwrap_err(),
+        [CommandError::ForeignEntity(0)]
+    );
+    assert_eq!(frame.len(), 2);
+}
+
+#[test]
+fn append_commandbuffer() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32,));
+
+    let mut cmd = CommandBuffer::new();
+    let mut other = CommandBuffer::new();
+
+    cmd.remove_one::<i32>(a);
+    other.insert_one(a, 2_i32);
+    other.spawn((3_i32,));
+    cmd.append(&mut other);
+
+    // The other buffer is left empty
+    other.execute(&mut frame);
+    assert_eq!(frame.len(), 1);
+
+    cmd.execute(&mut frame);
+
+    assert_eq!(*frame.get::<&i32>(a).unwrap(), 2);
+    assert_eq!(frame.len(), 2);
+}
+
+#[test]
+fn despawn_matching() {
+    struct Dead;
+
+    let mut frame = Frame::default();
+    let alive = frame.spawn((1_i32,));
+    let dying = frame.spawn((2_i32,));
+    frame.spawn((3_i32, Dead));
+
+    let mut cmd = CommandBuffer::new();
+    cmd.insert_one(dying, Dead);
+    cmd.despawn_matching::<With<(), &Dead>>();
+    cmd.execute(&mut frame);
+
+    assert_eq!(frame.len(), 1);
+    assert!(frame.contains(alive));
+}
+
+#[test]
+fn insert_or_spawn() {
+    let mut frame = Frame::default();
+    let alive = frame.spawn((1_i32,));
+    let ignored = frame.spawn((2_i32,));
+    let respawned = frame.spawn((3_i32,));
+
+    let mut cmd = CommandBuffer::new();
+    cmd.insert_or_ignore(alive, (1_u8,));
+    cmd.insert_or_ignore(ignored, (2_u8,));
+    cmd.insert_or_spawn(respawned, (3_u8,));
+
+    frame.despawn(ignored).unwrap();
+    frame.despawn(respawned).unwrap();
+    cmd.execute(&mut frame);
+
+    assert!(!frame.contains(ignored));
+    assert_eq!(*frame.get::<&u8>(alive).unwrap(), 1);
+    assert_eq!(*frame.get::<&u8>(respawned).unwrap(), 3);
+    assert!(frame.get::<&i32>(respawned).is_err());
+
+    // The id of a despawned entity is recycled by an unrelated entity
+    let mut cmd = CommandBuffer::new();
+    cmd.insert_or_spawn(respawned, (4_u8,));
+
+    frame.despawn(respawned).unwrap();
+    let recycled = frame.spawn((5_i32,));
+    assert_eq!(recycled.id(), respawned.id());
+
+    let errors = cmd.try_execute(&mut frame).unwrap_err();
+    assert_eq!(errors, [CommandError::EntityRecycled(0, respawned)]);
+    assert_eq!(*frame.get::<&i32>(recycled).unwrap(), 5);
+    assert!(!frame.contains(respawned));
+
+    // The id is recycled by a reserved entity
+    let mut cmd = CommandBuffer::new();
+    cmd.insert_or_spawn(recycled, (6_u8,));
+
+    frame.despawn(recycled).unwrap();
+    let reserved = frame.reserve_entity();
+    assert_eq!(reserved.id(), recycled.id());
+
+    let errors = cmd.try_execute(&mut frame).unwrap_err();
+    assert_eq!(errors, [CommandError::EntityRecycled(0, recycled)]);
+    assert!(frame.contains(reserved));
+}
+
+#[test]
+fn resource_commands() {
+    struct Level(u32);
+
+    let mut schedule = Schedule::builder()
+        .add_system(|mut cmd: Write<CommandBuffer>| {
+            cmd.insert_resource(Level(2));
+            cmd.remove_resource::<&'static str>();
+        })
+        .add_system(|mut cmd: Commands| cmd.insert_resource(3_u32))
+        .flush()
+        .add_system(
+            |level: Read<Level>, name: borrow::MaybeRead<&'static str>, value: Read<u32>| {
+                assert_eq!(level.0, 2);
+                assert!(name.option().is_none());
+                assert_eq!(*value, 3);
+            },
+        )
+        .build();
+
+    let mut resources = Resources::new();
+    resources.insert(Level(1));
+    resources.insert("name");
+    resources.insert(1_u32);
+
+    schedule.execute(&mut resources).unwrap();
+
+    assert_eq!(resources.get::<Level>().map(|level| level.0), Some(2));
+    assert_eq!(resources.get::<u32>(), Some(&3));
+    assert!(!resources.contains::<&'static str>());
+}
+
+#[test]
+fn resource_commands_without_resources() {
+    let mut frame = Frame::default();
+
+    let mut schedule = Schedule::builder()
+        .add_system(|mut cmd: Write<CommandBuffer>| cmd.insert_resource(1_u32))
+        .build();
+
+    assert!(matches!(
+        schedule.execute((&mut frame,)),
+        Err(Error::SystemError(..))
+    ));
+
+    let mut schedule = Schedule::builder()
+        .add_system(|mut cmd: Commands| cmd.remove_resource::<u32>())
+        .build();
+
+    assert!(matches!(
+        schedule.execute((&mut frame,)),
+        Err(Error::SystemError(..))
+    ));
+}
+
+#[test]
+fn try_execute() {
+    let mut frame = Frame::default();
+    let alive = frame.spawn((1_i32,));
+    let dead = frame.spawn((2_i32,));
+
+    let mut other = CommandBuffer::new();
+    other.despawn(dead);
+
+    let mut cmd = CommandBuffer::new();
+    cmd.insert_one(alive, 1_u8);
+    cmd.insert_one(dead, 2_u8);
+    cmd.remove_one::<u16>(alive);
+    cmd.append(&mut other);
+
+    frame.despawn(dead).unwrap();
+
+    let errors = cmd.try_execute(&mut frame).unwrap_err();
+    assert_eq!(
+        errors,
+        [
+            CommandError::NoSuchEntity(1, dead),
+            CommandError::MissingComponent(2, alive, std::any::type_name::<u16>()),
+            CommandError::DespawnFailed(3, dead),
+        ]
+    );
+
+    assert_eq!(*frame.get::<&u8>(alive).unwrap(), 1);
+    assert!(cmd.try_execute(&mut frame).is_ok());
+}
+
+#[test]
+fn command_order() {
+    let mut frame = Frame::default();
+    let recorded = frame.spawn((1_u8,));
+    let relaxed = frame.spawn((1_u8,));
+
+    let record = |cmd: &mut CommandBuffer, entity| {
+        cmd.remove_one::<u8>(entity);
+        cmd.insert_one(entity, 2_u8);
+        cmd.despawn(entity);
+        cmd.insert_or_spawn(entity, (3_i32,));
+    };
+
+    let mut cmd = CommandBuffer::new();
+    assert_eq!(cmd.order(), CommandOrder::Recorded);
+    record(&mut cmd, recorded);
+    cmd.execute(&mut frame);
+
+    cmd.set_order(CommandOrder::Relaxed);
+    record(&mut cmd, relaxed);
+    cmd.execute(&mut frame);
+
+    // Respawned without the inserted component
+    assert_eq!(*frame.get::<&i32>(recorded).unwrap(), 3);
+    assert!(frame.get::<&u8>(recorded).is_err());
+    // Despawned after all other commands
+    assert!(!frame.contains(relaxed));
+}
+
+#[test]
+fn command_order_runs() {
+    let mut frame = Frame::default();
+    let a = frame.spawn((1_i32,));
+
+    let mut cmd = CommandBuffer::new();
+    cmd.spawn((2_i32,));
+    cmd.insert_one(a, 1_u8);
+    cmd.write(|frame: &mut Frame| assert_eq!(frame.len(), 2));
+    cmd.despawn(a);
+    cmd.insert_one(a, 2_u8);
+    cmd.spawn((3_i32,));
+    cmd.write(|frame: &mut Frame| assert_eq!(frame.len(), 2));
+
+    assert_eq!(
+        cmd.try_execute(&mut frame).unwrap_err(),
+        [CommandError::NoSuchEntity(4, a)]
+    );
+
+    // The buffers of the runs are reused
+    cmd.spawn((4_i32,));
+    cmd.execute(&mut frame);
+    assert_eq!(frame.len(), 3);
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn command_stream() {
+    let mut frame = Frame::default();
+    let existing = frame.spawn((1_i32, 1.0_f32));
+
+    let mut registry = ComponentRegistry::new();
+    registry
+        .register_deserialize::<i32>("i32")
+        .register_deserialize::<String>("name")
+        .register::<f32>("f32");
+
+    let mut stream = CommandStream::new();
+    let spawned = stream.spawn((2_i32, String::from("spawned")));
+    stream.insert_one(existing, String::from("existing"));
+    stream.remove_one::<f32>(existing);
+    stream.despawn(spawned);
+
+    let value = stream
+        .serialize(serde_json::value::Serializer, &registry)
+        .unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!([
+            { "Spawn": { "i32": 2, "name": "spawned" } },
+            { "Insert": [{ "Entity": existing.to_bits().get() }, { "name": "existing" }] },
+            { "Remove": [{ "Entity": existing.to_bits().get() }, "f32"] },
+            { "Despawn": { "Deferred": 0 } },
+        ])
+    );
+
+    let mut cmd = CommandBuffer::new();
+    CommandStream::deserialize(value, &registry)
+        .unwrap()
+        .record(&mut cmd);
+    cmd.execute(&mut frame);
+
+    assert_eq!(frame.len(), 1);
+    assert_eq!(*frame.get::<&String>(existing).unwrap(), "existing");
+    assert!(frame.get::<&f32>(existing).is_err());
+
+    // Components which can not be deserialized, and deferred entities which
+    // are not yet spawned, are rejected
+    let mut stream = CommandStream::new();
+    stream.insert_one(existing, 2.0_f32);
+    let value = stream
+        .serialize(serde_json::value::Serializer, &registry)
+        .unwrap();
+    assert!(CommandStream::deserialize(value, &registry).is_err());
+
+    let value = serde_json::json!([{ "Despawn": { "Deferred": 0 } }]);
+    assert!(CommandStream::deserialize(value, &registry).is_err());
+}
+
+#[test]
+fn hierarchy() {
+    let mut frame = Frame::default();
+    let a = frame.spawn(());
+    let b = frame.spawn(());
+
+    let mut cmd = CommandBuffer::new();
+    let child = cmd.spawn_child(a, (1_i32,));
+    cmd.spawn_child(child, (2_i32,));
+    cmd.execute(&mut frame);
+
+    let (child, _) = frame
+        .query_mut::<&i32>()
+        .into_iter()
+        .find(|(_, v)| **v == 1)
+        .unwrap();
+    let grandchild = frame.get::<&Children>(child).unwrap()[0];
+
+    assert_eq!(&frame.get::<&Children>(a).unwrap()[..], [child]);
+    assert_eq!(frame.get::<&Parent>(child).unwrap().entity(), a);
+    assert_eq!(frame.get::<&Parent>(grandchild).unwrap().entity(), child);
+
+    cmd.set_parent(child, b);
+    cmd.set_parent(b, grandchild);
+    let errors = cmd.try_execute(&mut frame).unwrap_err();
+
+    assert_eq!(errors, [CommandError::CyclicParent(1, b)]);
+    assert!(frame.get::<&Children>(a).unwrap().is_empty());
+    assert_eq!(&frame.get::<&Children>(b).unwrap()[..], [child]);
+    assert_eq!(frame.get::<&Parent>(child).unwrap().entity(), b);
+}
+
+#[test]
+fn spawn_reserved() {
+    let mut frame = Frame::default();
+
+    let mut cmd = CommandBuffer::new();
+    let mut relaxed = CommandBuffer::new();
+    relaxed.set_order(CommandOrder::Relaxed);
+
+    let a = cmd.spawn_reserved(&frame, (1_i32,));
+    let b = relaxed.spawn_reserved(&frame, (a,));
+    cmd.insert_one(b, 2_i32);
+
+    relaxed.execute(&mut frame);
+    cmd.execute(&mut frame);
+
+    assert_eq!(*frame.get::<&i32>(a).unwrap(), 1);
+    assert_eq!(*frame.get::<&moss_hecs::Entity>(b).unwrap(), a);
+    assert_eq!(*frame.get::<&i32>(b).unwrap(), 2);
+}
@@ -245,7 +245,9 @@ fn atomic() {
     let empty = a.to_empty();
 
     // Count total number of entities
-    assert_eq!(empty.query::<()>().iter().count(), 2);
+    assert_eq!(empty.len(), 2);
+    assert_eq!(empty.count::<&f32>(), 1);
+    assert!(!empty.is_empty());
 
     assert!(b.native_query().iter().map(|(_, val)| *val).eq(["a", "b"]));
 }